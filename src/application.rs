@@ -1,39 +1,107 @@
 use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::time::{Duration, Instant};
 use err_derive::Error;
-use openvr::{System, Compositor, RenderModels, Context, InitError, tracked_device_index, TrackedDeviceClass, render_models, TrackedControllerRole};
+use openvr::{System, RenderModels, Context, InitError, tracked_device_index, render_models, property, TrackedDevicePoses};
 use openvr::compositor::CompositorError;
 use openvr::system::TrackedPropertyError;
-use image::{ImageError, DynamicImage, ImageBuffer};
+use image::ImageError;
 use obj::{load_obj, ObjError, TexturedVertex, Obj};
-use cgmath::{Matrix4, Vector2};
+use cgmath::{Matrix4, Vector2, Deg};
+use log::{info, warn};
 
-use crate::renderer::{Renderer, RendererCreationError, RenderError, model};
+use crate::renderer::{Renderer, RendererBuilder, DeviceSelector, RendererCreationError, RenderError, model};
+use crate::renderer::target::FlatTarget;
 use crate::renderer::model::{Model, ModelError, Vertex};
-use crate::openvr_vulkan::mat4;
+use crate::input::{Input, InputError};
 
+// Size and vertical field of view of the window used when no HMD is available
+const FLAT_SIZE: (u32, u32) = (1280, 720);
+const FLAT_FOV: Deg<f32> = Deg(60.0);
+
+// Head pose used when no HMD is available, standing at the origin
+const FLAT_POSE: [[f32; 4]; 3] = [
+	[1.0, 0.0, 0.0, 0.0],
+	[0.0, 1.0, 0.0, 1.7],
+	[0.0, 0.0, 1.0, 0.0],
+];
+
+// Field order matters, OpenVR has to be shutdown before the renderer is dropped
 pub struct Application {
+	vr: Option<VR>,
+	renderer: Renderer,
+	gpu_times: Option<GpuTimesLog>,
+}
+
+// Logs the GPU time of each eye once per second
+struct GpuTimesLog {
+	last: Instant,
+}
+
+struct VR {
 	context: Context,
 	system: System,
-	compositor: Compositor,
 	render_models: RenderModels,
-	renderer: Renderer,
+	// None without an action manifest
+	input: Option<Input>,
 }
 
 impl Application {
-	pub fn new(device: Option<usize>, debug: bool) -> Result<Application, ApplicationCreationError> {
-		let context = unsafe { openvr::init(openvr::ApplicationType::Scene) }?;
+	pub fn new(device: DeviceSelector, debug: bool, msaa: u32, render_scale: f32, mirror: bool, depth_prepass: bool, gpu_times: bool) -> Result<Application, ApplicationCreationError> {
+		let builder = RendererBuilder::new().device(device)
+		                                    .debug(debug)
+		                                    .msaa(msaa)
+		                                    .render_scale(render_scale)
+		                                    .mirror(mirror)
+		                                    .gpu_times(gpu_times);
+		
+		let gpu_times = if gpu_times { Some(GpuTimesLog { last: Instant::now() }) } else { None };
+		
+		let context = match unsafe { openvr::init(openvr::ApplicationType::Scene) } {
+			Ok(context) => context,
+			Err(err) => {
+				warn!("Failed to initialize OpenVR: {}, falling back to flat screen", err);
+				
+				let mut renderer = builder.build_target(FlatTarget::new(FLAT_SIZE, FLAT_FOV))?;
+				renderer.set_depth_prepass(depth_prepass);
+				
+				return Ok(Application {
+					vr: None,
+					renderer,
+					gpu_times,
+				});
+			},
+		};
+		
 		let system = context.system()?;
-		let compositor = context.compositor()?;
 		let render_models = context.render_models()?;
+		let input = match Input::new() {
+			Ok(input) => Some(input),
+			Err(err @ InputError::ManifestNotFound) => {
+				warn!("{}, running without controller input", err);
+				None
+			},
+			Err(err) => return Err(err.into()),
+		};
 		
-		let renderer = Renderer::new(&system, context.compositor()?, device, debug)?;
+		// The renderer keeps its own handles to OpenVR
+		let mut renderer = builder.build(&system, context.compositor()?)?;
+		renderer.set_system(context.system()?);
+		renderer.set_chaperone(context.chaperone()?);
+		if let Some(input) = &input {
+			renderer.set_haptics(input.haptics());
+		}
+		renderer.set_depth_prepass(depth_prepass);
 		
 		Ok(Application {
-			context,
-			system,
-			compositor,
-			render_models,
+			vr: Some(VR {
+				context,
+				system,
+				render_models,
+				input,
+			}),
 			renderer,
+			gpu_times,
 		})
 	}
 	
@@ -46,61 +114,108 @@ impl Application {
 			let vertices: Vec<Vertex> = obj.vertices.iter().map(Into::into).collect();
 			let image = image::load_from_memory(model::SCENE_PNG)?;
 			
-			let model = Model::new(&vertices, &obj.indices, image, &self.renderer)?;
+			let model = Model::new(&vertices, &obj.indices, Some(image), &self.renderer)?;
 			
 			scene.push((model, Matrix4::from_scale(0.035)));
 		}
 		
-		let mut devices: HashMap<u32, usize> = HashMap::new();
+		if self.vr.is_none() {
+			while self.renderer.mirror_open() {
+				self.renderer.render_with_pose(&FLAT_POSE, (Vector2::new(0.0, 0.0), Vector2::new(0.0, 0.0)), &mut scene)?;
+				
+				if let Some(gpu_times) = &mut self.gpu_times {
+					gpu_times.update(&self.renderer);
+				}
+			}
+			
+			return Ok(());
+		}
+		
+		let vr = self.vr.as_ref().unwrap();
+		// Keyed by render model name, so identical controllers share a model
+		let mut device_models: HashMap<CString, Model> = HashMap::new();
+		// Devices only get a model once they've shown up in the previous frame's poses
+		let mut poses: Option<TrackedDevicePoses> = None;
 		let mut eye_rotation = (Vector2::new(0.0, 0.0), Vector2::new(0.0, 0.0));
 		
 		loop {
-			let poses = self.compositor.wait_get_poses()?;
+			let mut devices = HashMap::new();
 			
-			for i in 0..poses.render.len() as u32 {
-				if self.system.tracked_device_class(i) != TrackedDeviceClass::Invalid
-				&& self.system.tracked_device_class(i) != TrackedDeviceClass::HMD {
-					if devices.contains_key(&i) {
-						scene[*devices.get(&i).unwrap()].1 = mat4(poses.render[i as usize].device_to_absolute_tracking());
-					} else if let Some(model) = self.render_models.load_render_model(&self.system.string_tracked_device_property(i, 1003)?)? {
-						if let Some(texture) = self.render_models.load_texture(model.diffuse_texture_id().unwrap())? {
-							let vertices: Vec<Vertex> = model.vertices().iter().map(Into::into).collect();
-							let indices = model.indices();
-							let size = texture.dimensions();
-							let image = DynamicImage::ImageRgba8(ImageBuffer::from_raw(size.0 as u32, size.1 as u32, texture.data().into()).unwrap());
-							
-							let model = Model::new(&vertices, indices, image, &self.renderer)?;
-							
-							devices.insert(i, scene.len());
-							scene.push((model, mat4(poses.render[i as usize].device_to_absolute_tracking())));
-							println!("Loaded {:?}", self.system.tracked_device_class(i));
-						} else { break }
-					} else { break }
+			for (i, pose) in poses.iter().flatten().enumerate() {
+				let i = i as u32;
+				
+				// Devices come and go at runtime, the renderer leaves out disconnected or untracked ones
+				if i == tracked_device_index::HMD || !pose.device_is_connected() { continue }
+				
+				// Not every device has a render model
+				let name = match vr.system.string_tracked_device_property(i, property::RenderModelName_String) {
+					Ok(name) => name,
+					Err(_) => continue,
+				};
+				
+				if !device_models.contains_key(&name) {
+					match vr.load_render_model(&name, &self.renderer)? {
+						Some(model) => {
+							info!("Loaded render model {:?} for {:?}", name, vr.system.tracked_device_class(i));
+							device_models.insert(name.clone(), model);
+						},
+						None => continue,
+					}
 				}
+				
+				devices.insert(i, device_models[&name].clone());
 			}
 			
-			if let Some(i) = self.system.tracked_device_index_for_controller_role(TrackedControllerRole::LeftHand) {
-				if let Some(state) = self.system.controller_state(i) {
-					eye_rotation.0 += Vector2::new(-state.axis[0].y, state.axis[0].x) / 100.0;
-				}
-			}
-
-			if let Some(i) = self.system.tracked_device_index_for_controller_role(TrackedControllerRole::RightHand) {
-				if let Some(state) = self.system.controller_state(i) {
-					eye_rotation.1 += Vector2::new(-state.axis[0].y, state.axis[0].x) / 100.0;
-				}
+			if let Some(input) = &vr.input {
+				let input = input.poll()?;
+				eye_rotation.0 += Vector2::new(input.left.joystick.y, -input.left.joystick.x) / 100.0;
+				eye_rotation.1 += Vector2::new(input.right.joystick.y, -input.right.joystick.x) / 100.0;
 			}
 			
-			let pose = poses.render[tracked_device_index::HMD as usize].device_to_absolute_tracking();
+			poses = Some(self.renderer.render_tracked(eye_rotation, &scene, &devices)?);
 			
-			self.renderer.render(pose, eye_rotation, &mut scene)?;
+			if let Some(gpu_times) = &mut self.gpu_times {
+				gpu_times.update(&self.renderer);
+			}
 		}
 		
 		// Ok(())
 	}
 }
 
-impl Drop for Application {
+impl GpuTimesLog {
+	fn update(&mut self, renderer: &Renderer) {
+		if self.last.elapsed() < Duration::from_secs(1) {
+			return;
+		}
+		
+		let (left, right) = renderer.last_gpu_times();
+		info!("GPU time left eye: {:.2}ms, right eye: {:.2}ms", left.as_secs_f32() * 1000.0, right.as_secs_f32() * 1000.0);
+		self.last = Instant::now();
+	}
+}
+
+impl VR {
+	// OpenVR loads render models in the background, this returns `None` until both mesh and texture are ready
+	fn load_render_model(&self, name: &CStr, renderer: &Renderer) -> Result<Option<Model>, ApplicationRunError> {
+		let model = match self.render_models.load_render_model(name)? {
+			Some(model) => model,
+			None => return Ok(None),
+		};
+		
+		let texture = match model.diffuse_texture_id() {
+			Some(id) => match self.render_models.load_texture(id)? {
+				Some(texture) => Some(texture),
+				None => return Ok(None),
+			},
+			None => None,
+		};
+		
+		Ok(Some(Model::from_render_model(&model, texture.as_ref(), renderer)?))
+	}
+}
+
+impl Drop for VR {
 	fn drop(&mut self) {
 		// Context has to be shutdown before dropping graphical API
 		unsafe { self.context.shutdown(); }
@@ -111,6 +226,7 @@ impl Drop for Application {
 pub enum ApplicationCreationError {
 	#[error(display = "{}", _0)] OpenVRInitError(#[error(source)] InitError),
 	#[error(display = "{}", _0)] RendererCreationError(#[error(source)] RendererCreationError),
+	#[error(display = "{}", _0)] InputError(#[error(source)] InputError),
 }
 
 #[derive(Debug, Error)]
@@ -122,4 +238,5 @@ pub enum ApplicationRunError {
 	#[error(display = "{}", _0)] TrackedPropertyError(#[error(source)] TrackedPropertyError),
 	#[error(display = "{}", _0)] RenderModelError(#[error(source)] render_models::Error),
 	#[error(display = "{}", _0)] ObjError(#[error(source)] ObjError),
+	#[error(display = "{}", _0)] InputError(#[error(source)] InputError),
 }
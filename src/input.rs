@@ -0,0 +1,213 @@
+use std::ffi::{CString, NulError};
+use std::os::raw::c_char;
+use std::path::Path;
+use std::{env, mem};
+use err_derive::Error;
+use openvr_sys as sys;
+use openvr::TrackedControllerRole;
+use cgmath::Vector2;
+
+// Looked up next to the executable, then in the working directory. The default bindings are found relative to it.
+const ACTION_MANIFEST: &str = "assets/actions.json";
+
+#[derive(Debug, Copy, Clone)]
+pub struct HandState {
+	pub connected: bool,
+	pub trigger_pressed: bool,
+	pub grip_pressed: bool,
+	pub menu_pressed: bool,
+	// 0 to 1
+	pub trigger: f32,
+	// Joystick or touchpad, -1 to 1 on each axis
+	pub joystick: Vector2<f32>,
+}
+
+const DISCONNECTED: HandState = HandState {
+	connected: false,
+	trigger_pressed: false,
+	grip_pressed: false,
+	menu_pressed: false,
+	trigger: 0.0,
+	joystick: Vector2::new(0.0, 0.0),
+};
+
+#[derive(Debug, Copy, Clone)]
+pub struct InputSnapshot {
+	pub left: HandState,
+	pub right: HandState,
+}
+
+// Reads the actions of assets/actions.json through SteamVR Input, users can rebind them in the SteamVR settings.
+// openvr 0.6 doesn't wrap the input interface, so this calls into openvr_sys the same way openvr does.
+// Needs an OpenVR context that outlives it.
+pub struct Input {
+	table: &'static sys::VR_IVRInput_FnTable,
+	action_set: sys::VRActionSetHandle_t,
+	trigger: sys::VRActionHandle_t,
+	trigger_click: sys::VRActionHandle_t,
+	grip: sys::VRActionHandle_t,
+	menu: sys::VRActionHandle_t,
+	joystick: sys::VRActionHandle_t,
+	haptic: sys::VRActionHandle_t,
+	left: sys::VRInputValueHandle_t,
+	right: sys::VRInputValueHandle_t,
+}
+
+// The vibration action of `Input`, for `Renderer::set_haptics`
+#[derive(Copy, Clone)]
+pub struct Haptics {
+	table: &'static sys::VR_IVRInput_FnTable,
+	action: sys::VRActionHandle_t,
+	left: sys::VRInputValueHandle_t,
+	right: sys::VRInputValueHandle_t,
+}
+
+impl Input {
+	pub fn new() -> Result<Input, InputError> {
+		let manifest = CString::new(action_manifest().ok_or(InputError::ManifestNotFound)?)?;
+		let table = input_table()?;
+		
+		check(unsafe { table.SetActionManifestPath.unwrap()(manifest.as_ptr() as *mut _) })?;
+		
+		Ok(Input {
+			table,
+			action_set: handle(table.GetActionSetHandle, "/actions/main")?,
+			trigger: handle(table.GetActionHandle, "/actions/main/in/trigger")?,
+			trigger_click: handle(table.GetActionHandle, "/actions/main/in/trigger_click")?,
+			grip: handle(table.GetActionHandle, "/actions/main/in/grip")?,
+			menu: handle(table.GetActionHandle, "/actions/main/in/menu")?,
+			joystick: handle(table.GetActionHandle, "/actions/main/in/joystick")?,
+			haptic: handle(table.GetActionHandle, "/actions/main/out/haptic")?,
+			left: handle(table.GetInputSourceHandle, "/user/hand/left")?,
+			right: handle(table.GetInputSourceHandle, "/user/hand/right")?,
+		})
+	}
+	
+	// Meant to be called once per frame, the action state only changes here
+	pub fn poll(&self) -> Result<InputSnapshot, InputError> {
+		// Zero is the invalid handle, so the set isn't restricted to a device and has no secondary set
+		let mut active: sys::VRActiveActionSet_t = unsafe { mem::zeroed() };
+		active.ulActionSet = self.action_set;
+		
+		check(unsafe { self.table.UpdateActionState.unwrap()(&mut active, mem::size_of::<sys::VRActiveActionSet_t>() as u32, 1) })?;
+		
+		Ok(InputSnapshot {
+			left: self.hand(self.left)?,
+			right: self.hand(self.right)?,
+		})
+	}
+	
+	pub fn haptics(&self) -> Haptics {
+		Haptics {
+			table: self.table,
+			action: self.haptic,
+			left: self.left,
+			right: self.right,
+		}
+	}
+	
+	fn hand(&self, source: sys::VRInputValueHandle_t) -> Result<HandState, InputError> {
+		let trigger = self.analog(self.trigger, source)?;
+		let joystick = self.analog(self.joystick, source)?;
+		let trigger_click = self.digital(self.trigger_click, source)?;
+		let grip = self.digital(self.grip, source)?;
+		let menu = self.digital(self.menu, source)?;
+		
+		// Actions are only active while a device bound to them is connected
+		if !(trigger.bActive || joystick.bActive || trigger_click.bActive || grip.bActive || menu.bActive) {
+			return Ok(DISCONNECTED);
+		}
+		
+		Ok(HandState {
+			connected: true,
+			trigger_pressed: trigger_click.bState,
+			grip_pressed: grip.bState,
+			menu_pressed: menu.bState,
+			trigger: trigger.x,
+			joystick: Vector2::new(joystick.x, joystick.y),
+		})
+	}
+	
+	fn digital(&self, action: sys::VRActionHandle_t, source: sys::VRInputValueHandle_t) -> Result<sys::InputDigitalActionData_t, InputError> {
+		let mut data: sys::InputDigitalActionData_t = unsafe { mem::zeroed() };
+		
+		check(unsafe { self.table.GetDigitalActionData.unwrap()(action, &mut data, mem::size_of::<sys::InputDigitalActionData_t>() as u32, source) })?;
+		
+		Ok(data)
+	}
+	
+	fn analog(&self, action: sys::VRActionHandle_t, source: sys::VRInputValueHandle_t) -> Result<sys::InputAnalogActionData_t, InputError> {
+		let mut data: sys::InputAnalogActionData_t = unsafe { mem::zeroed() };
+		
+		check(unsafe { self.table.GetAnalogActionData.unwrap()(action, &mut data, mem::size_of::<sys::InputAnalogActionData_t>() as u32, source) })?;
+		
+		Ok(data)
+	}
+}
+
+impl Haptics {
+	// Starts right away, frequency is in Hz and amplitude from 0 to 1
+	pub fn vibrate(&self, hand: TrackedControllerRole, duration_seconds: f32, frequency: f32, amplitude: f32) -> Result<(), InputError> {
+		let source = match hand {
+			TrackedControllerRole::LeftHand => self.left,
+			TrackedControllerRole::RightHand => self.right,
+		};
+		
+		check(unsafe { self.table.TriggerHapticVibrationAction.unwrap()(self.action, 0.0, duration_seconds, frequency, amplitude, source) })
+	}
+}
+
+// SteamVR needs an absolute path
+fn action_manifest() -> Option<String> {
+	let exe_dir = env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf));
+	
+	exe_dir.into_iter()
+	       .chain(env::current_dir().ok())
+	       .filter_map(|dir| dir.join(ACTION_MANIFEST).canonicalize().ok())
+	       // Canonical paths on Windows start with \\?\, which SteamVR doesn't understand
+	       .filter_map(|path| path.to_str().map(|path| path.trim_start_matches(r"\\?\").to_string()))
+	       .next()
+}
+
+fn input_table() -> Result<&'static sys::VR_IVRInput_FnTable, InputError> {
+	let mut magic = Vec::from(&b"FnTable:"[..]);
+	magic.extend_from_slice(sys::IVRInput_Version);
+	
+	let mut error = sys::EVRInitError_VRInitError_None;
+	let table = unsafe { sys::VR_GetGenericInterface(magic.as_ptr() as *const _, &mut error) };
+	
+	if error != sys::EVRInitError_VRInitError_None || table == 0 {
+		return Err(InputError::InterfaceNotFound);
+	}
+	
+	Ok(unsafe { &*(table as *const sys::VR_IVRInput_FnTable) })
+}
+
+// Action sets, actions and input sources all share the same lookup by path
+type HandleFn = Option<unsafe extern "C" fn(*mut c_char, *mut u64) -> sys::EVRInputError>;
+
+fn handle(get: HandleFn, path: &str) -> Result<u64, InputError> {
+	let path = CString::new(path)?;
+	let mut handle = 0;
+	
+	check(unsafe { get.unwrap()(path.as_ptr() as *mut _, &mut handle) })?;
+	
+	Ok(handle)
+}
+
+// Unlike the overlay interface, SteamVR Input has no names for its errors
+fn check(error: sys::EVRInputError) -> Result<(), InputError> {
+	if error == sys::EVRInputError_VRInputError_None {
+		Ok(())
+	} else {
+		Err(InputError::CallError(error))
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum InputError {
+	#[error(display = "{} not found next to the executable or in the working directory", ACTION_MANIFEST)] ManifestNotFound,
+	#[error(display = "OpenVR input interface is not available")] InterfaceNotFound,
+	#[error(display = "SteamVR Input error {}", _0)] CallError(sys::EVRInputError),
+	#[error(display = "{}", _0)] NulError(#[error(source)] NulError),
+}
@@ -5,16 +5,26 @@ use getopts::Options;
 mod shaders;
 mod renderer;
 mod application;
+mod input;
 mod openvr_vulkan;
 
 use application::Application;
+use renderer::{Renderer, DeviceSelector};
 
 fn main() -> Result<(), Box<dyn Error>> {
+	env_logger::from_env(env_logger::Env::default().default_filter_or("info")).init();
+	
 	let args: Vec<String> = env::args().collect();
 	let program = args[0].clone();
 	let mut opts = Options::new();
 	
-	opts.optopt("d", "device", "Select fallback device to use", "NUMBER");
+	opts.optopt("d", "device", "Select fallback device to use, by index or part of its name", "DEVICE");
+	opts.optopt("m", "msaa", "Number of MSAA samples (1, 2, 4 or 8)", "SAMPLES");
+	opts.optopt("s", "scale", "Render resolution relative to the recommended one", "SCALE");
+	opts.optflag("", "mirror", "Show the left eye in a desktop window");
+	opts.optflag("", "depth-prepass", "Draw the depth of opaque models before shading them");
+	opts.optflag("", "gpu-times", "Log the GPU time of each eye every second");
+	opts.optflag("l", "list-devices", "List available devices and exit");
 	opts.optflag("", "debug", "Enable debugging layer and info");
 	opts.optflag("h", "help", "Print this help menu");
 	
@@ -25,10 +35,28 @@ fn main() -> Result<(), Box<dyn Error>> {
 		return Ok(());
 	}
 	
-	let device = matches.opt_get("d")?;
+	if matches.opt_present("l") {
+		for device in Renderer::available_devices()? {
+			println!("{}: {} api: {} driver: {}", device.index, device.name, device.api_version, device.driver_version);
+		}
+		return Ok(());
+	}
+	
+	let device = match matches.opt_str("d") {
+		Some(device) => match device.parse() {
+			Ok(index) => DeviceSelector::Index(index),
+			Err(_) => DeviceSelector::Name(device),
+		},
+		None => DeviceSelector::First,
+	};
+	let msaa = matches.opt_get_default("m", 1)?;
+	let scale = matches.opt_get_default("s", 1.0)?;
 	let debug = matches.opt_present("debug");
+	let mirror = matches.opt_present("mirror");
+	let depth_prepass = matches.opt_present("depth-prepass");
+	let gpu_times = matches.opt_present("gpu-times");
 	
-	let application = Application::new(device, debug)?;
+	let application = Application::new(device, debug, msaa, scale, mirror, depth_prepass, gpu_times)?;
 	
 	application.run()?;
 	
@@ -0,0 +1,374 @@
+// Software decoders for block compressed textures, for devices without BC support. Every block covers 4x4 pixels.
+
+struct Bc7Mode {
+	subsets: usize,
+	partition_bits: u32,
+	rotation_bits: u32,
+	index_selection_bits: u32,
+	color_bits: u32,
+	alpha_bits: u32,
+	// One P-bit per endpoint, or one per subset shared by both of its endpoints
+	endpoint_pbits: bool,
+	shared_pbits: bool,
+	index_bits: u32,
+	// Modes 4 and 5 have separate indices for alpha
+	index_bits2: u32,
+}
+
+const fn mode(subsets: usize, partition_bits: u32, rotation_bits: u32, index_selection_bits: u32, color_bits: u32, alpha_bits: u32,
+              endpoint_pbits: bool, shared_pbits: bool, index_bits: u32, index_bits2: u32) -> Bc7Mode {
+	Bc7Mode { subsets, partition_bits, rotation_bits, index_selection_bits, color_bits, alpha_bits, endpoint_pbits, shared_pbits, index_bits, index_bits2 }
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+	mode(3, 4, 0, 0, 4, 0, true,  false, 3, 0),
+	mode(2, 6, 0, 0, 6, 0, false, true,  3, 0),
+	mode(3, 6, 0, 0, 5, 0, false, false, 2, 0),
+	mode(2, 6, 0, 0, 7, 0, true,  false, 2, 0),
+	mode(1, 0, 2, 1, 5, 6, false, false, 2, 3),
+	mode(1, 0, 2, 0, 7, 8, false, false, 2, 2),
+	mode(1, 0, 0, 0, 7, 7, true,  false, 4, 0),
+	mode(2, 6, 0, 0, 5, 5, true,  false, 2, 0),
+];
+
+// Interpolation weights out of 64 by index size
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+// Subset of every pixel in the 2 subset partitions, one bit per pixel in row order
+const PARTITIONS_2: [u16; 64] = [
+	0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80,
+	0xC800, 0xFFEC, 0xFE80, 0xE800, 0xFFE8, 0xFF00, 0xFFF0, 0xF000,
+	0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE,
+	0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C,
+	0xAAAA, 0xF0F0, 0x5A5A, 0x33CC, 0x3C3C, 0x55AA, 0x9696, 0xA55A,
+	0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660,
+	0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C,
+	0x9336, 0x9CC6, 0x817E, 0xE718, 0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+const PARTITIONS_3: [[u8; 16]; 64] = [
+	[0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2],
+	[0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+	[0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+	[0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+	[0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2],
+	[0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+	[0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1],
+	[0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+	[0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2],
+	[0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+	[0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+	[0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+	[0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2],
+	[0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+	[0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+	[0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+	[0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2],
+	[0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+	[0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2],
+	[0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+	[0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2],
+	[0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+	[0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2],
+	[0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+	[0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0],
+	[0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+	[0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0],
+	[0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+	[0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2],
+	[0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+	[0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1],
+	[0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+	[0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+	[0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+	[0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2],
+	[0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+	[0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0],
+	[0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+	[0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0],
+	[0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+	[0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1],
+	[0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+	[0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1],
+	[0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+	[0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1],
+	[0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+	[0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1],
+	[0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+	[0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2],
+	[0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+	[0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2],
+	[0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+	[0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2],
+	[0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+	[0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2],
+	[0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+	[0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2],
+	[0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+	[0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2],
+	[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+	[0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1],
+	[0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+	[0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+	[0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+// Pixel whose index drops its highest bit, besides pixel 0, for the second subset of 2
+const ANCHORS_2: [usize; 64] = [
+	15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
+	15, 2, 8, 2, 2, 8, 8, 15, 2, 8, 2, 2, 8, 8, 2, 2,
+	15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6,
+	6, 2, 6, 8, 15, 15, 2, 2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+// Same for the second and third subset of 3
+const ANCHORS_3: [[usize; 2]; 64] = [
+	[3, 15], [3, 8], [15, 8], [15, 3], [8, 15], [3, 15], [15, 3], [15, 8],
+	[8, 15], [8, 15], [6, 15], [6, 15], [6, 15], [5, 15], [3, 15], [3, 8],
+	[3, 15], [3, 8], [8, 15], [15, 3], [3, 15], [3, 8], [6, 15], [10, 8],
+	[5, 3], [8, 15], [8, 6], [6, 10], [8, 15], [5, 15], [15, 10], [15, 8],
+	[8, 15], [15, 3], [3, 15], [5, 10], [6, 10], [10, 8], [8, 9], [15, 10],
+	[15, 6], [3, 15], [15, 8], [5, 15], [15, 3], [15, 6], [15, 6], [15, 8],
+	[3, 15], [15, 3], [5, 15], [5, 15], [5, 15], [8, 15], [5, 15], [10, 15],
+	[5, 15], [10, 15], [8, 15], [13, 15], [15, 3], [12, 15], [3, 15], [3, 8],
+];
+
+// RGBA8 pixels of a whole image, `decode_block` turns `block_size` bytes into a block's pixels in row order
+pub fn decode(data: &[u8], width: u32, height: u32, block_size: usize, decode_block: fn(&[u8]) -> [[u8; 4]; 16]) -> Vec<u8> {
+	let blocks_x = (width + 3) / 4;
+	let blocks_y = (height + 3) / 4;
+	let mut pixels = vec![0; (width * height * 4) as usize];
+	
+	for (index, block) in data.chunks_exact(block_size).take((blocks_x * blocks_y) as usize).enumerate() {
+		let (block_x, block_y) = (index as u32 % blocks_x * 4, index as u32 / blocks_x * 4);
+		
+		for (pixel, texel) in decode_block(block).iter().enumerate() {
+			let (x, y) = (block_x + pixel as u32 % 4, block_y + pixel as u32 / 4);
+			
+			// Blocks at the right and bottom edge may stick out of the image
+			if x < width && y < height {
+				let offset = ((y * width + x) * 4) as usize;
+				pixels[offset .. offset + 4].copy_from_slice(texel);
+			}
+		}
+	}
+	
+	pixels
+}
+
+pub fn bc1(block: &[u8]) -> [[u8; 4]; 16] {
+	color_block(block, true)
+}
+
+// Alpha block followed by a BC1 block that's always opaque
+pub fn bc3(block: &[u8]) -> [[u8; 4]; 16] {
+	let mut texels = color_block(&block[8 ..], false);
+	
+	for (texel, alpha) in texels.iter_mut().zip(alpha_block(&block[.. 8]).iter()) {
+		texel[3] = *alpha;
+	}
+	
+	texels
+}
+
+// Two RGB565 endpoints and 2 bit indices. With the endpoints in ascending order BC1 has a transparent black instead of
+// the second interpolated color.
+fn color_block(block: &[u8], punch_through: bool) -> [[u8; 4]; 16] {
+	let c0 = u16::from_le_bytes([block[0], block[1]]);
+	let c1 = u16::from_le_bytes([block[2], block[3]]);
+	let (a, b) = (rgb565(c0), rgb565(c1));
+	
+	let mix = |weight_a: u32, weight_b: u32| {
+		let channel = |c: usize| ((a[c] as u32 * weight_a + b[c] as u32 * weight_b) / (weight_a + weight_b)) as u8;
+		[channel(0), channel(1), channel(2), 255]
+	};
+	
+	let palette = if c0 > c1 || !punch_through {
+		[a, b, mix(2, 1), mix(1, 2)]
+	} else {
+		[a, b, mix(1, 1), [0, 0, 0, 0]]
+	};
+	
+	let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+	let mut texels = [[0; 4]; 16];
+	
+	for (pixel, texel) in texels.iter_mut().enumerate() {
+		*texel = palette[(indices >> (pixel * 2) & 3) as usize];
+	}
+	
+	texels
+}
+
+fn rgb565(color: u16) -> [u8; 4] {
+	let (r, g, b) = ((color >> 11) as u8, (color >> 5 & 0x3F) as u8, (color & 0x1F) as u8);
+	[r << 3 | r >> 2, g << 2 | g >> 4, b << 3 | b >> 2, 255]
+}
+
+// Two endpoints and 3 bit indices, with six interpolated values or four and the extremes
+fn alpha_block(block: &[u8]) -> [u8; 16] {
+	let (a0, a1) = (block[0] as u32, block[1] as u32);
+	let mut palette = [a0, a1, 0, 0, 0, 0, 0, 255];
+	
+	if a0 > a1 {
+		for i in 1 .. 7 {
+			palette[i as usize + 1] = ((7 - i) * a0 + i * a1) / 7;
+		}
+	} else {
+		for i in 1 .. 5 {
+			palette[i as usize + 1] = ((5 - i) * a0 + i * a1) / 5;
+		}
+	}
+	
+	let indices = block[2 .. 8].iter().rev().fold(0u64, |indices, &byte| indices << 8 | byte as u64);
+	let mut alphas = [0; 16];
+	
+	for (pixel, alpha) in alphas.iter_mut().enumerate() {
+		*alpha = palette[(indices >> (pixel * 3) & 7) as usize] as u8;
+	}
+	
+	alphas
+}
+
+// Reads the block's fields from the least significant bit of the first byte on
+struct Bits<'a> {
+	data: &'a [u8],
+	position: usize,
+}
+
+impl<'a> Bits<'a> {
+	fn read(&mut self, count: u32) -> u32 {
+		let mut value = 0;
+		
+		for bit in 0 .. count {
+			value |= ((self.data[self.position / 8] >> (self.position % 8) & 1) as u32) << bit;
+			self.position += 1;
+		}
+		
+		value
+	}
+}
+
+pub fn bc7(block: &[u8]) -> [[u8; 4]; 16] {
+	let mut bits = Bits { data: block, position: 0 };
+	
+	// The mode is the number of zeros before the first set bit, blocks without one are reserved and decode to black
+	let mode = match (0 .. 8).find(|_| bits.read(1) == 1) {
+		Some(mode) => &BC7_MODES[mode],
+		None => return [[0; 4]; 16],
+	};
+	
+	let partition = bits.read(mode.partition_bits) as usize;
+	let rotation = bits.read(mode.rotation_bits);
+	let index_selection = bits.read(mode.index_selection_bits);
+	
+	// All reds come first, then all greens, blues and alphas
+	let endpoint_count = mode.subsets * 2;
+	let mut endpoints = [[255; 4]; 6];
+	for channel in 0 .. 4 {
+		let channel_bits = if channel == 3 { mode.alpha_bits } else { mode.color_bits };
+		if channel_bits == 0 { continue }
+		
+		for endpoint in &mut endpoints[.. endpoint_count] {
+			endpoint[channel] = bits.read(channel_bits);
+		}
+	}
+	
+	let mut pbits = [0; 6];
+	if mode.endpoint_pbits {
+		for pbit in &mut pbits[.. endpoint_count] {
+			*pbit = bits.read(1);
+		}
+	} else if mode.shared_pbits {
+		for subset in 0 .. mode.subsets {
+			let pbit = bits.read(1);
+			pbits[subset * 2] = pbit;
+			pbits[subset * 2 + 1] = pbit;
+		}
+	}
+	
+	// P-bits become the lowest bit of every channel, then the values are widened to 8 bits by repeating their top bits
+	for (endpoint, pbit) in endpoints[.. endpoint_count].iter_mut().zip(pbits.iter()) {
+		for (channel, value) in endpoint.iter_mut().enumerate() {
+			let channel_bits = if channel == 3 { mode.alpha_bits } else { mode.color_bits };
+			if channel_bits == 0 { continue }
+			
+			let (wide, wide_bits) = if mode.endpoint_pbits || mode.shared_pbits {
+				(*value << 1 | pbit, channel_bits + 1)
+			} else {
+				(*value, channel_bits)
+			};
+			
+			*value = wide << (8 - wide_bits) | wide >> (2 * wide_bits - 8);
+		}
+	}
+	
+	let subset = |pixel: usize| match mode.subsets {
+		2 => (PARTITIONS_2[partition] >> pixel & 1) as usize,
+		3 => PARTITIONS_3[partition][pixel] as usize,
+		_ => 0,
+	};
+	
+	// Anchor pixels start every subset, their index's highest bit is implied to be 0
+	let anchor = |pixel: usize| pixel == 0 || match mode.subsets {
+		2 => pixel == ANCHORS_2[partition],
+		3 => ANCHORS_3[partition].contains(&pixel),
+		_ => false,
+	};
+	
+	let mut indices = [0; 16];
+	for (pixel, index) in indices.iter_mut().enumerate() {
+		*index = bits.read(mode.index_bits - anchor(pixel) as u32);
+	}
+	
+	let mut indices2 = [0; 16];
+	if mode.index_bits2 > 0 {
+		for (pixel, index) in indices2.iter_mut().enumerate() {
+			*index = bits.read(mode.index_bits2 - (pixel == 0) as u32);
+		}
+	}
+	
+	// Mode 4 can swap which index set colors and alpha use
+	let (color_indices, color_bits, alpha_indices, alpha_bits) = if mode.index_bits2 == 0 {
+		(&indices, mode.index_bits, &indices, mode.index_bits)
+	} else if index_selection == 0 {
+		(&indices, mode.index_bits, &indices2, mode.index_bits2)
+	} else {
+		(&indices2, mode.index_bits2, &indices, mode.index_bits)
+	};
+	
+	let mut texels = [[0; 4]; 16];
+	
+	for (pixel, texel) in texels.iter_mut().enumerate() {
+		let (from, to) = (endpoints[subset(pixel) * 2], endpoints[subset(pixel) * 2 + 1]);
+		
+		for channel in 0 .. 4 {
+			texel[channel] = if channel == 3 {
+				interpolate(from[channel], to[channel], alpha_indices[pixel], alpha_bits)
+			} else {
+				interpolate(from[channel], to[channel], color_indices[pixel], color_bits)
+			};
+		}
+		
+		// Modes 4 and 5 store one color channel in place of alpha
+		match rotation {
+			1 => texel.swap(0, 3),
+			2 => texel.swap(1, 3),
+			3 => texel.swap(2, 3),
+			_ => {},
+		}
+	}
+	
+	texels
+}
+
+fn interpolate(from: u32, to: u32, index: u32, bits: u32) -> u8 {
+	let weight = match bits {
+		2 => WEIGHTS_2[index as usize],
+		3 => WEIGHTS_3[index as usize],
+		_ => WEIGHTS_4[index as usize],
+	};
+	
+	(((64 - weight) * from + weight * to + 32) >> 6) as u8
+}
@@ -0,0 +1,78 @@
+use std::path::Path;
+use err_derive::Error;
+use image::{RgbaImage, imageops};
+use vulkano::buffer::{CpuAccessibleBuffer, BufferUsage};
+use vulkano::buffer::cpu_access::ReadLockError;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CopyBufferImageError, BuildError, CommandBufferExecError};
+use vulkano::image::ImageAccess;
+use vulkano::memory::DeviceMemoryAllocError;
+use vulkano::sync::{GpuFuture, FlushError};
+use vulkano::sync;
+use vulkano::OomError;
+
+use crate::renderer::Renderer;
+
+// Debugging aids, these stall the pipeline until everything rendered so far has finished
+impl Renderer {
+	// Writes the last image rendered for `eye` to a PNG file.
+	// Without a compositor only the left eye is rendered.
+	pub fn capture_eye<P: AsRef<Path>>(&mut self, eye: openvr::Eye, path: P) -> Result<(), CaptureError> {
+		self.read_eye(eye)?.save(path)?;
+		Ok(())
+	}
+	
+	// Copies the last image rendered for `eye` back to the host
+	pub fn read_eye(&mut self, eye: openvr::Eye) -> Result<RgbaImage, CaptureError> {
+		let image = match eye {
+			openvr::Eye::Left => self.eyes.0.target().image.clone(),
+			openvr::Eye::Right => self.eyes.1.target().image.clone(),
+		};
+		
+		let [width, height] = image.dimensions().width_height();
+		
+		let buffer = CpuAccessibleBuffer::from_iter(self.device.clone(),
+		                                            BufferUsage::transfer_destination(),
+		                                            true,
+		                                            (0 .. width * height * 4).map(|_| 0u8))?;
+		
+		// vulkano transitions the image out of its attachment layout for the copy and back
+		let command_buffer = AutoCommandBufferBuilder::primary_one_time_submit(self.device.clone(), self.queue.family())?
+		                                              .copy_image_to_buffer(image, buffer.clone())?
+		                                              .build()?;
+		
+		let future = self.previous_frame_end.take()
+		                                    .unwrap()
+		                                    .then_execute(self.queue.clone(), command_buffer)?
+		                                    .then_signal_fence_and_flush();
+		
+		// Whatever happened, the next frame can start from scratch
+		self.previous_frame_end = Some(Box::new(sync::now(self.device.clone())) as Box<_>);
+		
+		future?.wait(None)?;
+		
+		// IMAGE_FORMAT already is 8 bit RGBA with sRGB encoding, which is what PNGs store
+		let pixels = buffer.read()?.to_vec();
+		let mut image = RgbaImage::from_raw(width, height, pixels).unwrap();
+		
+		// Adaptive resolution only renders to part of the image
+		let [region_width, region_height] = self.render_region();
+		if [region_width, region_height] != [width, height] {
+			image = imageops::crop(&mut image, 0, 0, region_width, region_height).to_image();
+		}
+		
+		Ok(image)
+	}
+}
+
+
+#[derive(Debug, Error)]
+pub enum CaptureError {
+	#[error(display = "{}", _0)] DeviceMemoryAllocError(#[error(source)] DeviceMemoryAllocError),
+	#[error(display = "{}", _0)] OomError(#[error(source)] OomError),
+	#[error(display = "{}", _0)] CopyBufferImageError(#[error(source)] CopyBufferImageError),
+	#[error(display = "{}", _0)] BuildError(#[error(source)] BuildError),
+	#[error(display = "{}", _0)] CommandBufferExecError(#[error(source)] CommandBufferExecError),
+	#[error(display = "{}", _0)] FlushError(#[error(source)] FlushError),
+	#[error(display = "{}", _0)] ReadLockError(#[error(source)] ReadLockError),
+	#[error(display = "{}", _0)] ImageError(#[error(source)] image::ImageError),
+}
@@ -0,0 +1,179 @@
+use std::iter;
+use std::sync::Arc;
+use err_derive::Error;
+use vulkano::device::Queue;
+use vulkano::buffer::{CpuBufferPool, CpuAccessibleBuffer, BufferUsage, TypedBufferAccess};
+use vulkano::buffer::cpu_access::ReadLockError;
+use vulkano::command_buffer::{AutoCommandBuffer, AutoCommandBufferBuilder, CommandBufferExecFuture, DispatchError, BuildError, CommandBufferExecError};
+use vulkano::descriptor::descriptor_set::{PersistentDescriptorSet, PersistentDescriptorSetError, PersistentDescriptorSetBuildError};
+use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::descriptor::pipeline_layout::PipelineLayout;
+use vulkano::pipeline::{ComputePipeline, ComputePipelineCreationError};
+use vulkano::memory::DeviceMemoryAllocError;
+use vulkano::sync::{self, GpuFuture, NowFuture, FlushError, FenceSignalFuture};
+use vulkano::OomError;
+use cgmath::Matrix4;
+
+use crate::shaders;
+use crate::renderer::model::Model;
+use crate::renderer::frustum::Frustum;
+
+// Invocations per workgroup, local_size_x of cull_comp.glsl
+const GROUP_SIZE: usize = 64;
+// Flags of cull_comp.glsl
+const BOUNDED: u32 = 1;
+const OCCLUDED: u32 = 2;
+
+type CullPipeline = ComputePipeline<PipelineLayout<shaders::cull_comp::Layout>>;
+
+// Element of the Models buffer in cull_comp.glsl
+#[derive(Copy, Clone)]
+#[repr(C)]
+struct Cullable {
+	model: [[f32; 4]; 4],
+	sphere: [f32; 4],
+	flags: u32,
+	_padding: [u32; 3],
+}
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+struct FrustumsUniform {
+	planes: [[f32; 4]; 12],
+	count: u32,
+	_padding: [u32; 3],
+}
+
+// Culls the models against both eyes' frustums on a compute queue of its own. The dispatch is submitted as soon as
+// the frame's views are known, so it runs while the graphics queue is still busy with the previous frame, and the
+// frame waits for its fence only once it records the draws.
+pub struct AsyncCulling {
+	queue: Arc<Queue>,
+	pipeline: Arc<CullPipeline>,
+	models: CpuBufferPool<Cullable>,
+	frustums: CpuBufferPool<FrustumsUniform>,
+	// Written by the compute queue and read by the host once the fence is signaled, grown with the scene
+	visibility: Option<Arc<CpuAccessibleBuffer<[u32]>>>,
+}
+
+// Culling in flight for one frame
+pub struct CullDispatch {
+	future: Option<FenceSignalFuture<CommandBufferExecFuture<NowFuture, AutoCommandBuffer>>>,
+	visibility: Option<Arc<CpuAccessibleBuffer<[u32]>>>,
+	count: usize,
+}
+
+impl AsyncCulling {
+	pub fn new(queue: Arc<Queue>) -> Result<AsyncCulling, CullingError> {
+		let device = queue.device().clone();
+		let shader = shaders::cull_comp::Shader::load(device.clone()).map_err(CullingError::ShaderLoadError)?;
+		let pipeline = Arc::new(ComputePipeline::new(device.clone(), &shader.main_entry_point(), &())?);
+		
+		Ok(AsyncCulling {
+			queue,
+			pipeline,
+			models: CpuBufferPool::new(device.clone(), BufferUsage { storage_buffer: true, ..BufferUsage::none() }),
+			frustums: CpuBufferPool::uniform_buffer(device),
+			visibility: None,
+		})
+	}
+	
+	// `occluded` models, by scene index, come out invisible to both eyes. Without `frustum_culling` models are never
+	// outside the frustums.
+	pub fn dispatch(&mut self, scene: &[(Model, Matrix4<f32>)], frustums: (&Frustum, &Frustum), frustum_culling: bool, occluded: &[bool])
+	               -> Result<CullDispatch, CullingError> {
+		if scene.is_empty() {
+			return Ok(CullDispatch { future: None, visibility: None, count: 0 });
+		}
+		
+		let models: Vec<Cullable> = scene.iter()
+		                                 .zip(occluded)
+		                                 .map(|((model, matrix), &occluded)| {
+			                                 let sphere = if frustum_culling { model.bounding_sphere() } else { None };
+			                                 let mut flags = if sphere.is_some() { BOUNDED } else { 0 };
+			                                 if occluded { flags |= OCCLUDED }
+			
+			                                 Cullable { model: (*matrix).into(),
+			                                            sphere: sphere.map_or([0.0; 4], |sphere| [sphere.center.x, sphere.center.y, sphere.center.z, sphere.radius]),
+			                                            flags,
+			                                            _padding: [0; 3] }
+		                                 })
+		                                 .collect();
+		
+		let mut planes = [[0.0; 4]; 12];
+		planes[.. 6].copy_from_slice(&frustums.0.planes());
+		planes[6 ..].copy_from_slice(&frustums.1.planes());
+		
+		let visibility = match &self.visibility {
+			Some(visibility) if visibility.len() >= scene.len() => visibility.clone(),
+			_ => {
+				let usage = BufferUsage { storage_buffer: true, ..BufferUsage::none() };
+				let visibility = CpuAccessibleBuffer::from_iter(self.queue.device().clone(), usage, true, iter::repeat(0).take(scene.len().next_power_of_two()))?;
+				self.visibility = Some(visibility.clone());
+				visibility
+			},
+		};
+		
+		let set = PersistentDescriptorSet::start(self.pipeline.descriptor_set_layout(0).unwrap().clone())
+		                                  .add_buffer(self.models.chunk(models)?)?
+		                                  .add_buffer(self.frustums.next(FrustumsUniform { planes, count: scene.len() as u32, _padding: [0; 3] })?)?
+		                                  .add_buffer(visibility.clone())?
+		                                  .build()?;
+		
+		let groups = (scene.len() + GROUP_SIZE - 1) / GROUP_SIZE;
+		let command_buffer = AutoCommandBufferBuilder::primary_one_time_submit(self.queue.device().clone(), self.queue.family())?
+		                                              .dispatch([groups as u32, 1, 1], self.pipeline.clone(), set, ())?
+		                                              .build()?;
+		
+		// Nothing the graphics queue writes is read here, so the dispatch doesn't wait for it
+		let future = sync::now(self.queue.device().clone()).then_execute(self.queue.clone(), command_buffer)?
+		                                                    .then_signal_fence_and_flush()?;
+		
+		Ok(CullDispatch {
+			future: Some(future),
+			visibility: Some(visibility),
+			count: scene.len(),
+		})
+	}
+}
+
+impl CullDispatch {
+	// Blocks until the compute queue is done. Bit 0 is set for models the left eye sees and bit 1 for the right eye, by
+	// scene index.
+	pub fn wait(self) -> Result<Vec<u32>, CullingError> {
+		let (future, visibility) = match (self.future, self.visibility) {
+			(Some(future), Some(visibility)) => (future, visibility),
+			_ => return Ok(Vec::new()),
+		};
+		
+		// The fence makes the compute queue's writes visible to the host
+		future.wait(None)?;
+		
+		let visible = visibility.read()?[.. self.count].to_vec();
+		Ok(visible)
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum CullingError {
+	#[error(display = "Failed to load shader: {}", _0)] ShaderLoadError(#[error(source, no_from)] OomError),
+	#[error(display = "{}", _0)] OomError(#[error(source)] OomError),
+	#[error(display = "{}", _0)] ComputePipelineCreationError(#[error(source)] ComputePipelineCreationError),
+	#[error(display = "{}", _0)] DeviceMemoryAllocError(#[error(source)] DeviceMemoryAllocError),
+	#[error(display = "{}", _0)] PersistentDescriptorSetError(#[error(source)] PersistentDescriptorSetError),
+	#[error(display = "{}", _0)] PersistentDescriptorSetBuildError(#[error(source)] PersistentDescriptorSetBuildError),
+	#[error(display = "{}", _0)] DispatchError(#[error(source)] DispatchError),
+	#[error(display = "{}", _0)] BuildError(#[error(source)] BuildError),
+	#[error(display = "{}", _0)] CommandBufferExecError(#[error(source)] CommandBufferExecError),
+	#[error(display = "{}", _0)] FlushError(#[error(source)] FlushError),
+	#[error(display = "{}", _0)] ReadLockError(#[error(source)] ReadLockError),
+}
+
+impl CullingError {
+	pub fn device_lost(&self) -> bool {
+		match self {
+			CullingError::FlushError(FlushError::DeviceLost) => true,
+			_ => false,
+		}
+	}
+}
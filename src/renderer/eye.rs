@@ -1,79 +1,290 @@
 use std::sync::Arc;
+use std::mem;
 use err_derive::Error;
 use vulkano::framebuffer::{Framebuffer, FramebufferAbstract, FramebufferCreationError, RenderPassAbstract};
 use vulkano::image::{AttachmentImage, ImageUsage, ImageAccess, ImageCreationError};
+use vulkano::buffer::{ImmutableBuffer, BufferUsage};
+use vulkano::memory::DeviceMemoryAllocError;
+use vulkano::sync::{GpuFuture, now};
 use vulkano::format::Format;
 use vulkano::format;
-use vulkano::device::Queue;
+use vulkano::device::{Device, Queue};
+use vulkano::instance::{PhysicalDevice, loader};
+use vulkano::VulkanObject;
 use openvr::compositor::texture::{vulkan, Handle, ColorSpace};
 use openvr::compositor::Texture;
 use cgmath::Matrix4;
 
 use crate::openvr_vulkan::OpenVRPtr;
+use crate::renderer::model::Vertex;
+use crate::renderer::tonemap::HDR_FORMAT;
 
+// Eye images are reused round-robin, so a frame never renders into the image the compositor got last frame
 pub struct Eye {
+	pub targets: Vec<EyeTarget>,
+	current: usize,
+	pub msaa_image: Option<Arc<AttachmentImage<Format>>>,
+	pub depth_image: Arc<AttachmentImage<Format>>,
+	pub velocity: EyeVelocity,
+	pub hdr: Option<EyeHdr>,
+	pub fxaa: Option<EyeFxaa>,
+	// Triangles covering what can't be seen through the lens, already in clip space
+	pub hidden_area: Option<Arc<ImmutableBuffer<[Vertex]>>>,
+	pub projection: Matrix4<f32>,
+	pub eye_to_head: Matrix4<f32>,
+}
+
+// The image submitted to the compositor and the framebuffer rendering into it
+pub struct EyeTarget {
 	pub image: Arc<AttachmentImage<format::R8G8B8A8Srgb>>,
-	pub depth_image: Arc<AttachmentImage<format::D16Unorm>>,
 	pub texture: Texture,
-	pub projection: Matrix4<f32>,
+	// Renders the scene into `image`, with HDR it tonemaps `EyeHdr::image` into it instead
+	pub frame_buffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+	// FXAA pass reading `EyeFxaa::image` and writing `image`
+	pub fxaa_frame_buffer: Option<Arc<dyn FramebufferAbstract + Send + Sync>>,
+}
+
+// With FXAA the eye is rendered into an image of its own first, shared by all targets like the depth image
+pub struct EyeFxaa {
+	pub image: Arc<AttachmentImage<format::R8G8B8A8Srgb>>,
+	// Same as `EyeTarget::frame_buffer`, with `image` in place of the target's
+	pub frame_buffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+}
+
+// With HDR the scene is rendered into a floating point image, which the tonemap pass reads
+pub struct EyeHdr {
+	pub image: Arc<AttachmentImage<Format>>,
 	pub frame_buffer: Arc<dyn FramebufferAbstract + Send + Sync>,
 }
 
+// Screen space motion of every pixel since the previous frame, shared by all targets and overwritten by every frame
+pub struct EyeVelocity {
+	pub msaa_image: Option<Arc<AttachmentImage<Format>>>,
+	// Resolved velocities, for reprojection to sample or copy after the frame
+	pub image: Arc<AttachmentImage<Format>>,
+}
+
+// Shaders, blending and MSAA resolves all work on linear colors, the hardware encodes them to sRGB when writing.
+// Color textures are sRGB and decoded when sampled, every other color (clear color, model and vertex colors,
+// material factors) is linear. What ends up in the image is what a regular sRGB display or PNG expects.
 pub const IMAGE_FORMAT: Format = Format::R8G8B8A8Srgb;
-pub const DEPTH_FORMAT: Format = Format::D16Unorm;
+// Tried after the preferred ones. Formats with a stencil come first, it masks the hidden area more cheaply than depth.
+// Vulkan guarantees attachment support for one of the first two and for D16Unorm.
+const DEPTH_FORMATS: [Format; 4] = [Format::D24Unorm_S8Uint, Format::D32Sfloat_S8Uint, Format::D32Sfloat, Format::D16Unorm];
+// Half floats are plenty for fractions of the image, and every device can render to, blend and sample them
+pub const VELOCITY_FORMAT: Format = Format::R16G16Sfloat;
+
+// First of `preferred`, then of the defaults, the device can render depth to.
+// vulkano 0.18 can't query format properties, so this goes through the loader directly.
+pub fn depth_format(physical: PhysicalDevice, preferred: &[Format]) -> Option<Format> {
+	let get_properties: GetPhysicalDeviceFormatProperties = unsafe {
+		let function = loader::auto_loader().ok()?
+		                                    .get_instance_proc_addr(physical.instance().internal_object(), b"vkGetPhysicalDeviceFormatProperties\0".as_ptr() as *const _);
+		mem::transmute(function)
+	};
+	
+	preferred.iter()
+	         .chain(DEPTH_FORMATS.iter())
+	         .cloned()
+	         .find(|&format| {
+		         let mut properties: FormatProperties = unsafe { mem::zeroed() };
+		         unsafe { get_properties(physical.internal_object(), format as u32, &mut properties); }
+		         properties.optimal_tiling_features & FORMAT_FEATURE_DEPTH_STENCIL_ATTACHMENT != 0
+	         })
+}
 
 impl Eye {
-	pub fn new<RPD>(recommended_size:(u32, u32), projection: Matrix4<f32>, queue: &Queue, render_pass: &Arc<RPD>)
-	               -> Result<Eye, EyeCreationError>
-	               where RPD: RenderPassAbstract + Sync + Send + 'static {
+	// `hidden_area` is a triangle list in 0..1 image coordinates, the returned future finishes its upload.
+	// The FXAA and HDR resources are only created when their render passes are given, `render_pass` has to render
+	// to `tonemap::HDR_FORMAT` with the latter.
+	pub fn new(recommended_size:(u32, u32), projection: Matrix4<f32>, eye_to_head: Matrix4<f32>, hidden_area: &[[f32; 2]], samples: u32, buffers: usize, depth_format: Format, queue: &Arc<Queue>, render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>,
+	           fxaa_render_pass: Option<&Arc<dyn RenderPassAbstract + Send + Sync>>, tonemap_render_pass: Option<&Arc<dyn RenderPassAbstract + Send + Sync>>)
+	          -> Result<(Eye, Box<dyn GpuFuture>), EyeCreationError> {
 		let dimensions = [recommended_size.0, recommended_size.1];
 		
 		let device = queue.device();
 		
-		let image = AttachmentImage::with_usage(device.clone(),
-		                                        dimensions,
-		                                        format::R8G8B8A8Srgb,
-		                                        ImageUsage { transfer_source: true,
-		                                                     transfer_destination: true,
-		                                                     sampled: true,
-		                                                     ..ImageUsage::none() })?;
+		let scene_format = if tonemap_render_pass.is_some() { HDR_FORMAT } else { IMAGE_FORMAT };
+		
+		// Only the resolved image outlives the render pass, the others can be shared by all targets
+		let msaa_image = if samples > 1 {
+			Some(AttachmentImage::transient_multisampled(device.clone(), dimensions, samples, scene_format)?)
+		} else {
+			None
+		};
 		
-		let depth_image = AttachmentImage::transient(device.clone(), dimensions, format::D16Unorm)?;
+		// Not transient, the occlusion queries test against it after the pass
+		let depth_image = AttachmentImage::multisampled_with_usage(device.clone(),
+		                                                           dimensions,
+		                                                           samples,
+		                                                           depth_format,
+		                                                           ImageUsage { depth_stencil_attachment: true, ..ImageUsage::none() })?;
 		
-		let texture = Texture {
-			handle: Handle::Vulkan(vulkan::Texture {
-				        image: (*image).as_ptr(),
-				        device: device.as_ptr(),
-				        physical_device: device.physical_device().as_ptr(),
-				        instance: device.instance().as_ptr(),
-				        queue: queue.as_ptr(),
-				        queue_family_index: queue.family().id(),
-				        width: image.dimensions().width(),
-				        height: image.dimensions().height(),
-				        format: image.format() as u32,
-				        sample_count: image.samples(),
-			        }),
-			color_space: ColorSpace::Gamma,
+		let velocity = EyeVelocity::new(device, dimensions, samples)?;
+		
+		let hdr = match tonemap_render_pass {
+			Some(_) => {
+				let image = AttachmentImage::sampled(device.clone(), dimensions, HDR_FORMAT)?;
+				let frame_buffer = Eye::frame_buffer(render_pass, &msaa_image, &depth_image, &velocity, &image)?;
+				Some(EyeHdr { image, frame_buffer })
+			},
+			None => None,
 		};
 		
+		// Images written by the tonemap pass only need its render pass
+		let first_frame_buffer = |image: &Arc<AttachmentImage<format::R8G8B8A8Srgb>>| -> Result<Arc<dyn FramebufferAbstract + Send + Sync>, FramebufferCreationError> {
+			match tonemap_render_pass {
+				Some(tonemap_render_pass) => Ok(Arc::new(Framebuffer::start(tonemap_render_pass.clone())
+				                                            .add(image.clone())?
+				                                            .build()?)),
+				None => Eye::frame_buffer(render_pass, &msaa_image, &depth_image, &velocity, image),
+			}
+		};
 		
-		let frame_buffer = Arc::new(Framebuffer::start(render_pass.clone())
-		                       .add(image.clone())?
-		                       .add(depth_image.clone())?
-		                       .build()?);
+		let fxaa = match fxaa_render_pass {
+			Some(_) => {
+				let image = AttachmentImage::sampled(device.clone(), dimensions, format::R8G8B8A8Srgb)?;
+				let frame_buffer = first_frame_buffer(&image)?;
+				Some(EyeFxaa { image, frame_buffer })
+			},
+			None => None,
+		};
+		
+		let (hidden_area, upload): (_, Box<dyn GpuFuture>) = if hidden_area.is_empty() {
+			(None, Box::new(now(device.clone())))
+		} else {
+			// Image coordinates have their origin in the top left, just like Vulkan's clip space
+			let vertices = hidden_area.iter().map(|[x, y]| Vertex::new([x * 2.0 - 1.0, y * 2.0 - 1.0, 0.0], [0.0, 0.0, 0.0], [0.0, 0.0]));
+			let (buffer, future) = ImmutableBuffer::from_iter(vertices,
+			                                                  BufferUsage{ vertex_buffer: true, ..BufferUsage::none() },
+			                                                  queue.clone())?;
+			(Some(buffer), Box::new(future))
+		};
+		
+		let mut targets = Vec::with_capacity(buffers);
+		
+		for _ in 0 .. buffers.max(1) {
+			let image = AttachmentImage::with_usage(device.clone(),
+			                                        dimensions,
+			                                        format::R8G8B8A8Srgb,
+			                                        ImageUsage { transfer_source: true,
+			                                                     transfer_destination: true,
+			                                                     sampled: true,
+			                                                     ..ImageUsage::none() })?;
+			
+			let texture = Texture {
+				handle: Handle::Vulkan(vulkan::Texture {
+					        image: (*image).as_ptr(),
+					        device: device.as_ptr(),
+					        physical_device: device.physical_device().as_ptr(),
+					        instance: device.instance().as_ptr(),
+					        queue: queue.as_ptr(),
+					        queue_family_index: queue.family().id(),
+					        width: image.dimensions().width(),
+					        height: image.dimensions().height(),
+					        format: image.format() as u32,
+					        sample_count: image.samples(),
+				        }),
+				// The stored values are already encoded for display
+				color_space: ColorSpace::Gamma,
+			};
+			
+			let frame_buffer = first_frame_buffer(&image)?;
+			
+			let fxaa_frame_buffer: Option<Arc<dyn FramebufferAbstract + Send + Sync>> = match fxaa_render_pass {
+				Some(fxaa_render_pass) => Some(Arc::new(Framebuffer::start(fxaa_render_pass.clone())
+				                                           .add(image.clone())?
+				                                           .build()?)),
+				None => None,
+			};
+			
+			targets.push(EyeTarget {
+				image,
+				texture,
+				frame_buffer,
+				fxaa_frame_buffer,
+			});
+		}
 		
-		Ok(Eye {
-			image,
+		Ok((Eye {
+			targets,
+			current: 0,
+			msaa_image,
 			depth_image,
-			texture,
+			velocity,
+			hdr,
+			fxaa,
+			hidden_area,
 			projection,
-			frame_buffer,
+			eye_to_head,
+		}, upload))
+	}
+	
+	// With MSAA the multisampled images are rendered to and resolved into `image` and the velocity image
+	pub fn frame_buffer<F>(render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>, msaa_image: &Option<Arc<AttachmentImage<Format>>>,
+	                       depth_image: &Arc<AttachmentImage<Format>>, velocity: &EyeVelocity, image: &Arc<AttachmentImage<F>>)
+	                      -> Result<Arc<dyn FramebufferAbstract + Send + Sync>, FramebufferCreationError>
+	                      where F: 'static + Send + Sync {
+		Ok(match (msaa_image, &velocity.msaa_image) {
+			(Some(msaa_image), Some(velocity_msaa_image)) => Arc::new(Framebuffer::start(render_pass.clone())
+			                                                             .add(msaa_image.clone())?
+			                                                             .add(velocity_msaa_image.clone())?
+			                                                             .add(depth_image.clone())?
+			                                                             .add(image.clone())?
+			                                                             .add(velocity.image.clone())?
+			                                                             .build()?),
+			_ => Arc::new(Framebuffer::start(render_pass.clone())
+			                 .add(image.clone())?
+			                 .add(velocity.image.clone())?
+			                 .add(depth_image.clone())?
+			                 .build()?),
 		})
 	}
+	
+	// The target of the frame being rendered, or the last one rendered in between frames
+	pub fn target(&self) -> &EyeTarget {
+		&self.targets[self.current]
+	}
+	
+	// Called once at the start of each frame. All frames are submitted to the same queue, so by the time a target
+	// comes around again the commands of its previous frame, including the compositor's copy, were submitted before.
+	pub fn advance(&mut self) {
+		self.current = (self.current + 1) % self.targets.len();
+	}
+}
+
+impl EyeVelocity {
+	pub fn new(device: &Arc<Device>, dimensions: [u32; 2], samples: u32) -> Result<EyeVelocity, ImageCreationError> {
+		let msaa_image = if samples > 1 {
+			Some(AttachmentImage::transient_multisampled(device.clone(), dimensions, samples, VELOCITY_FORMAT)?)
+		} else {
+			None
+		};
+		
+		let image = AttachmentImage::with_usage(device.clone(),
+		                                        dimensions,
+		                                        VELOCITY_FORMAT,
+		                                        ImageUsage { sampled: true, transfer_source: true, ..ImageUsage::none() })?;
+		
+		Ok(EyeVelocity { msaa_image, image })
+	}
 }
 
 #[derive(Debug, Error)]
 pub enum EyeCreationError {
 	#[error(display = "{}", _0)] ImageCreationError(#[error(source)] ImageCreationError),
 	#[error(display = "{}", _0)] FramebufferCreationError(#[error(source)] FramebufferCreationError),
+	#[error(display = "{}", _0)] DeviceMemoryAllocError(#[error(source)] DeviceMemoryAllocError),
+}
+
+// Just enough of the Vulkan headers for the format query
+const FORMAT_FEATURE_DEPTH_STENCIL_ATTACHMENT: u32 = 0x200;
+
+type GetPhysicalDeviceFormatProperties = unsafe extern "system" fn(usize, u32, *mut FormatProperties);
+
+#[repr(C)]
+struct FormatProperties {
+	linear_tiling_features: u32,
+	optimal_tiling_features: u32,
+	buffer_features: u32,
 }
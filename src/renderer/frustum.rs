@@ -0,0 +1,38 @@
+use cgmath::{Matrix4, Matrix, Vector4, Point3, InnerSpace, EuclideanSpace};
+
+// Planes extracted from a Vulkan style projection-view matrix, normals point inwards
+pub struct Frustum {
+	planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+	pub fn new(pv: &Matrix4<f32>) -> Frustum {
+		let rows = [pv.row(0), pv.row(1), pv.row(2), pv.row(3)];
+		let normalize = |plane: Vector4<f32>| plane / plane.truncate().magnitude();
+		
+		Frustum {
+			planes: [
+				normalize(rows[3] + rows[0]),
+				normalize(rows[3] - rows[0]),
+				normalize(rows[3] + rows[1]),
+				normalize(rows[3] - rows[1]),
+				// Vulkan depth goes from 0 to w
+				normalize(rows[2]),
+				normalize(rows[3] - rows[2]),
+			],
+		}
+	}
+	
+	// In the order `new` extracts them, as plane equations
+	pub fn planes(&self) -> [[f32; 4]; 6] {
+		let mut planes = [[0.0; 4]; 6];
+		for (plane, frustum_plane) in planes.iter_mut().zip(&self.planes) {
+			*plane = (*frustum_plane).into();
+		}
+		planes
+	}
+	
+	pub fn intersects_sphere(&self, center: Point3<f32>, radius: f32) -> bool {
+		self.planes.iter().all(|plane| plane.truncate().dot(center.to_vec()) + plane.w >= -radius)
+	}
+}
@@ -0,0 +1,90 @@
+use std::sync::Arc;
+use err_derive::Error;
+use vulkano::device::Device;
+use vulkano::framebuffer::{Subpass, RenderPassAbstract, RenderPassCreationError};
+use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineCreationError};
+use vulkano::pipeline::vertex::BufferlessDefinition;
+use vulkano::sampler::{Sampler, Filter, MipmapMode, SamplerAddressMode, SamplerCreationError};
+use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::OomError;
+
+use crate::shaders;
+use crate::renderer::eye;
+
+type FxaaPipelineType = GraphicsPipeline<
+	BufferlessDefinition,
+	Box<dyn PipelineLayoutAbstract + Send + Sync>,
+	Arc<dyn RenderPassAbstract + Send + Sync>
+>;
+
+// Full screen pass smoothing the edges of an eye's image on its way into the image that gets submitted.
+// Much cheaper than MSAA, at the cost of blurring some texture detail along with the edges.
+pub struct Fxaa {
+	pub render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+	pub pipeline: Arc<FxaaPipelineType>,
+	// Linear, the shader samples between pixels to find and blend along edges
+	pub sampler: Arc<Sampler>,
+}
+
+impl Fxaa {
+	pub fn new(device: &Arc<Device>) -> Result<Fxaa, FxaaError> {
+		let vs = shaders::fxaa_vert::Shader::load(device.clone()).map_err(FxaaError::ShaderLoadError)?;
+		let fs = shaders::fxaa_frag::Shader::load(device.clone()).map_err(FxaaError::ShaderLoadError)?;
+		
+		// Every pixel of the rendered region is overwritten, so there is nothing to load or clear
+		let render_pass: Arc<dyn RenderPassAbstract + Send + Sync> = Arc::new(
+			vulkano::single_pass_renderpass!(device.clone(),
+				attachments: {
+					color: {
+						load: DontCare,
+						store: Store,
+						format: eye::IMAGE_FORMAT,
+						samples: 1,
+					}
+				},
+				pass: {
+					color: [color],
+					depth_stencil: {}
+				}
+			)?
+		);
+		
+		let pipeline = Arc::new(
+			GraphicsPipeline::start()
+			                 .vertex_input(BufferlessDefinition)
+			                 .vertex_shader(vs.main_entry_point(), ())
+			                 .viewports_dynamic_scissors_irrelevant(1)
+			                 .fragment_shader(fs.main_entry_point(), ())
+			                 .cull_mode_disabled()
+			                 .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+			                 .build(device.clone())?
+		);
+		
+		let sampler = Sampler::new(device.clone(),
+		                           Filter::Linear,
+		                           Filter::Linear,
+		                           MipmapMode::Nearest,
+		                           SamplerAddressMode::ClampToEdge,
+		                           SamplerAddressMode::ClampToEdge,
+		                           SamplerAddressMode::ClampToEdge,
+		                           0.0,
+		                           1.0,
+		                           0.0,
+		                           0.0)?;
+		
+		Ok(Fxaa {
+			render_pass,
+			pipeline,
+			sampler,
+		})
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum FxaaError {
+	#[error(display = "Failed to load shader: {}", _0)] ShaderLoadError(#[error(source, no_from)] OomError),
+	#[error(display = "{}", _0)] RenderPassCreationError(#[error(source)] RenderPassCreationError),
+	#[error(display = "{}", _0)] GraphicsPipelineCreationError(#[error(source)] GraphicsPipelineCreationError),
+	#[error(display = "{}", _0)] SamplerCreationError(#[error(source)] SamplerCreationError),
+}
+
@@ -0,0 +1,198 @@
+use std::sync::Arc;
+use std::collections::VecDeque;
+use std::time::Duration;
+use err_derive::Error;
+use vulkano::device::{Device, Queue};
+use vulkano::command_buffer::pool::StandardCommandPool;
+use vulkano::command_buffer::pool::standard::StandardCommandPoolAlloc;
+use vulkano::command_buffer::sys::{UnsafeCommandBufferBuilder, UnsafeCommandBuffer, Kind, Flags};
+use vulkano::command_buffer::submit::{SubmitCommandBufferBuilder, SubmitCommandBufferError};
+use vulkano::query::{UnsafeQueryPool, QueryType, QueryPoolCreationError};
+use vulkano::sync::PipelineStages;
+use vulkano::OomError;
+
+use crate::renderer::query_results::{QueryResults, QueryStatus};
+
+// Frames that can be waiting for their timestamps, further frames aren't measured
+const SLOTS: u32 = 8;
+// Timestamps written by `mark` between the ones of `begin` and `end`
+pub const MARKS: usize = 3;
+const TIMESTAMPS: usize = MARKS + 2;
+
+// vulkano 0.18 can't record queries into its command buffers, so the timestamps are written by command buffers of
+// their own, submitted right before and after a frame. Timestamps at the bottom of the pipe are only written once
+// everything submitted before them has finished, so their difference is the time the GPU spent on the frame.
+// Marks split the frame into parts the same way, but only between submissions.
+pub struct GpuTimer {
+	queue: Arc<Queue>,
+	command_pool: Arc<StandardCommandPool>,
+	query_pool: UnsafeQueryPool,
+	query_results: QueryResults,
+	// Nanoseconds per tick
+	period: f64,
+	mask: u64,
+	next: u32,
+	// Oldest first, the last one is still missing its end while a frame is being submitted
+	pending: VecDeque<Pending>,
+}
+
+struct Pending {
+	slot: u32,
+	// Timestamps of the slot that have been submitted
+	written: usize,
+	// Have to stay alive until the GPU is done with them
+	command_buffers: Vec<UnsafeCommandBuffer<StandardCommandPoolAlloc>>,
+}
+
+impl GpuTimer {
+	// `None` if the queue doesn't support timestamps or they can't be read back
+	pub fn new(queue: &Arc<Queue>) -> Result<Option<GpuTimer>, GpuTimerError> {
+		let device = queue.device();
+		
+		let bits = match queue.family().timestamp_valid_bits() {
+			Some(bits) => bits,
+			None => return Ok(None),
+		};
+		
+		let query_results = match QueryResults::load(device) {
+			Some(query_results) => query_results,
+			None => return Ok(None),
+		};
+		
+		Ok(Some(GpuTimer {
+			queue: queue.clone(),
+			command_pool: Device::standard_command_pool(device, queue.family()),
+			query_pool: UnsafeQueryPool::new(device.clone(), QueryType::Timestamp, SLOTS * TIMESTAMPS as u32)?,
+			query_results,
+			period: device.physical_device().limits().timestamp_period() as f64,
+			mask: if bits >= 64 { !0 } else { (1 << bits) - 1 },
+			next: 0,
+			pending: VecDeque::new(),
+		}))
+	}
+	
+	// Submitted right before the frame, skipped while all slots are waiting for results
+	pub fn begin(&mut self) -> Result<(), GpuTimerError> {
+		match self.pending.back() {
+			// The previous frame failed before its end was submitted, the measurement covers this frame as well
+			Some(pending) if pending.written < TIMESTAMPS => return Ok(()),
+			_ if self.pending.len() >= SLOTS as usize => return Ok(()),
+			_ => {},
+		}
+		
+		let slot = self.next;
+		self.next = (self.next + 1) % SLOTS;
+		
+		let command_buffer = unsafe {
+			let mut builder = UnsafeCommandBufferBuilder::new(&self.command_pool, Kind::primary(), Flags::OneTimeSubmit)?;
+			builder.reset_query_pool(self.query_pool.queries_range(slot * TIMESTAMPS as u32, TIMESTAMPS as u32).unwrap());
+			builder.write_timestamp(self.query_pool.query(slot * TIMESTAMPS as u32).unwrap(), PipelineStages { bottom_of_pipe: true, ..PipelineStages::none() });
+			builder.build()?
+		};
+		
+		self.submit(&command_buffer)?;
+		self.pending.push_back(Pending { slot, written: 1, command_buffers: vec![command_buffer] });
+		
+		Ok(())
+	}
+	
+	// True between `begin` and `end` of a measured frame, the parts of a frame have to be submitted before `mark`
+	pub fn measuring(&self) -> bool {
+		self.pending.back().map_or(false, |pending| pending.written < TIMESTAMPS)
+	}
+	
+	// Submitted between parts of the frame, ignored once all marks of the frame are used
+	pub fn mark(&mut self) -> Result<(), GpuTimerError> {
+		match self.pending.back() {
+			Some(pending) if pending.written < TIMESTAMPS - 1 => self.write(pending.written + 1),
+			_ => Ok(()),
+		}
+	}
+	
+	// Submitted right after the frame, does nothing if `begin` skipped it. Marks that weren't submitted get the end's time.
+	pub fn end(&mut self) -> Result<(), GpuTimerError> {
+		match self.pending.back() {
+			Some(pending) if pending.written < TIMESTAMPS => self.write(TIMESTAMPS),
+			_ => Ok(()),
+		}
+	}
+	
+	// Writes the timestamps of the last frame up to `written`
+	fn write(&mut self, written: usize) -> Result<(), GpuTimerError> {
+		let pending = self.pending.back().unwrap();
+		let first = pending.slot * TIMESTAMPS as u32;
+		
+		let command_buffer = unsafe {
+			let mut builder = UnsafeCommandBufferBuilder::new(&self.command_pool, Kind::primary(), Flags::OneTimeSubmit)?;
+			for index in pending.written..written {
+				builder.write_timestamp(self.query_pool.query(first + index as u32).unwrap(), PipelineStages { bottom_of_pipe: true, ..PipelineStages::none() });
+			}
+			builder.build()?
+		};
+		
+		self.submit(&command_buffer)?;
+		
+		let pending = self.pending.back_mut().unwrap();
+		pending.written = written;
+		pending.command_buffers.push(command_buffer);
+		
+		Ok(())
+	}
+	
+	// GPU times of the parts of the frames that finished since the last call, oldest first. Never waits for the GPU.
+	pub fn poll(&mut self) -> Vec<[Duration; MARKS + 1]> {
+		let mut times = Vec::new();
+		
+		while let Some(pending) = self.pending.front() {
+			if pending.written < TIMESTAMPS {
+				break;
+			}
+			
+			let mut timestamps = [0u64; TIMESTAMPS];
+			
+			let status = self.query_results.read(&self.query_pool, pending.slot * TIMESTAMPS as u32, &mut timestamps);
+			
+			// Results come in order, so the later frames aren't ready either
+			if status == QueryStatus::NotReady {
+				break;
+			}
+			
+			self.pending.pop_front();
+			
+			if status == QueryStatus::Ready {
+				let mut parts = [Duration::default(); MARKS + 1];
+				for (part, pair) in parts.iter_mut().zip(timestamps.windows(2)) {
+					let ticks = pair[1].wrapping_sub(pair[0]) & self.mask;
+					*part = Duration::from_nanos((ticks as f64 * self.period) as u64);
+				}
+				times.push(parts);
+			}
+		}
+		
+		times
+	}
+	
+	fn submit(&self, command_buffer: &UnsafeCommandBuffer<StandardCommandPoolAlloc>) -> Result<(), SubmitCommandBufferError> {
+		unsafe {
+			let mut submit = SubmitCommandBufferBuilder::new();
+			submit.add_command_buffer(command_buffer);
+			submit.submit(&self.queue)
+		}
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum GpuTimerError {
+	#[error(display = "{}", _0)] OomError(#[error(source)] OomError),
+	#[error(display = "{}", _0)] QueryPoolCreationError(#[error(source)] QueryPoolCreationError),
+	#[error(display = "{}", _0)] SubmitCommandBufferError(#[error(source)] SubmitCommandBufferError),
+}
+
+impl GpuTimerError {
+	pub fn device_lost(&self) -> bool {
+		match self {
+			GpuTimerError::SubmitCommandBufferError(SubmitCommandBufferError::DeviceLost) => true,
+			_ => false,
+		}
+	}
+}
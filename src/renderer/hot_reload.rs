@@ -0,0 +1,133 @@
+use std::fs;
+use std::ffi::CStr;
+use std::sync::Arc;
+use std::time::SystemTime;
+use err_derive::Error;
+use shaderc::{Compiler, ShaderKind};
+use vulkano::device::Device;
+use vulkano::pipeline::GraphicsPipelineCreationError;
+use vulkano::pipeline::shader::{ShaderModule, GraphicsShaderType};
+use vulkano::descriptor::descriptor::ShaderStages;
+use vulkano::OomError;
+use log::{info, error};
+
+use crate::shaders;
+use crate::renderer::{Renderer, PipelineDesc, ModelPipelines, PipelineType};
+
+// Read straight from the source tree, so this only works on the machine the binary was built on
+const VERT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders/vert.glsl");
+const FRAG_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders/frag.glsl");
+// The depth prepass has to keep transforming vertices the same way as vert.glsl
+const DEPTH_VERT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders/depth_vert.glsl");
+
+// Polls the modification times once per frame, which is plenty for editing by hand.
+// The reloaded shaders reuse the interface reflected from the compiled in ones, so edits must not change
+// inputs, outputs, descriptor sets or push constants. Those still need a rebuild.
+pub struct ShaderWatcher {
+	compiler: Option<Compiler>,
+	modified: Option<[SystemTime; 3]>,
+	desc: PipelineDesc,
+}
+
+impl ShaderWatcher {
+	pub fn new(desc: PipelineDesc) -> ShaderWatcher {
+		ShaderWatcher {
+			compiler: Compiler::new(),
+			modified: modified(),
+			desc,
+		}
+	}
+	
+	fn changed(&mut self) -> bool {
+		let modified = modified();
+		
+		// Missing sources just mean there is nothing to watch
+		if modified.is_none() || modified == self.modified {
+			return false;
+		}
+		
+		self.modified = modified;
+		true
+	}
+	
+	// The model pipelines and the depth prepass pipeline
+	fn rebuild(&mut self, device: &Arc<Device>) -> Result<(ModelPipelines, Arc<PipelineType>), HotReloadError> {
+		let compiler = self.compiler.as_mut().ok_or(HotReloadError::NoCompiler)?;
+		
+		let vs = compile(compiler, VERT_PATH, ShaderKind::Vertex, device)?;
+		let fs = compile(compiler, FRAG_PATH, ShaderKind::Fragment, device)?;
+		let depth_vs = compile(compiler, DEPTH_VERT_PATH, ShaderKind::Vertex, device)?;
+		// Only writes depth, nothing to edit there
+		let depth_fs = shaders::shadow_frag::Shader::load(device.clone())?;
+		
+		let main = CStr::from_bytes_with_nul(b"main\0").unwrap();
+		
+		// Only sound as long as the interface didn't change, see above
+		let (vs_entry, fs_entry, depth_vs_entry) = unsafe {(
+			vs.graphics_entry_point(main,
+			                        shaders::vert::MainInput,
+			                        shaders::vert::MainOutput,
+			                        shaders::vert::Layout(ShaderStages { vertex: true, ..ShaderStages::none() }),
+			                        GraphicsShaderType::Vertex),
+			fs.graphics_entry_point(main,
+			                        shaders::frag::MainInput,
+			                        shaders::frag::MainOutput,
+			                        shaders::frag::Layout(ShaderStages { fragment: true, ..ShaderStages::none() }),
+			                        GraphicsShaderType::Fragment),
+			depth_vs.graphics_entry_point(main,
+			                              shaders::depth_vert::MainInput,
+			                              shaders::depth_vert::MainOutput,
+			                              shaders::depth_vert::Layout(ShaderStages { vertex: true, ..ShaderStages::none() }),
+			                              GraphicsShaderType::Vertex),
+		)};
+		
+		Ok((self.desc.build(device, vs_entry, fs_entry)?, self.desc.build_prepass(device, depth_vs_entry, depth_fs.main_entry_point())?))
+	}
+}
+
+impl Renderer {
+	// On failure the previous pipelines are kept, so a typo doesn't take down the running application
+	pub(super) fn reload_shaders(&mut self) {
+		if !self.shader_watcher.changed() {
+			return;
+		}
+		
+		match self.shader_watcher.rebuild(&self.device) {
+			Ok((ModelPipelines { pipeline, transparent_pipeline, wireframe_pipeline, points_pipeline, prepassed_pipeline }, prepass_pipeline)) => {
+				info!("Reloaded shaders");
+				self.pipeline = pipeline;
+				self.transparent_pipeline = transparent_pipeline;
+				self.wireframe_pipeline = wireframe_pipeline;
+				self.points_pipeline = points_pipeline;
+				self.prepassed_pipeline = prepassed_pipeline;
+				self.prepass_pipeline = prepass_pipeline;
+				self.mark_scene_dirty();
+			},
+			Err(err) => error!("Failed to reload shaders: {}", err),
+		}
+	}
+}
+
+fn modified() -> Option<[SystemTime; 3]> {
+	let modified = |path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+	
+	Some([modified(VERT_PATH)?, modified(FRAG_PATH)?, modified(DEPTH_VERT_PATH)?])
+}
+
+fn compile(compiler: &mut Compiler, path: &str, kind: ShaderKind, device: &Arc<Device>) -> Result<Arc<ShaderModule>, HotReloadError> {
+	let source = fs::read_to_string(path)?;
+	let artifact = compiler.compile_into_spirv(&source, kind, path, "main", None)?;
+	
+	// shaderc validates the SPIR-V it emits
+	Ok(unsafe { ShaderModule::from_words(device.clone(), artifact.as_binary())? })
+}
+
+
+#[derive(Debug, Error)]
+pub enum HotReloadError {
+	#[error(display = "Failed to initialize shaderc")] NoCompiler,
+	#[error(display = "{}", _0)] IoError(#[error(source)] std::io::Error),
+	#[error(display = "{}", _0)] CompilationError(#[error(source)] shaderc::Error),
+	#[error(display = "{}", _0)] OomError(#[error(source)] OomError),
+	#[error(display = "{}", _0)] GraphicsPipelineCreationError(#[error(source)] GraphicsPipelineCreationError),
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use vulkano::buffer::{CpuBufferPool, BufferUsage, BufferSlice, TypedBufferAccess};
+use vulkano::command_buffer::DrawIndexedIndirectCommand;
+use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::descriptor::descriptor_set::{DescriptorSet, PersistentDescriptorSet};
+use vulkano::device::Device;
+use vulkano::framebuffer::Subpass;
+use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineCreationError};
+use vulkano::pipeline::depth_stencil::{DepthStencil, Compare};
+use vulkano::pipeline::shader::GraphicsEntryPoint;
+use cgmath::Matrix4;
+
+use crate::shaders;
+use crate::renderer::{RenderError, PipelineType, PipelineDesc, FragmentEntryPoint, face_culling};
+use crate::renderer::model::{self, Model, Indices};
+use crate::renderer::transforms::{self, TRANSFORM_SIZE};
+use crate::renderer::secondary::ModelRecorder;
+
+type IndirectVertexEntryPoint<'a> = GraphicsEntryPoint<'a, (), shaders::indirect_vert::MainInput, shaders::indirect_vert::MainOutput, shaders::indirect_vert::Layout>;
+
+// Element of the Draws buffer in indirect_vert.glsl
+#[derive(Copy, Clone)]
+#[repr(C)]
+struct Draw {
+	transform: [[f32; 4]; TRANSFORM_SIZE],
+	color: [f32; 4],
+}
+
+// Opaque models sharing their vertices, indices, material and joints are drawn with a single indirect draw, one command
+// per model. Needs the multi_draw_indirect and draw_indirect_first_instance device features.
+pub struct Indirect {
+	pipeline: Arc<PipelineType>,
+	// Opaque models after the depth prepass, only the closest surface passes
+	prepassed_pipeline: Arc<PipelineType>,
+	draws: CpuBufferPool<Draw>,
+	commands: CpuBufferPool<DrawIndexedIndirectCommand>,
+}
+
+// The frame's per-model data, shared by both eyes
+pub struct IndirectDraws<'a> {
+	indirect: &'a Indirect,
+	pipeline: &'a Arc<PipelineType>,
+	set: Arc<dyn DescriptorSet + Send + Sync>,
+}
+
+impl Indirect {
+	pub fn new(device: &Arc<Device>, desc: &PipelineDesc, vs: IndirectVertexEntryPoint, fs: FragmentEntryPoint) -> Result<Indirect, GraphicsPipelineCreationError> {
+		let build_pipeline = |prepassed: bool| -> Result<Arc<PipelineType>, GraphicsPipelineCreationError> {
+			let builder = GraphicsPipeline::start()
+			                               .vertex_input_single_buffer::<model::Vertex>()
+			                               .vertex_shader(vs, ())
+			                               .viewports_dynamic_scissors_irrelevant(1)
+			                               .fragment_shader(fs, ())
+			                               .depth_stencil(DepthStencil { depth_compare: if prepassed { Compare::Equal } else { Compare::Less },
+			                                                             depth_write: !prepassed,
+			                                                             stencil_front: desc.visible_stencil,
+			                                                             stencil_back: desc.visible_stencil,
+			                                                             ..DepthStencil::simple_depth_test() })
+			                               .render_pass(Subpass::from(desc.render_pass.clone(), 0).unwrap());
+			
+			Ok(Arc::new(face_culling(builder, desc.cull_mode, desc.front_face).with_auto_layout(device.clone(), &[])?))
+		};
+		
+		Ok(Indirect {
+			pipeline: build_pipeline(false)?,
+			prepassed_pipeline: build_pipeline(true)?,
+			draws: CpuBufferPool::new(device.clone(), BufferUsage { storage_buffer: true, ..BufferUsage::none() }),
+			commands: CpuBufferPool::new(device.clone(), BufferUsage { indirect_buffer: true, ..BufferUsage::none() }),
+		})
+	}
+	
+	// Transforms and colors of `scene`, read by the index each command passes as its first instance. `previous` has
+	// the model matrices of the previous frame, by scene index.
+	pub fn frame(&self, scene: &[(Model, Matrix4<f32>)], previous: &[Matrix4<f32>], prepass: bool) -> Result<IndirectDraws<'_>, RenderError> {
+		let mut draws: Vec<Draw> = scene.iter()
+		                                .zip(previous)
+		                                .map(|((model, matrix), previous)| Draw { transform: transforms::transform(matrix, previous), color: model.color() })
+		                                .collect();
+		
+		// The descriptor needs something to point at even if nothing is drawn
+		if draws.is_empty() {
+			draws.push(Draw { transform: [[0.0; 4]; TRANSFORM_SIZE], color: [0.0; 4] });
+		}
+		
+		let pipeline = if prepass { &self.prepassed_pipeline } else { &self.pipeline };
+		let layout = pipeline.descriptor_set_layout(3).ok_or(RenderError::NoLayout)?;
+		
+		let set = PersistentDescriptorSet::start(layout.clone())
+		                                  .add_buffer(self.draws.chunk(draws)?)?
+		                                  .build()?;
+		
+		Ok(IndirectDraws {
+			indirect: self,
+			pipeline,
+			set: Arc::new(set),
+		})
+	}
+	
+	// Everything else keeps drawing one model at a time. Batches push no emissive color.
+	pub fn batched(model: &Model) -> bool {
+		!model.transparent() && model.indices.is_some() && model.emissive() == [0.0; 3]
+	}
+}
+
+impl<'a> IndirectDraws<'a> {
+	// `models` have to be `batched`, `sets` gives the sets 0 to 2 of a model. Batches are drawn in the order of their
+	// first model.
+	pub fn record<F>(&self, recorder: &mut ModelRecorder, scene: &[(Model, Matrix4<f32>)], models: &[usize], sets: F) -> Result<(), RenderError>
+	                where F: Fn(usize) -> Vec<Arc<dyn DescriptorSet + Send + Sync>> {
+		let mut batches: Vec<(usize, Vec<Arc<dyn DescriptorSet + Send + Sync>>, Vec<DrawIndexedIndirectCommand>)> = Vec::new();
+		let mut keys = HashMap::new();
+		
+		for &index in models {
+			let (model, _) = &scene[index];
+			let (indices, index_count) = match &model.indices {
+				Some(Indices::U16(indices)) => (address(&**indices), indices.len()),
+				Some(Indices::U32(indices)) => (address(&**indices), indices.len()),
+				None => continue,
+			};
+			
+			let model_sets = sets(index);
+			let key = (address(&*model.vertices), indices, model_sets.iter().map(|set| address(&**set)).collect::<Vec<_>>());
+			let batch = *keys.entry(key).or_insert_with(|| {
+				batches.push((index, model_sets, Vec::new()));
+				batches.len() - 1
+			});
+			
+			batches[batch].2.push(DrawIndexedIndirectCommand { index_count: index_count as u32,
+			                                                   instance_count: 1,
+			                                                   first_index: 0,
+			                                                   vertex_offset: 0,
+			                                                   first_instance: index as u32 });
+		}
+		
+		if batches.is_empty() {
+			return Ok(());
+		}
+		
+		// All of the eye's commands go into one buffer, each batch draws its own range of it
+		let commands: Vec<DrawIndexedIndirectCommand> = batches.iter().flat_map(|(_, _, commands)| commands.iter().cloned()).collect();
+		let commands = self.indirect.commands.chunk(commands)?;
+		let mut first = 0;
+		
+		for (index, sets, batch) in batches {
+			let range = BufferSlice::from_typed_buffer_access(commands.clone()).slice(first .. first + batch.len()).unwrap();
+			recorder.draw_indirect(self.pipeline, &scene[index].0, sets, self.set.clone(), range, batch.len() as u32)?;
+			first += batch.len();
+		}
+		
+		Ok(())
+	}
+}
+
+// Identifies what's bound for a model, regardless of the vtable behind it
+fn address<T: ?Sized>(value: &T) -> usize {
+	value as *const T as *const u8 as usize
+}
@@ -0,0 +1,136 @@
+use std::fs;
+use std::io;
+use std::convert::TryInto;
+use std::path::Path;
+use err_derive::Error;
+use image::RgbaImage;
+use vulkano::format::Format;
+
+use crate::renderer::bcn;
+
+const IDENTIFIER: [u8; 12] = [0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB, b'\r', b'\n', 0x1A, b'\n'];
+// Identifier, header and the index of the data format descriptor, key/value data and supercompression global data
+const LEVEL_INDEX_OFFSET: usize = 80;
+const LEVEL_INDEX_SIZE: usize = 24;
+
+// 2D texture from a KTX2 file, every mip level the file has, largest first. Only BC1, BC3 and BC7 and 8 bit RGBA are
+// supported, without supercompression.
+pub struct Ktx2 {
+	pub format: Format,
+	pub width: u32,
+	pub height: u32,
+	pub levels: Vec<Vec<u8>>,
+}
+
+impl Ktx2 {
+	pub fn load<P: AsRef<Path>>(path: P) -> Result<Ktx2, Ktx2Error> {
+		Ktx2::parse(&fs::read(path)?)
+	}
+	
+	pub fn parse(data: &[u8]) -> Result<Ktx2, Ktx2Error> {
+		if !data.starts_with(&IDENTIFIER) {
+			return Err(Ktx2Error::NotKtx2);
+		}
+		
+		let word = |offset: usize| data.get(offset .. offset + 4).map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap())).ok_or(Ktx2Error::Truncated);
+		let long = |offset: usize| data.get(offset .. offset + 8).map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()) as usize).ok_or(Ktx2Error::Truncated);
+		
+		let vk_format = word(12)?;
+		let width = word(20)?;
+		let height = word(24)?;
+		let depth = word(28)?;
+		let layers = word(32)?;
+		let faces = word(36)?;
+		let level_count = word(40)?;
+		let supercompression = word(44)?;
+		
+		let format = match vk_format {
+			37 => Format::R8G8B8A8Unorm,
+			43 => Format::R8G8B8A8Srgb,
+			133 => Format::BC1_RGBAUnormBlock,
+			134 => Format::BC1_RGBASrgbBlock,
+			137 => Format::BC3UnormBlock,
+			138 => Format::BC3SrgbBlock,
+			145 => Format::BC7UnormBlock,
+			146 => Format::BC7SrgbBlock,
+			_ => return Err(Ktx2Error::UnsupportedFormat(vk_format)),
+		};
+		
+		if width == 0 || height == 0 || depth > 1 || layers > 1 || faces != 1 {
+			return Err(Ktx2Error::UnsupportedLayout);
+		}
+		
+		if level_count > 32 - width.max(height).leading_zeros() {
+			return Err(Ktx2Error::InvalidLevelCount(level_count));
+		}
+		
+		if supercompression != 0 {
+			return Err(Ktx2Error::Supercompressed);
+		}
+		
+		// A level count of 0 asks for mipmaps to be generated, which only works for uncompressed images anyway
+		let mut levels = Vec::new();
+		for level in 0 .. level_count.max(1) as usize {
+			let index = LEVEL_INDEX_OFFSET + level * LEVEL_INDEX_SIZE;
+			let (offset, length) = (long(index)?, long(index + 8)?);
+			let bytes = data.get(offset .. offset.checked_add(length).ok_or(Ktx2Error::Truncated)?).ok_or(Ktx2Error::Truncated)?;
+			
+			if bytes.len() < level_size(format, (width >> level).max(1), (height >> level).max(1)) {
+				return Err(Ktx2Error::Truncated);
+			}
+			
+			levels.push(bytes.to_vec());
+		}
+		
+		Ok(Ktx2 {
+			format,
+			width,
+			height,
+			levels,
+		})
+	}
+	
+	pub fn compressed(&self) -> bool {
+		self.format.block_dimensions() != (1, 1)
+	}
+	
+	pub fn srgb(&self) -> bool {
+		match self.format {
+			Format::R8G8B8A8Srgb | Format::BC1_RGBASrgbBlock | Format::BC3SrgbBlock | Format::BC7SrgbBlock => true,
+			_ => false,
+		}
+	}
+	
+	// Largest level as 8 bit RGBA, for devices that can't sample the compressed format
+	pub fn decompress(&self) -> RgbaImage {
+		let data = &self.levels[0];
+		let (width, height) = (self.width, self.height);
+		
+		let pixels = match self.format {
+			Format::BC1_RGBAUnormBlock | Format::BC1_RGBASrgbBlock => bcn::decode(data, width, height, 8, bcn::bc1),
+			Format::BC3UnormBlock | Format::BC3SrgbBlock => bcn::decode(data, width, height, 16, bcn::bc3),
+			Format::BC7UnormBlock | Format::BC7SrgbBlock => bcn::decode(data, width, height, 16, bcn::bc7),
+			_ => data[.. (width * height * 4) as usize].to_vec(),
+		};
+		
+		RgbaImage::from_raw(width, height, pixels).unwrap()
+	}
+}
+
+// In bytes, block compressed formats cover partial blocks at the edges with whole ones
+fn level_size(format: Format, width: u32, height: u32) -> usize {
+	let (block_width, block_height) = format.block_dimensions();
+	let blocks = ((width + block_width - 1) / block_width) as usize * ((height + block_height - 1) / block_height) as usize;
+	blocks * format.size().unwrap()
+}
+
+#[derive(Debug, Error)]
+pub enum Ktx2Error {
+	#[error(display = "File is not a KTX2 file")] NotKtx2,
+	#[error(display = "KTX2 file is truncated")] Truncated,
+	#[error(display = "Unsupported KTX2 format {}, only BC1, BC3, BC7 and RGBA8 are supported", _0)] UnsupportedFormat(u32),
+	#[error(display = "Only 2D KTX2 textures without layers or faces are supported")] UnsupportedLayout,
+	#[error(display = "KTX2 file has {} mip levels, more than its size allows", _0)] InvalidLevelCount(u32),
+	#[error(display = "Supercompressed KTX2 files are not supported")] Supercompressed,
+	#[error(display = "{}", _0)] IoError(#[error(source)] io::Error),
+}
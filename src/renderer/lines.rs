@@ -0,0 +1,124 @@
+use std::sync::Arc;
+use err_derive::Error;
+use vulkano::device::Queue;
+use vulkano::buffer::{ImmutableBuffer, CpuBufferPool, BufferUsage};
+use vulkano::framebuffer::{Subpass, RenderPassAbstract};
+use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineCreationError};
+use vulkano::pipeline::vertex::SingleBufferDefinition;
+use vulkano::pipeline::depth_stencil::{DepthStencil, Stencil};
+use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::memory::DeviceMemoryAllocError;
+use vulkano::sync::GpuFuture;
+use vulkano::OomError;
+
+use crate::shaders;
+
+// Half the width of the grid, in meters
+const GRID_EXTENT: i32 = 10;
+const GRID_COLOR: [f32; 4] = [0.3, 0.3, 0.3, 1.0];
+const AXIS_LENGTH: f32 = 1.0;
+
+#[derive(Default, Copy, Clone)]
+pub struct LineVertex {
+	pos: [f32; 3],
+	color: [f32; 4],
+}
+
+vulkano::impl_vertex!(LineVertex, pos, color);
+
+impl LineVertex {
+	pub const fn new(pos: [f32; 3], color: [f32; 4]) -> Self {
+		LineVertex {
+			pos,
+			color,
+		}
+	}
+}
+
+type LinePipelineType = GraphicsPipeline<
+	SingleBufferDefinition<LineVertex>,
+	Box<dyn PipelineLayoutAbstract + Send + Sync>,
+	Arc<dyn RenderPassAbstract + Send + Sync>
+>;
+
+// Reference geometry and debug lines in world space, `Renderer::set_line_width` pixels wide and hidden behind models
+// like anything else. Alpha blends, without sorting against transparent models.
+pub struct Lines {
+	pub pipeline: Arc<LinePipelineType>,
+	// Floor at y = 0 with a line every meter
+	pub grid: Arc<ImmutableBuffer<[LineVertex]>>,
+	// X, Y and Z in red, green and blue, starting at the origin
+	pub axes: Arc<ImmutableBuffer<[LineVertex]>>,
+	// Lines of the next frame, two vertices each
+	pub pending: Vec<LineVertex>,
+	// All pending lines of a frame end up in one chunk
+	pub buffer: CpuBufferPool<LineVertex>,
+}
+
+impl Lines {
+	// The returned future finishes the upload of the grid and axes
+	pub fn new(queue: &Arc<Queue>, render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>, visible_stencil: Stencil) -> Result<(Lines, Box<dyn GpuFuture>), LinesError> {
+		let device = queue.device();
+		
+		let vs = shaders::line_vert::Shader::load(device.clone()).map_err(LinesError::ShaderLoadError)?;
+		let fs = shaders::line_frag::Shader::load(device.clone()).map_err(LinesError::ShaderLoadError)?;
+		
+		let pipeline = Arc::new(
+			GraphicsPipeline::start()
+			                 .vertex_input_single_buffer::<LineVertex>()
+			                 .vertex_shader(vs.main_entry_point(), ())
+			                 .line_list()
+			                 .line_width_dynamic()
+			                 .viewports_dynamic_scissors_irrelevant(1)
+			                 .fragment_shader(fs.main_entry_point(), ())
+			                 .blend_alpha_blending()
+			                 .depth_stencil(DepthStencil { stencil_front: visible_stencil,
+			                                               stencil_back: visible_stencil,
+			                                               ..DepthStencil::simple_depth_test() })
+			                 .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+			                 .build(device.clone())?
+		);
+		
+		let extent = GRID_EXTENT as f32;
+		let grid: Vec<LineVertex> = (-GRID_EXTENT ..= GRID_EXTENT).flat_map(|i| {
+			                                                          let i = i as f32;
+			                                                          vec![LineVertex::new([i, 0.0, -extent], GRID_COLOR),
+			                                                               LineVertex::new([i, 0.0,  extent], GRID_COLOR),
+			                                                               LineVertex::new([-extent, 0.0, i], GRID_COLOR),
+			                                                               LineVertex::new([ extent, 0.0, i], GRID_COLOR)]
+		                                                          })
+		                                                          .collect();
+		
+		let axes = [
+			LineVertex::new([0.0, 0.0, 0.0], [1.0, 0.0, 0.0, 1.0]),
+			LineVertex::new([AXIS_LENGTH, 0.0, 0.0], [1.0, 0.0, 0.0, 1.0]),
+			LineVertex::new([0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 1.0]),
+			LineVertex::new([0.0, AXIS_LENGTH, 0.0], [0.0, 1.0, 0.0, 1.0]),
+			LineVertex::new([0.0, 0.0, 0.0], [0.0, 0.0, 1.0, 1.0]),
+			LineVertex::new([0.0, 0.0, AXIS_LENGTH], [0.0, 0.0, 1.0, 1.0]),
+		];
+		
+		let (grid, grid_promise) = ImmutableBuffer::from_iter(grid.into_iter(),
+		                                                      BufferUsage{ vertex_buffer: true, ..BufferUsage::none() },
+		                                                      queue.clone())?;
+		
+		let (axes, axes_promise) = ImmutableBuffer::from_iter(axes.iter().cloned(),
+		                                                      BufferUsage{ vertex_buffer: true, ..BufferUsage::none() },
+		                                                      queue.clone())?;
+		
+		Ok((Lines {
+			pipeline,
+			grid,
+			axes,
+			pending: Vec::new(),
+			buffer: CpuBufferPool::vertex_buffer(device.clone()),
+		}, Box::new(grid_promise.join(axes_promise))))
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum LinesError {
+	#[error(display = "Failed to load shader: {}", _0)] ShaderLoadError(#[error(source, no_from)] OomError),
+	#[error(display = "{}", _0)] GraphicsPipelineCreationError(#[error(source)] GraphicsPipelineCreationError),
+	#[error(display = "{}", _0)] DeviceMemoryAllocError(#[error(source)] DeviceMemoryAllocError),
+}
@@ -0,0 +1,159 @@
+use std::sync::{Arc, Mutex};
+use err_derive::Error;
+use vulkano::descriptor::descriptor::DescriptorDesc;
+use vulkano::descriptor::descriptor_set::{DescriptorSet, DescriptorSetDesc, UnsafeDescriptorSet, UnsafeDescriptorSetLayout, UnsafeDescriptorPool,
+                                          DescriptorWrite, DescriptorPoolAllocError};
+use vulkano::device::{Device, DeviceOwned};
+use vulkano::buffer::BufferAccess;
+use vulkano::image::{ImmutableImage, ImageViewAccess};
+use vulkano::format::Format;
+use vulkano::sampler::Sampler;
+use vulkano::OomError;
+
+// Sets per Vulkan pool, another pool is added whenever they're all in use
+const POOL_SIZE: u32 = 64;
+
+// Texture, normal map and emissive map sets of the models, set 0 of the model pipelines. Sets of dropped models go back to a free
+// list the next model takes from, vulkano's own pools only hand out sets again while they're its newest pool.
+pub struct MaterialSets {
+	inner: Arc<Inner>,
+}
+
+struct Inner {
+	device: Arc<Device>,
+	layout: Arc<UnsafeDescriptorSetLayout>,
+	pools: Mutex<Pools>,
+}
+
+struct Pools {
+	// Never freed, the sets allocated from them are only ever recycled
+	pools: Vec<UnsafeDescriptorPool>,
+	free: Vec<UnsafeDescriptorSet>,
+}
+
+pub struct MaterialSet {
+	// Only taken when the set goes back to the free list
+	set: Option<UnsafeDescriptorSet>,
+	sets: Arc<Inner>,
+	images: [Arc<ImmutableImage<Format>>; 3],
+	// The set refers to it, it only has to stay alive
+	_sampler: Arc<Sampler>,
+}
+
+// Snapshot of `Renderer::material_set_usage`, for debugging
+#[derive(Debug, Copy, Clone, Default)]
+pub struct MaterialSetUsage {
+	// Sets held by models, including removed ones that frames in flight still draw
+	pub in_use: usize,
+	// Sets allocated from Vulkan, in use or waiting on the free list
+	pub allocated: usize,
+	pub pools: usize,
+}
+
+impl MaterialSets {
+	pub fn new(device: &Arc<Device>, layout: Arc<UnsafeDescriptorSetLayout>) -> MaterialSets {
+		MaterialSets {
+			inner: Arc::new(Inner {
+				device: device.clone(),
+				layout,
+				pools: Mutex::new(Pools { pools: Vec::new(), free: Vec::new() }),
+			}),
+		}
+	}
+	
+	pub fn alloc(&self, image: Arc<ImmutableImage<Format>>, normal_map: Arc<ImmutableImage<Format>>, emissive_map: Arc<ImmutableImage<Format>>, sampler: Arc<Sampler>)
+	            -> Result<Arc<MaterialSet>, MaterialSetError> {
+		let mut set = {
+			let mut pools = self.inner.pools.lock().unwrap();
+			
+			if pools.free.is_empty() {
+				let count = *self.inner.layout.descriptors_count() * POOL_SIZE;
+				let mut pool = UnsafeDescriptorPool::new(self.inner.device.clone(), &count, POOL_SIZE, false)?;
+				let sets = unsafe { pool.alloc((0 .. POOL_SIZE).map(|_| &*self.inner.layout))? };
+				
+				pools.free.extend(sets);
+				pools.pools.push(pool);
+			}
+			
+			pools.free.pop().unwrap()
+		};
+		
+		// Recycled sets are rewritten completely, nothing of the previous model is left
+		unsafe {
+			set.write(&self.inner.device, vec![DescriptorWrite::combined_image_sampler(0, 0, &sampler, &image),
+			                                   DescriptorWrite::combined_image_sampler(1, 0, &sampler, &normal_map),
+			                                   DescriptorWrite::combined_image_sampler(2, 0, &sampler, &emissive_map)].into_iter());
+		}
+		
+		Ok(Arc::new(MaterialSet {
+			set: Some(set),
+			sets: self.inner.clone(),
+			images: [image, normal_map, emissive_map],
+			_sampler: sampler,
+		}))
+	}
+	
+	pub fn usage(&self) -> MaterialSetUsage {
+		let pools = self.inner.pools.lock().unwrap();
+		let allocated = pools.pools.len() * POOL_SIZE as usize;
+		
+		MaterialSetUsage {
+			in_use: allocated - pools.free.len(),
+			allocated,
+			pools: pools.pools.len(),
+		}
+	}
+}
+
+// Only dropped once no command buffer uses the set anymore, they keep it alive until the GPU is done with them
+impl Drop for MaterialSet {
+	fn drop(&mut self) {
+		if let Some(set) = self.set.take() {
+			self.sets.pools.lock().unwrap().free.push(set);
+		}
+	}
+}
+
+unsafe impl DescriptorSet for MaterialSet {
+	fn inner(&self) -> &UnsafeDescriptorSet {
+		self.set.as_ref().unwrap()
+	}
+	
+	fn num_buffers(&self) -> usize {
+		0
+	}
+	
+	fn buffer(&self, _index: usize) -> Option<(&dyn BufferAccess, u32)> {
+		None
+	}
+	
+	fn num_images(&self) -> usize {
+		self.images.len()
+	}
+	
+	fn image(&self, index: usize) -> Option<(&dyn ImageViewAccess, u32)> {
+		self.images.get(index).map(|image| (&**image as &dyn ImageViewAccess, index as u32))
+	}
+}
+
+unsafe impl DescriptorSetDesc for MaterialSet {
+	fn num_bindings(&self) -> usize {
+		self.sets.layout.num_bindings()
+	}
+	
+	fn descriptor(&self, binding: usize) -> Option<DescriptorDesc> {
+		self.sets.layout.descriptor(binding)
+	}
+}
+
+unsafe impl DeviceOwned for MaterialSet {
+	fn device(&self) -> &Arc<Device> {
+		&self.sets.device
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum MaterialSetError {
+	#[error(display = "{}", _0)] OomError(#[error(source)] OomError),
+	#[error(display = "{}", _0)] DescriptorPoolAllocError(#[error(source)] DescriptorPoolAllocError),
+}
@@ -0,0 +1,157 @@
+use std::collections::HashSet;
+use std::os::raw::c_void;
+use std::mem;
+use vulkano::buffer::BufferAccess;
+use vulkano::image::ImageAccess;
+use vulkano::format::Format;
+use vulkano::instance::loader;
+use vulkano::VulkanObject;
+
+use crate::renderer::Renderer;
+
+// Byte counts are computed from the resource sizes, padding and alignment added by the allocator aren't included
+#[derive(Debug, Copy, Clone, Default)]
+pub struct MemoryReport {
+	// Vertex and index buffers of the models added with `add_model`
+	pub buffers: u64,
+	// Textures of those models and the skybox, including mipmaps
+	pub textures: u64,
+	// Color, multisampled color, FXAA input and depth images of both eyes, plus the shadow map
+	pub eyes: u64,
+	// Summed over the device local heaps, only available with VK_EXT_memory_budget
+	pub budget: Option<MemoryBudget>,
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct MemoryBudget {
+	// How much the application can allocate before running into trouble
+	pub budget: u64,
+	// How much the application has allocated, as seen by the driver
+	pub usage: u64,
+}
+
+impl Renderer {
+	pub fn memory_report(&self) -> MemoryReport {
+		let mut report = MemoryReport::default();
+		
+		// Clones of a model share its buffers and texture
+		let mut seen = HashSet::new();
+		
+		for model in self.scene.iter() {
+			if !seen.insert(model.vertices.inner().buffer.internal_object()) {
+				continue;
+			}
+			
+			report.buffers += model.vertices.size() as u64 + model.indices.as_ref().map_or(0, |indices| indices.size() as u64);
+			report.textures += image_bytes(&*model.image) + image_bytes(&*model.normal_map) + image_bytes(&*model.emissive_map);
+		}
+		
+		if let Some(skybox) = &self.skybox {
+			report.textures += image_bytes(&*skybox.image);
+		}
+		
+		for eye in &[&self.eyes.0, &self.eyes.1] {
+			report.eyes += eye.targets.iter().map(|target| image_bytes(&*target.image)).sum::<u64>();
+			report.eyes += image_bytes(&*eye.depth_image);
+			report.eyes += eye.msaa_image.as_ref().map_or(0, |image| image_bytes(&**image));
+			report.eyes += eye.fxaa.as_ref().map_or(0, |fxaa| image_bytes(&*fxaa.image));
+			report.eyes += eye.hdr.as_ref().map_or(0, |hdr| image_bytes(&*hdr.image));
+		}
+		
+		report.eyes += image_bytes(&*self.shadow_map.image);
+		
+		if self.memory_budget {
+			report.budget = self.query_memory_budget();
+		}
+		
+		report
+	}
+	
+	// vulkano 0.18 doesn't know VK_EXT_memory_budget, so this goes through the loader directly
+	fn query_memory_budget(&self) -> Option<MemoryBudget> {
+		let instance = self.instance.internal_object();
+		let physical = self.device.physical_device();
+		
+		let get_properties: GetPhysicalDeviceMemoryProperties2 = unsafe {
+			let function = loader::auto_loader().ok()?
+			                                    .get_instance_proc_addr(instance, b"vkGetPhysicalDeviceMemoryProperties2KHR\0".as_ptr() as *const _);
+			mem::transmute(function)
+		};
+		
+		let mut budget: PhysicalDeviceMemoryBudgetProperties = unsafe { mem::zeroed() };
+		budget.s_type = STRUCTURE_TYPE_PHYSICAL_DEVICE_MEMORY_BUDGET_PROPERTIES_EXT;
+		
+		let mut properties: PhysicalDeviceMemoryProperties2 = unsafe { mem::zeroed() };
+		properties.s_type = STRUCTURE_TYPE_PHYSICAL_DEVICE_MEMORY_PROPERTIES_2;
+		properties.p_next = &mut budget as *mut _ as *mut c_void;
+		
+		unsafe { get_properties(physical.internal_object(), &mut properties); }
+		
+		let mut result = MemoryBudget::default();
+		
+		for (index, heap) in physical.memory_heaps().enumerate() {
+			if heap.is_device_local() {
+				result.budget += budget.heap_budget[index];
+				result.usage += budget.heap_usage[index];
+			}
+		}
+		
+		Some(result)
+	}
+}
+
+fn image_bytes<I: ImageAccess + ?Sized>(image: &I) -> u64 {
+	let dimensions = image.dimensions();
+	// vulkano has no size for combined depth/stencil formats, these are what drivers typically allocate
+	let size = match image.format() {
+		Format::D16Unorm_S8Uint | Format::D24Unorm_S8Uint => 4,
+		Format::D32Sfloat_S8Uint => 8,
+		format => format.size().unwrap_or(0),
+	};
+	let texel = size as u64 * image.samples() as u64;
+	
+	(0 .. image.mipmap_levels()).map(|level| {
+		                            let width = (dimensions.width() >> level).max(1) as u64;
+		                            let height = (dimensions.height() >> level).max(1) as u64;
+		                            width * height * dimensions.array_layers() as u64 * texel
+	                            })
+	                            .sum()
+}
+
+// Just enough of the Vulkan headers for the budget query
+const STRUCTURE_TYPE_PHYSICAL_DEVICE_MEMORY_PROPERTIES_2: u32 = 1000059006;
+const STRUCTURE_TYPE_PHYSICAL_DEVICE_MEMORY_BUDGET_PROPERTIES_EXT: u32 = 1000237000;
+const MAX_MEMORY_TYPES: usize = 32;
+const MAX_MEMORY_HEAPS: usize = 16;
+
+type GetPhysicalDeviceMemoryProperties2 = unsafe extern "system" fn(usize, *mut PhysicalDeviceMemoryProperties2);
+
+#[repr(C)]
+struct MemoryType {
+	property_flags: u32,
+	heap_index: u32,
+}
+
+#[repr(C)]
+struct MemoryHeap {
+	size: u64,
+	flags: u32,
+}
+
+#[repr(C)]
+struct PhysicalDeviceMemoryProperties2 {
+	s_type: u32,
+	p_next: *mut c_void,
+	memory_type_count: u32,
+	memory_types: [MemoryType; MAX_MEMORY_TYPES],
+	memory_heap_count: u32,
+	memory_heaps: [MemoryHeap; MAX_MEMORY_HEAPS],
+}
+
+#[repr(C)]
+struct PhysicalDeviceMemoryBudgetProperties {
+	s_type: u32,
+	p_next: *mut c_void,
+	heap_budget: [u64; MAX_MEMORY_HEAPS],
+	heap_usage: [u64; MAX_MEMORY_HEAPS],
+}
@@ -0,0 +1,198 @@
+use std::sync::Arc;
+use err_derive::Error;
+use vulkano::device::Queue;
+use vulkano::image::{AttachmentImage, ImageUsage, SwapchainImage};
+use vulkano::instance::Instance;
+use vulkano::swapchain::{Swapchain, Surface, SurfaceTransform, PresentMode, FullscreenExclusive, ColorSpace, SwapchainCreationError, AcquireError, CapabilitiesError};
+use vulkano::swapchain;
+use vulkano::sync::GpuFuture;
+use vulkano::sampler::Filter;
+use vulkano::format;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, BlitImageError, BuildError, CommandBufferExecError};
+use vulkano::OomError;
+use vulkano_win::VkSurfaceBuild;
+use winit::event_loop::{EventLoop, ControlFlow};
+use winit::event::{Event, WindowEvent};
+use winit::window::{Window, WindowBuilder};
+use winit::platform::desktop::EventLoopExtDesktop;
+
+pub struct Mirror {
+	event_loop: EventLoop<()>,
+	surface: Arc<Surface<Window>>,
+	swapchain: Arc<Swapchain<Window>>,
+	images: Vec<Arc<SwapchainImage<Window>>>,
+	recreate_swapchain: bool,
+	closed: bool,
+}
+
+impl Mirror {
+	pub fn new(instance: &Arc<Instance>, queue: &Arc<Queue>, vsync: bool) -> Result<Mirror, MirrorCreationError> {
+		let event_loop = EventLoop::new();
+		let surface = WindowBuilder::new().with_title("vkeyes mirror")
+		                                  .build_vk_surface(&event_loop, instance.clone())?;
+		
+		if !surface.is_supported(queue.family())? {
+			return Err(MirrorCreationError::PresentNotSupported);
+		}
+		
+		let caps = surface.capabilities(queue.device().physical_device())?;
+		let dimensions = caps.current_extent.unwrap_or_else(|| surface.window().inner_size().into());
+		// Blits decode the sRGB eye image, so only an sRGB swapchain encodes it again. Otherwise the mirror looks too dark.
+		let format = caps.supported_formats
+		                 .iter()
+		                 .map(|&(format, _)| format)
+		                 .find(|&format| format == format::Format::B8G8R8A8Srgb || format == format::Format::R8G8B8A8Srgb)
+		                 .unwrap_or(caps.supported_formats[0].0);
+		let alpha = caps.supported_composite_alpha.iter().next().ok_or(MirrorCreationError::NoCompositeAlpha)?;
+		
+		// Unless asked to, never wait for the desktop's vsync, the HMD sets the pace
+		let mode = if vsync {
+			           PresentMode::Fifo
+		           } else if caps.present_modes.mailbox {
+			           PresentMode::Mailbox
+		           } else if caps.present_modes.immediate {
+			           PresentMode::Immediate
+		           } else {
+			           PresentMode::Fifo
+		           };
+		
+		let (swapchain, images) = Swapchain::new(queue.device().clone(),
+		                                         surface.clone(),
+		                                         caps.min_image_count,
+		                                         format,
+		                                         dimensions,
+		                                         1,
+		                                         ImageUsage { transfer_destination: true,
+		                                                      ..ImageUsage::none() },
+		                                         queue,
+		                                         SurfaceTransform::Identity,
+		                                         alpha,
+		                                         mode,
+		                                         FullscreenExclusive::Default,
+		                                         true,
+		                                         ColorSpace::SrgbNonLinear)?;
+		
+		Ok(Mirror {
+			event_loop,
+			surface,
+			swapchain,
+			images,
+			recreate_swapchain: false,
+			closed: false,
+		})
+	}
+	
+	pub fn closed(&self) -> bool {
+		self.closed
+	}
+	
+	pub fn invalidate(&mut self) {
+		self.recreate_swapchain = true;
+	}
+	
+	// Appends a blit of the top left `region` of `image` to the window's next swapchain image after `future`.
+	// Frames where the swapchain can't be used are skipped and `future` is returned as is.
+	pub fn present(&mut self, future: Box<dyn GpuFuture>, queue: &Arc<Queue>, image: &Arc<AttachmentImage<format::R8G8B8A8Srgb>>, region: [u32; 2]) -> Result<Box<dyn GpuFuture>, MirrorError> {
+		self.poll_events();
+		
+		if self.closed {
+			return Ok(future);
+		}
+		
+		if self.recreate_swapchain {
+			let dimensions: [u32; 2] = self.surface.window().inner_size().into();
+			
+			match self.swapchain.recreate_with_dimensions(dimensions) {
+				Ok((swapchain, images)) => {
+					self.swapchain = swapchain;
+					self.images = images;
+				},
+				// Happens while the window is minimized, try again next frame
+				Err(SwapchainCreationError::UnsupportedDimensions) => return Ok(future),
+				Err(err) => return Err(err.into()),
+			}
+			
+			self.recreate_swapchain = false;
+		}
+		
+		let (image_num, suboptimal, acquire_future) = match swapchain::acquire_next_image(self.swapchain.clone(), None) {
+			Ok(result) => result,
+			Err(AcquireError::OutOfDate) => {
+				self.recreate_swapchain = true;
+				return Ok(future);
+			},
+			Err(err) => return Err(err.into()),
+		};
+		
+		if suboptimal {
+			self.recreate_swapchain = true;
+		}
+		
+		let target = self.swapchain.dimensions();
+		
+		let command_buffer = AutoCommandBufferBuilder::primary_one_time_submit(queue.device().clone(), queue.family())?
+		                                              .blit_image(image.clone(),
+		                                                          [0, 0, 0],
+		                                                          [region[0] as i32, region[1] as i32, 1],
+		                                                          0,
+		                                                          0,
+		                                                          self.images[image_num].clone(),
+		                                                          [0, 0, 0],
+		                                                          [target[0] as i32, target[1] as i32, 1],
+		                                                          0,
+		                                                          0,
+		                                                          1,
+		                                                          Filter::Linear)?
+		                                              .build()?;
+		
+		Ok(Box::new(future.join(acquire_future)
+		                  .then_execute(queue.clone(), command_buffer)?
+		                  .then_swapchain_present(queue.clone(), self.swapchain.clone(), image_num)))
+	}
+	
+	fn poll_events(&mut self) {
+		let mut recreate_swapchain = false;
+		let mut closed = false;
+		
+		self.event_loop.run_return(|event, _, control_flow| {
+			match event {
+				Event::WindowEvent { event: WindowEvent::Resized(_), .. } => recreate_swapchain = true,
+				Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => closed = true,
+				Event::MainEventsCleared => *control_flow = ControlFlow::Exit,
+				_ => {},
+			}
+		});
+		
+		self.recreate_swapchain |= recreate_swapchain;
+		self.closed |= closed;
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum MirrorCreationError {
+	#[error(display = "Graphics queue can't present to the mirror window.")] PresentNotSupported,
+	#[error(display = "Mirror window doesn't support any composite alpha mode.")] NoCompositeAlpha,
+	#[error(display = "{}", _0)] WindowCreationError(#[error(source)] vulkano_win::CreationError),
+	#[error(display = "{}", _0)] CapabilitiesError(#[error(source)] CapabilitiesError),
+	#[error(display = "{}", _0)] SwapchainCreationError(#[error(source)] SwapchainCreationError),
+}
+
+#[derive(Debug, Error)]
+pub enum MirrorError {
+	#[error(display = "{}", _0)] SwapchainCreationError(#[error(source)] SwapchainCreationError),
+	#[error(display = "{}", _0)] AcquireError(#[error(source)] AcquireError),
+	#[error(display = "{}", _0)] OomError(#[error(source)] OomError),
+	#[error(display = "{}", _0)] BlitImageError(#[error(source)] BlitImageError),
+	#[error(display = "{}", _0)] BuildError(#[error(source)] BuildError),
+	#[error(display = "{}", _0)] CommandBufferExecError(#[error(source)] CommandBufferExecError),
+}
+
+impl MirrorError {
+	pub fn device_lost(&self) -> bool {
+		match self {
+			MirrorError::SwapchainCreationError(SwapchainCreationError::DeviceLost) => true,
+			MirrorError::AcquireError(AcquireError::DeviceLost) => true,
+			_ => false,
+		}
+	}
+}
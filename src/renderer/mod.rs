@@ -1,28 +1,119 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::ffi::CString;
+use std::cmp::Ordering;
+use std::path::Path;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use std::sync::mpsc::Sender;
 use err_derive::Error;
 use vulkano::{app_info_from_cargo_toml, OomError};
-use vulkano::device::{Device, DeviceExtensions, RawDeviceExtensions, Features, Queue, DeviceCreationError};
-use vulkano::instance::debug::{DebugCallback, MessageSeverity, MessageType};
-use vulkano::instance::{Instance, InstanceExtensions, RawInstanceExtensions, PhysicalDevice, LayersListError, InstanceCreationError};
-use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineCreationError};
-use vulkano::sync::{GpuFuture, FlushError};
+use vulkano::instance::Version;
+use vulkano::device::{Device, DeviceExtensions, RawDeviceExtensions, Queue, DeviceCreationError};
+pub use vulkano::device::Features;
+pub use vulkano::format::Format;
+use vulkano::instance::debug::{DebugCallback, DebugCallbackCreationError, MessageSeverity, MessageType};
+use vulkano::instance::{Instance, InstanceExtensions, PhysicalDevice, QueueFamily, LayersListError, InstanceCreationError};
+use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineBuilder, GraphicsPipelineCreationError};
+use vulkano::sync::{GpuFuture, FlushError, FenceSignalFuture};
 use vulkano::sync;
 use vulkano::pipeline::viewport::Viewport;
+use vulkano::pipeline::shader::GraphicsEntryPoint;
+use vulkano::pipeline::depth_stencil::{DepthStencil, Stencil, StencilOp, Compare};
+use vulkano::pipeline::blend::AttachmentBlend;
+pub use vulkano::pipeline::raster::{CullMode, FrontFace};
+pub use openvr::TrackingUniverseOrigin;
 use vulkano::framebuffer::{Subpass, RenderPassCreationError, RenderPassAbstract};
-use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState, BeginRenderPassError, AutoCommandBufferBuilderContextError, BuildError, CommandBufferExecError, DrawIndexedError};
-use vulkano::format::ClearValue;
-use openvr::{System, Compositor};
-use cgmath::{Matrix4, Transform, Matrix, Vector2, Euler, Rad};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState, BeginRenderPassError, AutoCommandBufferBuilderContextError, BuildError, CommandBufferExecError, DrawIndexedError, DrawError, ExecuteCommandsError};
+use vulkano::command_buffer::synced::SyncCommandBufferBuilderError;
+use vulkano::format::{ClearValue, FormatTy};
+use vulkano::framebuffer::FramebufferAbstract;
+use vulkano::image::AttachmentImage;
+use vulkano::pipeline::vertex::BufferlessVertices;
+use vulkano::buffer::{CpuBufferPool, CpuAccessibleBuffer, BufferUsage, BufferAccess, TypedBufferAccess};
+use vulkano::buffer::cpu_pool::CpuBufferPoolChunk;
+use vulkano::buffer::cpu_access::WriteLockError;
+use vulkano::memory::pool::StdMemoryPool;
+use vulkano::memory::DeviceMemoryAllocError;
+use vulkano::sampler::{Sampler, Filter, MipmapMode, SamplerAddressMode, SamplerCreationError};
+use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::descriptor::descriptor_set::{DescriptorSet, PersistentDescriptorSet, PersistentDescriptorSetError, PersistentDescriptorSetBuildError};
+use openvr::{System, Compositor, Chaperone, TrackedDeviceIndex, TrackedDevicePose, TrackedDevicePoses, tracked_device_index};
+use openvr::compositor::texture::Bounds;
+use cgmath::{Matrix4, Transform, Matrix, SquareMatrix, InnerSpace, Point3, Vector2, Vector3, Rad, Deg};
 use openvr::compositor::CompositorError;
+use log::{info, warn, debug, log, Level};
 
 pub mod model;
 mod eye;
+mod mirror;
+mod frustum;
+mod skybox;
+mod capture;
+mod timer;
+mod scene;
+mod memory;
+mod gpu_timer;
+mod resolution;
+mod stl;
+mod ply;
+mod shadow;
+mod fxaa;
+mod tonemap;
+mod occlusion;
+mod culling;
+mod secondary;
+mod prerecorded;
+mod transforms;
+mod material_sets;
+mod lines;
+mod text;
+mod skin;
+mod ktx2;
+mod bcn;
+mod thumbnail;
+mod reflection;
+mod query_results;
+#[cfg(feature = "hot-reload")]
+mod hot_reload;
+#[cfg(feature = "indirect-draw")]
+mod indirect;
+pub mod target;
+pub mod overlay;
 
 use crate::shaders;
 use crate::openvr_vulkan::*;
+use crate::input::{Haptics, InputError};
 use crate::renderer::eye::EyeCreationError;
-use crate::renderer::model::Model;
+use crate::renderer::model::{Model, Indices};
+use crate::renderer::mirror::{MirrorCreationError, MirrorError};
 use eye::Eye;
+use mirror::Mirror;
+use frustum::Frustum;
+use skybox::{Skybox, SkyboxError};
+use timer::FrameTimer;
+use scene::Scene;
+use gpu_timer::{GpuTimer, GpuTimerError};
+use resolution::AdaptiveResolution;
+use shadow::{ShadowMap, ShadowMapError};
+use fxaa::{Fxaa, FxaaError};
+use tonemap::{Tonemap, TonemapError};
+use occlusion::{Occlusion, OcclusionError};
+use culling::{AsyncCulling, CullDispatch, CullingError};
+use secondary::{EyeDraws, SceneCommands, ModelCommandBuffer};
+use prerecorded::{Prerecorded, RecordKey};
+use transforms::{Transforms, TransformSet};
+use material_sets::MaterialSets;
+use reflection::Reflection;
+use lines::{Lines, LineVertex, LinesError};
+use text::{Text, TextVertex, TextError};
+pub use scene::{ModelHandle, InvalidHandleError, ParentError};
+pub use material_sets::MaterialSetUsage;
+pub use reflection::ReflectionError;
+use target::{Target, OpenVRTarget, HeadlessTarget};
+#[cfg(feature = "hot-reload")]
+use hot_reload::ShaderWatcher;
+#[cfg(feature = "indirect-draw")]
+use indirect::Indirect;
 
 // workaround https://github.com/vulkano-rs/vulkano/issues/709
 type PipelineType = GraphicsPipeline<
@@ -31,54 +122,813 @@ type PipelineType = GraphicsPipeline<
 	std::sync::Arc<dyn RenderPassAbstract + Send + Sync>
 >;
 
+// Set with `Renderer::set_update`
+type UpdateFn = dyn FnMut(&mut Renderer, Duration);
+
+// Bits of an eye in the visibility from `AsyncCulling`
+const LEFT_VISIBLE: u32 = 1;
+const RIGHT_VISIBLE: u32 = 2;
+
+// Decides which models `Renderer::draw_order` skips
+#[derive(Copy, Clone)]
+enum Culling<'a> {
+	// Draws everything, for recordings that outlive the view
+	None,
+	Frustum(&'a Frustum),
+	// Frustum of a reflected view, the occlusion results only hold for the eye's own
+	Reflection(&'a Frustum),
+	// Results of `AsyncCulling` by scene index, with the eye's bit
+	Visibility(&'a [u32], u32),
+}
+
 pub struct Renderer {
 	pub instance: Arc<Instance>,
 	
+	debug_callback: Option<DebugCallback>,
 	device: Arc<Device>,
 	queue: Arc<Queue>,
 	load_queue: Arc<Queue>,
+	extra_queues: Vec<Arc<Queue>>,
 	pipeline: Arc<PipelineType>,
+	transparent_pipeline: Arc<PipelineType>,
+	wireframe_pipeline: Option<Arc<PipelineType>>,
+	points_pipeline: Arc<PipelineType>,
+	prepassed_pipeline: Arc<PipelineType>,
+	wireframe: bool,
+	// None if the device lacks the features it needs
+	#[cfg(feature = "indirect-draw")]
+	indirect: Option<Indirect>,
+	#[cfg(feature = "indirect-draw")]
+	indirect_draw: bool,
+	skybox_pipeline: Arc<PipelineType>,
+	skybox: Option<Skybox>,
+	hidden_area_pipeline: Arc<PipelineType>,
+	fade_pipeline: Arc<PipelineType>,
+	// Covers the whole viewport
+	fade_triangle: Arc<CpuAccessibleBuffer<[model::Vertex]>>,
+	shadow_pipeline: Arc<PipelineType>,
+	shadow_map: ShadowMap,
+	prepass_pipeline: Arc<PipelineType>,
+	depth_prepass: bool,
+	fxaa_pass: Option<Fxaa>,
+	fxaa: bool,
+	tonemap: Option<Tonemap>,
+	// In stops
+	exposure: f32,
+	lines: Lines,
+	// In pixels, within the device's line width range
+	line_width: f32,
+	show_grid: bool,
+	show_axes: bool,
+	debug_bounds: bool,
+	chaperone: Option<Chaperone>,
+	show_boundary: bool,
+	boundary_color: [f32; 4],
+	text: Text,
+	#[cfg(feature = "hot-reload")]
+	shader_watcher: ShaderWatcher,
+	pipeline_desc: PipelineDesc,
+	reflection: Option<Reflection>,
 	eyes: (Eye, Eye),
-	compositor: Compositor,
+	msaa: u32,
+	clear_color: ([f32; 4], [f32; 4]),
+	light_direction: Vector3<f32>,
+	ambient: f32,
+	view: Matrix4<f32>,
+	ipd_scale: f32,
+	frustum_culling: bool,
+	occlusion: Option<Occlusion>,
+	occlusion_culling: bool,
+	// Culls on a compute queue instead of the CPU, see `RendererBuilder::async_culling`
+	async_culling: Option<AsyncCulling>,
+	// Models skipped by occlusion culling in the last frame
+	occluded: usize,
+	// Projection and view of both eyes in the last frame, and the model matrices by scene index along with
+	// `Model::id` of their model
+	previous_views: Option<((Matrix4<f32>, Matrix4<f32>), (Matrix4<f32>, Matrix4<f32>))>,
+	previous_matrices: Vec<(u64, Matrix4<f32>)>,
+	static_scene: bool,
+	prerecorded: Option<Prerecorded>,
+	light_buffer: CpuBufferPool<LightUniform>,
+	view_buffer: CpuBufferPool<ViewUniform>,
+	joint_buffer: CpuBufferPool<JointUniform>,
+	transforms: Transforms,
+	material_sets: MaterialSets,
+	sampler: Arc<Sampler>,
+	memory_budget: bool,
+	system: Option<System>,
+	compositor: Option<Compositor>,
+	haptics: Option<Haptics>,
+	frame_timer: FrameTimer,
+	update: Option<Box<UpdateFn>>,
+	last_update: Option<Instant>,
+	gpu_timer: Option<GpuTimer>,
+	// Of the left and right eye's passes in the last measured frame
+	gpu_times: (Duration, Duration),
+	adaptive_resolution: Option<AdaptiveResolution>,
+	scene: Scene,
+	head_pose: [[f32; 4]; 3],
+	// Poses of the frame being drawn, or the last one drawn in between frames
+	predicted_poses: Option<TrackedDevicePoses>,
+	frame_pose: [[f32; 4]; 3],
+	tracking_loss: TrackingLossPolicy,
+	// Since when the HMD's pose has been invalid
+	tracking_lost: Option<Instant>,
+	eye_rotation: (Vector2<f32>, Vector2<f32>),
+	mirror: Option<Mirror>,
 	previous_frame_end: Option<Box<dyn GpuFuture>>,
+	// Fences of the submitted frames, oldest first
+	in_flight: VecDeque<FrameFence>,
+	frames_in_flight: usize,
 }
 
-// Translates OpenGL projection matrix to Vulkan
-const CLIP: Matrix4<f32> = Matrix4::new(
+type FrameFence = Arc<FenceSignalFuture<Box<dyn GpuFuture>>>;
+
+// A frame from `Renderer::render_deferred` that still has to be submitted
+pub struct PendingFrame {
+	hmd_pose: [[f32; 4]; 3],
+	region: [u32; 2],
+	queries: Vec<occlusion::Query>,
+	stereo: bool,
+}
+
+// Translates OpenGL projection matrix to Vulkan: flips Y and maps depth from -1..1 to 0..1. Only touches clip space,
+// imported models are brought into world space by `model::Axes` long before that.
+pub const CLIP: Matrix4<f32> = Matrix4::new(
 	1.0, 0.0, 0.0, 0.0,
 	0.0,-1.0, 0.0, 0.0,
 	0.0, 0.0, 0.5, 0.0,
 	0.0, 0.0, 0.5, 1.0,
 );
 
+const DEFAULT_CLEAR_COLOR: [f32; 4] = [0.5, 0.5, 0.5, 1.0];
+const DEFAULT_LIGHT_DIRECTION: Vector3<f32> = Vector3::new(-0.3, -1.0, -0.5);
+const DEFAULT_AMBIENT: f32 = 0.3;
+const HEADLESS_FOV: Deg<f32> = Deg(90.0);
+const DEFAULT_ANISOTROPY: f32 = 8.0;
+const DEFAULT_EYE_BUFFERS: usize = 3;
+const DEFAULT_FRAMES_IN_FLIGHT: usize = 2;
+const DEFAULT_SHADOW_MAP_SIZE: u32 = 2048;
+// Graphics and load queue
+const DEFAULT_QUEUE_PRIORITIES: (f32, f32) = (0.5, 0.2);
+const BOUNDS_COLOR: [f32; 4] = [1.0, 1.0, 0.0, 1.0];
+const DEFAULT_BOUNDARY_COLOR: [f32; 4] = [0.0, 0.8, 1.0, 1.0];
+// Walls of the play area boundary, drawn as a lattice of lines
+const BOUNDARY_HEIGHT: f32 = 2.0;
+const BOUNDARY_SPACING: f32 = 0.5;
+// Lines are split this finely so the fade follows the distance along them
+const BOUNDARY_SEGMENT: f32 = 0.25;
+// Walls are fully visible closer than the first distance and gone beyond the second
+const BOUNDARY_FADE: (f32, f32) = (0.5, 1.5);
+// Frame budget of adaptive resolution when there is no HMD to ask
+const DEFAULT_REFRESH_RATE: f32 = 60.0;
+// vulkano 0.18 doesn't know this extension yet
+const MEMORY_BUDGET_EXTENSION: &str = "VK_EXT_memory_budget";
+// VK_LOD_CLAMP_NONE
+const MAX_LOD: f32 = 1000.0;
+// Seconds `TrackingLossPolicy::FadeToBlack` takes to go fully black
+const TRACKING_LOSS_FADE: f32 = 0.5;
+
+const IDENTITY_POSE: [[f32; 4]; 3] = [
+	[1.0, 0.0, 0.0, 0.0],
+	[0.0, 1.0, 0.0, 0.0],
+	[0.0, 0.0, 1.0, 0.0],
+];
+
+// In order of preference, SDKs since 1.1.126 only ship the KHRONOS layer
+const DEFAULT_VALIDATION_LAYERS: [&str; 2] = ["VK_LAYER_KHRONOS_validation", "VK_LAYER_LUNARG_standard_validation"];
+
+// Hidden area fragments are marked with 1, everything else only draws where that bit is still cleared. The others mark
+// the mirror, see reflection.rs.
+const VISIBLE_STENCIL: Stencil = Stencil {
+	compare: Compare::Equal,
+	pass_op: StencilOp::Keep,
+	fail_op: StencilOp::Keep,
+	depth_fail_op: StencilOp::Keep,
+	compare_mask: Some(1),
+	write_mask: Some(0),
+	reference: Some(0),
+};
+
+const HIDDEN_STENCIL: Stencil = Stencil {
+	compare: Compare::Always,
+	pass_op: StencilOp::Replace,
+	fail_op: StencilOp::Keep,
+	depth_fail_op: StencilOp::Keep,
+	compare_mask: Some(0xff),
+	write_mask: Some(0xff),
+	reference: Some(1),
+};
+
+type VertexEntryPoint<'a> = GraphicsEntryPoint<'a, (), shaders::vert::MainInput, shaders::vert::MainOutput, shaders::vert::Layout>;
+type FragmentEntryPoint<'a> = GraphicsEntryPoint<'a, (), shaders::frag::MainInput, shaders::frag::MainOutput, shaders::frag::Layout>;
+type DepthVertexEntryPoint<'a> = GraphicsEntryPoint<'a, (), shaders::depth_vert::MainInput, shaders::depth_vert::MainOutput, shaders::depth_vert::Layout>;
+type DepthFragmentEntryPoint<'a> = GraphicsEntryPoint<'a, (), shaders::shadow_frag::MainInput, shaders::shadow_frag::MainOutput, shaders::shadow_frag::Layout>;
+
+// Everything the model pipelines are built from besides the shaders, kept around so they can be rebuilt
+#[derive(Clone)]
+struct PipelineDesc {
+	render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+	cull_mode: CullMode,
+	front_face: FrontFace,
+	// Stencil test against the hidden area, or none if the depth format has no stencil
+	visible_stencil: Stencil,
+}
+
+struct ModelPipelines {
+	pipeline: Arc<PipelineType>,
+	transparent_pipeline: Arc<PipelineType>,
+	wireframe_pipeline: Option<Arc<PipelineType>>,
+	points_pipeline: Arc<PipelineType>,
+	// Opaque models after the depth prepass, only the closest surface passes
+	prepassed_pipeline: Arc<PipelineType>,
+}
+
+impl PipelineDesc {
+	// vulkano 0.18 has no way to build pipelines with a PipelineCache, so repeat runs rely on the driver's own shader cache
+	fn build(&self, device: &Arc<Device>, vs: VertexEntryPoint, fs: FragmentEntryPoint) -> Result<ModelPipelines, GraphicsPipelineCreationError> {
+		let build_pipeline = |wireframe: bool, transparent: bool, prepassed: bool| -> Result<Arc<PipelineType>, GraphicsPipelineCreationError> {
+			let builder = GraphicsPipeline::start()
+			                               .vertex_input_single_buffer::<model::Vertex>()
+			                               .vertex_shader(vs, ())
+			                               .viewports_dynamic_scissors_irrelevant(1)
+			                               .fragment_shader(fs, ())
+			                               .depth_stencil(DepthStencil { depth_compare: if prepassed { Compare::Equal } else { Compare::Less },
+			                                                             depth_write: !transparent && !prepassed,
+			                                                             stencil_front: self.visible_stencil,
+			                                                             stencil_back: self.visible_stencil,
+			                                                             ..DepthStencil::simple_depth_test() })
+			                               .render_pass(Subpass::from(self.render_pass.clone(), 0).unwrap());
+			
+			let builder = if wireframe {
+				              builder.polygon_mode_line()
+			              } else {
+				              builder.polygon_mode_fill()
+			              };
+			
+			let builder = if transparent {
+				              builder.blend_alpha_blending()
+			              } else {
+				              builder
+			              };
+			
+			let builder = face_culling(builder, self.cull_mode, self.front_face);
+			
+			// The transforms in set 3 are bound once per frame and picked with a dynamic offset per model
+			Ok(Arc::new(builder.with_auto_layout(device.clone(), &[(3, 0)])?))
+		};
+		
+		let wireframe_pipeline = if device.enabled_features().fill_mode_non_solid {
+			                         Some(build_pipeline(true, false, false)?)
+		                         } else {
+			                         None
+		                         };
+		
+		// Point clouds have no faces to cull
+		let points_pipeline = GraphicsPipeline::start()
+		                                       .vertex_input_single_buffer::<model::Vertex>()
+		                                       .vertex_shader(vs, ())
+		                                       .point_list()
+		                                       .viewports_dynamic_scissors_irrelevant(1)
+		                                       .fragment_shader(fs, ())
+		                                       .depth_stencil(DepthStencil { stencil_front: self.visible_stencil,
+		                                                                     stencil_back: self.visible_stencil,
+		                                                                     ..DepthStencil::simple_depth_test() })
+		                                       .cull_mode_disabled()
+		                                       .render_pass(Subpass::from(self.render_pass.clone(), 0).unwrap())
+		                                       .with_auto_layout(device.clone(), &[(3, 0)])?;
+		
+		Ok(ModelPipelines {
+			pipeline: build_pipeline(false, false, false)?,
+			transparent_pipeline: build_pipeline(false, true, false)?,
+			wireframe_pipeline,
+			points_pipeline: Arc::new(points_pipeline),
+			prepassed_pipeline: build_pipeline(false, false, true)?,
+		})
+	}
+	
+	// Same culling as the model pipelines, so the prepass leaves no depth where they draw nothing
+	fn build_prepass(&self, device: &Arc<Device>, vs: DepthVertexEntryPoint, fs: DepthFragmentEntryPoint) -> Result<Arc<PipelineType>, GraphicsPipelineCreationError> {
+		let builder = GraphicsPipeline::start()
+		                               .vertex_input_single_buffer::<model::Vertex>()
+		                               .vertex_shader(vs, ())
+		                               .viewports_dynamic_scissors_irrelevant(1)
+		                               .fragment_shader(fs, ())
+		                               .depth_stencil(DepthStencil { stencil_front: self.visible_stencil,
+		                                                             stencil_back: self.visible_stencil,
+		                                                             ..DepthStencil::simple_depth_test() })
+		                               .blend_collective(AttachmentBlend { mask_red: false,
+		                                                                   mask_green: false,
+		                                                                   mask_blue: false,
+		                                                                   mask_alpha: false,
+		                                                                   ..AttachmentBlend::pass_through() })
+		                               .render_pass(Subpass::from(self.render_pass.clone(), 0).unwrap());
+		
+		// Binds the same transforms as the model pipelines, in set 2
+		Ok(Arc::new(face_culling(builder, self.cull_mode, self.front_face).with_auto_layout(device.clone(), &[(2, 0)])?))
+	}
+}
+
+// Every pipeline drawing models culls the same way, so they agree on which faces are visible
+fn face_culling<Vdef, Vs, Vss, Tcs, Tcss, Tes, Tess, Gs, Gss, Fs, Fss, Rp>(builder: GraphicsPipelineBuilder<Vdef, Vs, Vss, Tcs, Tcss, Tes, Tess, Gs, Gss, Fs, Fss, Rp>, cull_mode: CullMode, front_face: FrontFace)
+                                                                          -> GraphicsPipelineBuilder<Vdef, Vs, Vss, Tcs, Tcss, Tes, Tess, Gs, Gss, Fs, Fss, Rp> {
+	let builder = match cull_mode {
+		CullMode::None => builder.cull_mode_disabled(),
+		CullMode::Front => builder.cull_mode_front(),
+		CullMode::Back => builder.cull_mode_back(),
+		CullMode::FrontAndBack => builder.cull_mode_front_and_back(),
+	};
+	
+	match front_face {
+		FrontFace::CounterClockwise => builder.front_face_counter_clockwise(),
+		FrontFace::Clockwise => builder.front_face_clockwise(),
+	}
+}
+
+// Mirrors the `Light` uniform block of the fragment shader
+#[derive(Copy, Clone)]
+#[repr(C)]
+struct LightUniform {
+	direction: [f32; 3],
+	ambient: f32,
+	// Light space projection and view, for looking up the shadow map
+	pv: [[f32; 4]; 4],
+}
+
+// Mirrors the `View` uniform block of the vertex shader
+#[derive(Copy, Clone)]
+#[repr(C)]
+struct ViewUniform {
+	pv: [[f32; 4]; 4],
+	// Same eye in the previous frame, for the velocity
+	previous_pv: [[f32; 4]; 4],
+}
+
+// Mirrors the `Joints` uniform block of the vertex shaders
+#[derive(Copy, Clone)]
+#[repr(C)]
+struct JointUniform {
+	matrices: [[[f32; 4]; 4]; skin::MAX_JOINTS],
+}
+
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+	pub index: usize,
+	pub name: String,
+	pub api_version: Version,
+	pub driver_version: u32,
+}
+
+impl From<PhysicalDevice<'_>> for DeviceInfo {
+	fn from(physical: PhysicalDevice) -> Self {
+		DeviceInfo {
+			index: physical.index(),
+			name: physical.name().to_string(),
+			api_version: physical.api_version(),
+			driver_version: physical.driver_version(),
+		}
+	}
+}
+
+// Only consulted when the target can't tell which device it is connected to
+#[derive(Debug, Clone)]
+pub enum DeviceSelector {
+	First,
+	Index(usize),
+	// Case insensitive substring of the device name
+	Name(String),
+}
+
+impl DeviceSelector {
+	fn select<'a>(&self, instance: &'a Arc<Instance>) -> Option<PhysicalDevice<'a>> {
+		match self {
+			DeviceSelector::First => PhysicalDevice::enumerate(instance).next(),
+			DeviceSelector::Index(index) => PhysicalDevice::from_index(instance, *index),
+			DeviceSelector::Name(name) => {
+				let name = name.to_lowercase();
+				PhysicalDevice::enumerate(instance).find(|physical| physical.name().to_lowercase().contains(&name))
+			},
+		}
+	}
+}
+
+// Family of the queues requested with `RendererBuilder::extra_queues`
+#[derive(Debug, Clone)]
+pub enum QueueFamilySelector {
+	// Index into the device's queue families
+	Index(u32),
+	Graphics,
+	// Prefers a family without graphics, so compute work can run alongside rendering
+	Compute,
+	// Prefers a family that only does transfers
+	Transfer,
+}
+
+impl QueueFamilySelector {
+	fn select<'a>(&self, physical: PhysicalDevice<'a>) -> Option<QueueFamily<'a>> {
+		match self {
+			QueueFamilySelector::Index(index) => physical.queue_family_by_id(*index),
+			QueueFamilySelector::Graphics => physical.queue_families().find(|q| q.supports_graphics()),
+			QueueFamilySelector::Compute => physical.queue_families()
+			                                        .find(|q| q.supports_compute() && !q.supports_graphics())
+			                                        .or_else(|| physical.queue_families().find(|q| q.supports_compute())),
+			QueueFamilySelector::Transfer => physical.queue_families()
+			                                         .find(|q| q.explicitly_supports_transfers() && !q.supports_graphics() && !q.supports_compute())
+			                                         .or_else(|| physical.queue_families().find(|q| q.explicitly_supports_transfers())),
+		}
+	}
+}
+
+// What the HMD shows while its pose isn't valid, e.g. when the base stations lose sight of it
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TrackingLossPolicy {
+	// Keeps drawing from the last valid pose
+	Freeze,
+	// Freezes and fades the frame to black, it comes back at once when tracking recovers
+	FadeToBlack,
+	// Draws from the identity pose, at the tracking origin
+	Identity,
+}
+
+#[derive(Debug, Clone)]
+pub struct ValidationMessage {
+	pub severity: MessageSeverity,
+	pub ty: MessageType,
+	pub layer_prefix: String,
+	pub description: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct RendererBuilder {
+	device: DeviceSelector,
+	debug: bool,
+	validation: Option<Sender<ValidationMessage>>,
+	validation_layers: Option<Vec<String>>,
+	features: Features,
+	clear_color: [f32; 4],
+	msaa: u32,
+	near: f32,
+	far: f32,
+	render_scale: f32,
+	cull_mode: CullMode,
+	front_face: FrontFace,
+	tracking_origin: TrackingUniverseOrigin,
+	ipd_scale: f32,
+	anisotropy: f32,
+	eye_buffers: usize,
+	frames_in_flight: usize,
+	adaptive_resolution: Option<(f32, f32)>,
+	clip: Matrix4<f32>,
+	shadow_map_size: u32,
+	depth_formats: Vec<Format>,
+	fxaa: bool,
+	hdr: bool,
+	mirror: bool,
+	queue_priorities: (f32, f32),
+	extra_queues: Vec<(QueueFamilySelector, Vec<f32>)>,
+	async_culling: bool,
+	tracking_loss: TrackingLossPolicy,
+	gpu_times: bool,
+}
+
+impl RendererBuilder {
+	pub fn new() -> RendererBuilder {
+		RendererBuilder {
+			device: DeviceSelector::First,
+			debug: false,
+			validation: None,
+			validation_layers: None,
+			features: Features::none(),
+			clear_color: DEFAULT_CLEAR_COLOR,
+			msaa: 1,
+			near: 0.1,
+			far: 1000.1,
+			render_scale: 1.0,
+			cull_mode: CullMode::None,
+			front_face: FrontFace::CounterClockwise,
+			tracking_origin: TrackingUniverseOrigin::Standing,
+			ipd_scale: 1.0,
+			anisotropy: DEFAULT_ANISOTROPY,
+			eye_buffers: DEFAULT_EYE_BUFFERS,
+			frames_in_flight: DEFAULT_FRAMES_IN_FLIGHT,
+			adaptive_resolution: None,
+			clip: CLIP,
+			shadow_map_size: DEFAULT_SHADOW_MAP_SIZE,
+			depth_formats: Vec::new(),
+			fxaa: false,
+			hdr: false,
+			mirror: false,
+			queue_priorities: DEFAULT_QUEUE_PRIORITIES,
+			extra_queues: Vec::new(),
+			async_culling: false,
+			tracking_loss: TrackingLossPolicy::Freeze,
+			gpu_times: false,
+		}
+	}
+	
+	// Only used when the target can't tell which device it is connected to
+	pub fn device(mut self, device: DeviceSelector) -> RendererBuilder {
+		self.device = device;
+		self
+	}
+	
+	pub fn device_index(self, index: usize) -> RendererBuilder {
+		self.device(DeviceSelector::Index(index))
+	}
+	
+	pub fn device_name(self, name: &str) -> RendererBuilder {
+		self.device(DeviceSelector::Name(name.to_string()))
+	}
+	
+	pub fn debug(mut self, debug: bool) -> RendererBuilder {
+		self.debug = debug;
+		self
+	}
+	
+	// Enables the validation layer and forwards its messages, in addition to logging them
+	pub fn validation(mut self, sender: Sender<ValidationMessage>) -> RendererBuilder {
+		self.validation = Some(sender);
+		self
+	}
+	
+	// Replaces the default layers enabled by `debug` and `validation`, missing ones are skipped with a warning
+	pub fn validation_layers(mut self, layers: &[&str]) -> RendererBuilder {
+		self.validation_layers = Some(layers.iter().map(|layer| layer.to_string()).collect());
+		self
+	}
+	
+	// Device features needed by custom shaders, creation fails if the device lacks any of them
+	pub fn features(mut self, features: Features) -> RendererBuilder {
+		self.features = features;
+		self
+	}
+	
+	pub fn clear_color(mut self, clear_color: [f32; 4]) -> RendererBuilder {
+		self.clear_color = clear_color;
+		self
+	}
+	
+	pub fn msaa(mut self, msaa: u32) -> RendererBuilder {
+		self.msaa = msaa;
+		self
+	}
+	
+	pub fn near(mut self, near: f32) -> RendererBuilder {
+		self.near = near;
+		self
+	}
+	
+	pub fn far(mut self, far: f32) -> RendererBuilder {
+		self.far = far;
+		self
+	}
+	
+	// Multiplies the recommended render target size
+	pub fn render_scale(mut self, render_scale: f32) -> RendererBuilder {
+		self.render_scale = render_scale;
+		self
+	}
+	
+	pub fn cull_mode(mut self, cull_mode: CullMode) -> RendererBuilder {
+		self.cull_mode = cull_mode;
+		self
+	}
+	
+	// Winding of front faces as seen on screen
+	pub fn front_face(mut self, front_face: FrontFace) -> RendererBuilder {
+		self.front_face = front_face;
+		self
+	}
+	
+	// Space the poses from `render_tracked` are in, seated poses can be recentered
+	pub fn tracking_origin(mut self, tracking_origin: TrackingUniverseOrigin) -> RendererBuilder {
+		self.tracking_origin = tracking_origin;
+		self
+	}
+	
+	// Multiplies the distance between the eyes, 0 renders both eyes from the same point
+	pub fn ipd_scale(mut self, ipd_scale: f32) -> RendererBuilder {
+		self.ipd_scale = ipd_scale;
+		self
+	}
+	
+	// Maximum anisotropic filtering of model textures, clamped to what the device supports. 1 turns it off.
+	pub fn anisotropy(mut self, anisotropy: f32) -> RendererBuilder {
+		self.anisotropy = anisotropy;
+		self
+	}
+	
+	// Number of images each eye cycles through, so rendering doesn't overwrite what the compositor still reads
+	pub fn eye_buffers(mut self, eye_buffers: usize) -> RendererBuilder {
+		self.eye_buffers = eye_buffers.max(1);
+		self
+	}
+	
+	// How many frames the CPU may record ahead of the GPU. Each eye gets at least this many images.
+	pub fn frames_in_flight(mut self, frames_in_flight: usize) -> RendererBuilder {
+		self.frames_in_flight = frames_in_flight.max(1);
+		self
+	}
+	
+	// Scales the rendered part of the eye images between `min` and `max` times the render scale, depending on how
+	// long the GPU takes per frame. Needs timestamp queries, without them the render scale stays as it is.
+	pub fn adaptive_resolution(mut self, min: f32, max: f32) -> RendererBuilder {
+		self.adaptive_resolution = Some((min.min(max), max));
+		self
+	}
+	
+	// Applied after OpenVR's projection, `CLIP` by default. Its Y flip brings Vulkan's winding back to the counter-clockwise
+	// front faces models are authored with, one that doesn't flip Y needs `FrontFace::Clockwise`.
+	pub fn clip(mut self, clip: Matrix4<f32>) -> RendererBuilder {
+		self.clip = clip;
+		self
+	}
+	
+	// Width and height of the shadow map, it covers all visible models so larger scenes need more
+	pub fn shadow_map_size(mut self, shadow_map_size: u32) -> RendererBuilder {
+		self.shadow_map_size = shadow_map_size.max(1);
+		self
+	}
+	
+	// Depth formats to try first, in order of preference. Formats the device can't render depth to are skipped, and
+	// if none is left the default ones are tried. Without a stencil aspect the hidden area is masked through depth.
+	pub fn depth_formats(mut self, depth_formats: &[Format]) -> RendererBuilder {
+		self.depth_formats = depth_formats.to_vec();
+		self
+	}
+	
+	// Smooths edges with a post-process pass instead of, or on top of, MSAA. Without it the pass can't be turned
+	// on later, with it `Renderer::set_fxaa` can switch it off and on again.
+	pub fn fxaa(mut self, fxaa: bool) -> RendererBuilder {
+		self.fxaa = fxaa;
+		self
+	}
+	
+	// Renders the scene into floating point images and maps them to the displayable range with the ACES filmic curve,
+	// so bright skyboxes and highlights roll off instead of clipping. Colors and lights can go above 1, the clear color
+	// goes through the curve as well. See `Renderer::set_exposure`.
+	pub fn hdr(mut self, hdr: bool) -> RendererBuilder {
+		self.hdr = hdr;
+		self
+	}
+	
+	// Opens a desktop window showing the left eye
+	pub fn mirror(mut self, mirror: bool) -> RendererBuilder {
+		self.mirror = mirror;
+		self
+	}
+	
+	// Priorities of the queue the renderer draws on and of the one models are uploaded on, between 0 and 1
+	pub fn queue_priorities(mut self, graphics: f32, load: f32) -> RendererBuilder {
+		self.queue_priorities = (graphics, load);
+		self
+	}
+	
+	// Creates one more queue per priority in the selected family, for the app's own work. Creation fails if the
+	// family doesn't have that many queues left besides the renderer's. See `Renderer::extra_queues`.
+	pub fn extra_queues(mut self, family: QueueFamilySelector, priorities: &[f32]) -> RendererBuilder {
+		self.extra_queues.push((family, priorities.to_vec()));
+		self
+	}
+	
+	// Culls models against the frustums and occlusion results on a compute queue, overlapping the previous frame's
+	// rendering. Needs a compute queue left over besides the renderer's and the extra ones, otherwise culling stays on
+	// the CPU. Static scenes are recorded without culling either way.
+	pub fn async_culling(mut self, async_culling: bool) -> RendererBuilder {
+		self.async_culling = async_culling;
+		self
+	}
+	
+	// Only applies to `render` and `render_tracked`, which get the validity of the pose from the compositor
+	pub fn tracking_loss_policy(mut self, tracking_loss: TrackingLossPolicy) -> RendererBuilder {
+		self.tracking_loss = tracking_loss;
+		self
+	}
+	
+	// Measures how long the GPU takes for each eye, see `Renderer::last_gpu_times`. Needs timestamp queries.
+	// Each eye gets a submission of its own, which costs a little.
+	pub fn gpu_times(mut self, gpu_times: bool) -> RendererBuilder {
+		self.gpu_times = gpu_times;
+		self
+	}
+	
+	pub fn build(&self, system: &System, compositor: Compositor) -> Result<Renderer, RendererCreationError> {
+		self.build_target(OpenVRTarget::new(system, compositor))
+	}
+	
+	pub fn build_target<T: Target>(&self, target: T) -> Result<Renderer, RendererCreationError> {
+		Renderer::create(target, self)
+	}
+}
+
+impl Default for RendererBuilder {
+	fn default() -> RendererBuilder {
+		RendererBuilder::new()
+	}
+}
+
+// vulkano doesn't name its features, but the Debug output has one `name: bool` pair per feature
+fn feature_names(features: &Features) -> String {
+	let debug = format!("{:?}", features);
+	let fields = debug.trim_start_matches("Features {").trim_end_matches('}');
+	
+	fields.split(',')
+	      .filter_map(|field| field.trim().strip_suffix(": true"))
+	      .collect::<Vec<_>>()
+	      .join(", ")
+}
+
+fn find_validation_layers(requested: Option<Vec<String>>) -> Result<Vec<String>, LayersListError> {
+	let available: Vec<String> = vulkano::instance::layers_list()?.map(|layer| layer.name().to_string()).collect();
+	
+	let layers: Vec<String> = match requested {
+		// The default layers overlap, so only the first available one is enabled
+		None => DEFAULT_VALIDATION_LAYERS.iter()
+		                                 .find(|&&layer| available.iter().any(|name| name == layer))
+		                                 .map(|layer| layer.to_string())
+		                                 .into_iter()
+		                                 .collect(),
+		Some(requested) => requested.into_iter()
+		                            .filter(|layer| {
+			                            let found = available.contains(layer);
+			                            if !found { warn!("Validation layer {} is not available", layer) }
+			                            found
+		                            })
+		                            .collect(),
+	};
+	
+	if layers.is_empty() {
+		warn!("No validation layer available, continuing without validation");
+	}
+	
+	Ok(layers)
+}
+
 impl Renderer {
-	pub fn new(system: &System, compositor: Compositor, device: Option<usize>, debug: bool) -> Result<Renderer, RendererCreationError> {
-		let recommended_size = system.recommended_render_target_size();
+	pub fn new(system: &System, compositor: Compositor, device: Option<usize>, debug: bool, msaa: u32) -> Result<Renderer, RendererCreationError> {
+		RendererBuilder::new().device(device.map_or(DeviceSelector::First, DeviceSelector::Index))
+		                      .debug(debug)
+		                      .msaa(msaa)
+		                      .build(system, compositor)
+	}
+	
+	// Same as `new`, but also opens a desktop window showing the left eye
+	pub fn with_mirror(system: &System, compositor: Compositor, device: Option<usize>, debug: bool, msaa: u32) -> Result<Renderer, RendererCreationError> {
+		RendererBuilder::new().device(device.map_or(DeviceSelector::First, DeviceSelector::Index))
+		                      .debug(debug)
+		                      .msaa(msaa)
+		                      .mirror(true)
+		                      .build(system, compositor)
+	}
+	
+	// Fixed perspective projection and no output besides `read_eye`, for automated tests
+	pub fn headless(size: (u32, u32)) -> Result<Renderer, RendererCreationError> {
+		RendererBuilder::new().build_target(HeadlessTarget::new(size, HEADLESS_FOV))
+	}
+	
+	fn create<T: Target>(target: T, options: &RendererBuilder) -> Result<Renderer, RendererCreationError> {
+		let RendererBuilder { device, debug, validation, validation_layers, features, clear_color, msaa, near, far, render_scale, cull_mode, front_face, tracking_origin, ipd_scale, anisotropy, eye_buffers, frames_in_flight, adaptive_resolution, clip, shadow_map_size, depth_formats, fxaa, hdr, mirror, queue_priorities, extra_queues, async_culling, tracking_loss, gpu_times } = options.clone();
+		let mirror = mirror || target.needs_mirror();
+		
+		let recommended_size = target.render_target_size();
 		
 		if debug {
-			println!("List of Vulkan debugging layers available to use:");
+			info!("List of Vulkan debugging layers available to use:");
 			let layers = vulkano::instance::layers_list()?;
 			for layer in layers {
-				println!("\t{}", layer.name());
+				info!("\t{}", layer.name());
 			}
 		}
 		
+		let layers = if debug || validation.is_some() {
+			             find_validation_layers(validation_layers)?
+		             } else {
+			             vec![]
+		             };
+		
+		// Without a layer there is nothing to report
+		let validate = !layers.is_empty();
+		
 		let instance = {
 			let app_infos = app_info_from_cargo_toml!();
-			let extensions = RawInstanceExtensions::new(compositor.vulkan_instance_extensions_required())
-			                                       .union(&(&InstanceExtensions { ext_debug_utils: debug,
-			                                                                      ..InstanceExtensions::none() }).into());
+			// Needed to query the memory budget
+			let properties2 = InstanceExtensions::supported_by_core().map_or(false, |supported| supported.khr_get_physical_device_properties2);
+			
+			let extensions = target.instance_extensions()
+			                       .union(&(&InstanceExtensions { ext_debug_utils: validate,
+			                                                      khr_get_physical_device_properties2: properties2,
+			                                                      ..InstanceExtensions::none() }).into());
 			
-			let layers = if debug {
-				             vec!["VK_LAYER_LUNARG_standard_validation"]
-			             } else {
-				             vec![]
-			             };
+			let extensions = if mirror {
+				                 extensions.union(&(&vulkano_win::required_extensions()).into())
+			                 } else {
+				                 extensions
+			                 };
 			
-			Instance::new(Some(&app_infos), extensions, layers)?
+			Instance::new(Some(&app_infos), extensions, layers.iter().map(String::as_str))?
 		};
 		
-		if debug {
+		let debug_callback = if validate {
 			let severity = MessageSeverity { error:       true,
 			                                 warning:     true,
 			                                 information: false,
@@ -86,69 +936,119 @@ impl Renderer {
 			
 			let ty = MessageType::all();
 			
-			let _debug_callback = DebugCallback::new(&instance, severity, ty, |msg| {
-				                                         let severity = if msg.severity.error {
-					                                         "error"
-				                                         } else if msg.severity.warning {
-					                                         "warning"
-				                                         } else if msg.severity.information {
-					                                         "information"
-				                                         } else if msg.severity.verbose {
-					                                         "verbose"
-				                                         } else {
-					                                         panic!("no-impl");
-				                                         };
-				                                         
-				                                         let ty = if msg.ty.general {
-					                                         "general"
-				                                         } else if msg.ty.validation {
-					                                         "validation"
-				                                         } else if msg.ty.performance {
-					                                         "performance"
-				                                         } else {
-					                                         panic!("no-impl");
-				                                         };
-				                                         
-				                                         println!("{} {} {}: {}",
-				                                                  msg.layer_prefix,
-				                                                  ty,
-				                                                  severity,
-				                                                  msg.description);
-			                                         });
-		}
+			// The callback has to be unwind safe, which a bare Sender isn't
+			let validation = validation.map(Mutex::new);
+			
+			Some(DebugCallback::new(&instance, severity, ty, move |msg| {
+				                        let (level, severity) = if msg.severity.error {
+					                        (Level::Error, "error")
+				                        } else if msg.severity.warning {
+					                        (Level::Warn, "warning")
+				                        } else if msg.severity.information {
+					                        (Level::Info, "information")
+				                        } else if msg.severity.verbose {
+					                        (Level::Trace, "verbose")
+				                        } else {
+					                        panic!("no-impl");
+				                        };
+				                        
+				                        let ty = if msg.ty.general {
+					                        "general"
+				                        } else if msg.ty.validation {
+					                        "validation"
+				                        } else if msg.ty.performance {
+					                        "performance"
+				                        } else {
+					                        panic!("no-impl");
+				                        };
+				                        
+				                        log!(level,
+				                             "{} {} {}: {}",
+				                             msg.layer_prefix,
+				                             ty,
+				                             severity,
+				                             msg.description);
+				                        
+				                        if let Some(sender) = &validation {
+					                        // Nobody listening anymore isn't the renderer's problem
+					                        let _ = sender.lock().unwrap().send(ValidationMessage { severity: msg.severity,
+					                                                                                ty: msg.ty,
+					                                                                                layer_prefix: msg.layer_prefix.to_string(),
+					                                                                                description: msg.description.to_string() });
+				                        }
+			                        })?)
+		} else {
+			None
+		};
 		
 		if debug {
-			println!("Devices:");
+			info!("Devices:");
 			for device in PhysicalDevice::enumerate(&instance) {
-				println!("\t{}: {} api: {} driver: {}",
-				         device.index(),
-				         device.name(),
-				         device.api_version(),
-				         device.driver_version());
+				info!("\t{}: {} api: {} driver: {}",
+				      device.index(),
+				      device.name(),
+				      device.api_version(),
+				      device.driver_version());
 			}
 		}
 		
-		let physical = system.vulkan_output_device(instance.as_ptr())
-		                     .and_then(|ptr| PhysicalDevice::enumerate(&instance).find(|physical| physical.as_ptr() == ptr))
+		let physical = target.physical_device(&instance)
 		                     .or_else(|| {
-			                     println!("Failed to fetch device from openvr, using fallback");
-			                     PhysicalDevice::enumerate(&instance).skip(device.unwrap_or(0)).next()
+			                     warn!("Failed to fetch device from target, using fallback");
+			                     device.select(&instance)
 		                     })
 		                     .ok_or(RendererCreationError::NoDevices)?;
 		
-		println!("\nUsing {}: {} api: {} driver: {}",
-		         physical.index(),
-		         physical.name(),
-		         physical.api_version(),
-		         physical.driver_version());
+		info!("Using {}: {} api: {} driver: {}",
+		      physical.index(),
+		      physical.name(),
+		      physical.api_version(),
+		      physical.driver_version());
+		
+		let limits = physical.limits();
+		if !msaa.is_power_of_two()
+		|| msaa & limits.framebuffer_color_sample_counts() == 0
+		|| msaa & limits.framebuffer_depth_sample_counts() == 0 {
+			return Err(RendererCreationError::UnsupportedSampleCount(msaa));
+		}
+		
+		let supported_features = physical.supported_features();
+		if !supported_features.superset_of(&features) {
+			return Err(RendererCreationError::UnsupportedFeatures(feature_names(&features.difference(supported_features))));
+		}
+		
+		let anisotropy = if supported_features.sampler_anisotropy {
+			                 anisotropy.max(1.0).min(limits.max_sampler_anisotropy())
+		                 } else {
+			                 1.0
+		                 };
+		
+		// The eye images have to fit the largest adaptive scale
+		let render_scale = render_scale * adaptive_resolution.map_or(1.0, |(_, max)| max);
+		
+		// The compositor samples the eye textures at whatever size they are, so only the device limits matter
+		let max_width = limits.max_image_dimension_2d().min(limits.max_framebuffer_width());
+		let max_height = limits.max_image_dimension_2d().min(limits.max_framebuffer_height());
+		let render_scale = render_scale.min(max_width as f32 / recommended_size.0 as f32)
+		                               .min(max_height as f32 / recommended_size.1 as f32);
+		let render_size = (((recommended_size.0 as f32 * render_scale).round() as u32).max(1).min(max_width),
+		                   ((recommended_size.1 as f32 * render_scale).round() as u32).max(1).min(max_height));
+		
+		if render_size != recommended_size {
+			info!("Rendering at {}x{} (recommended {}x{})", render_size.0, render_size.1, recommended_size.0, recommended_size.1);
+		}
 		
 		if debug {
 			for family in physical.queue_families() {
-				println!("Found a queue family with {:?} queue(s)", family.queues_count());
+				debug!("Found a queue family with {:?} queue(s)", family.queues_count());
 			}
 		}
 		
-		let (device, mut queues) = {
+		let memory_budget_extension = CString::new(MEMORY_BUDGET_EXTENSION).unwrap();
+		let memory_budget = instance.loaded_extensions().khr_get_physical_device_properties2
+		                 && RawDeviceExtensions::supported_by_device(physical).iter().any(|extension| *extension == memory_budget_extension);
+		
+		let (device, mut queues, extra_count) = {
 			let queue_family = physical.queue_families()
 			                           .find(|&q| q.supports_graphics())
 			                           .ok_or(RendererCreationError::NoQueue)?;
@@ -157,187 +1057,1927 @@ impl Renderer {
 			                                .find(|&q| q.explicitly_supports_transfers())
 			                                .unwrap_or(queue_family);
 			
-			let families = vec![
-				(queue_family, 0.5),
-				(load_queue_family, 0.2),
+			let mut families = vec![
+				(queue_family, queue_priorities.0),
+				(load_queue_family, queue_priorities.1),
 			];
 			
+			for (selector, priorities) in &extra_queues {
+				let family = selector.select(physical).ok_or_else(|| RendererCreationError::NoQueueFamily(selector.clone()))?;
+				families.extend(priorities.iter().map(|&priority| (family, priority)));
+			}
+			
+			// vulkano doesn't say which family or priority it rejects
+			if let Some(&(_, priority)) = families.iter().find(|(_, priority)| !(0.0 ..= 1.0).contains(priority)) {
+				return Err(RendererCreationError::InvalidQueuePriority(priority));
+			}
+			
+			let requested = |family: QueueFamily| families.iter().filter(|(requested, _)| requested.id() == family.id()).count();
+			
+			for family in physical.queue_families() {
+				if requested(family) > family.queues_count() {
+					return Err(RendererCreationError::TooManyQueues(family.id(), family.queues_count(), requested(family)));
+				}
+			}
+			
+			// Culling comes last, it only gets a queue if that leaves enough for everything else
+			let extra_count = families.len() - 2;
+			let culling_family = if async_culling { QueueFamilySelector::Compute.select(physical) } else { None };
+			let culling_family = culling_family.filter(|&family| requested(family) < family.queues_count());
+			
+			if async_culling && culling_family.is_none() {
+				warn!("No compute queue left for async culling, culling on the CPU");
+			}
+			
+			if let Some(family) = culling_family {
+				families.push((family, queue_priorities.0));
+			}
+			
+			let mut extensions = target.device_extensions(&physical)
+			                           .union(&(&DeviceExtensions { khr_swapchain: mirror,
+			                                                        ..DeviceExtensions::none() }).into());
+			
+			if memory_budget {
+				extensions.insert(memory_budget_extension);
+			}
+			
 			Device::new(physical,
-			            &Features::none(),
-			            RawDeviceExtensions::new(vulkan_device_extensions_required(&compositor, &physical))
-			                                .union(&(&DeviceExtensions { khr_swapchain: true,
-			                                                             ..DeviceExtensions::none() }).into()),
-			            families.into_iter())?
+			            &Features { fill_mode_non_solid: features.fill_mode_non_solid || supported_features.fill_mode_non_solid,
+			                        sampler_anisotropy: features.sampler_anisotropy || anisotropy > 1.0,
+			                        // KTX2 textures stay compressed where it's supported and are decompressed otherwise
+			                        texture_compression_bc: features.texture_compression_bc || supported_features.texture_compression_bc,
+			                        wide_lines: features.wide_lines || supported_features.wide_lines,
+			                        #[cfg(feature = "indirect-draw")]
+			                        multi_draw_indirect: features.multi_draw_indirect || supported_features.multi_draw_indirect,
+			                        #[cfg(feature = "indirect-draw")]
+			                        draw_indirect_first_instance: features.draw_indirect_first_instance || supported_features.draw_indirect_first_instance,
+			                        ..features },
+			            extensions,
+			            families.into_iter())
+			      .map(|(device, queues)| (device, queues, extra_count))?
 		};
 		
 		let queue = queues.next().ok_or(RendererCreationError::NoQueue)?;
 		let load_queue = queues.next().ok_or(RendererCreationError::NoQueue)?;
+		let extra_queues: Vec<Arc<Queue>> = queues.by_ref().take(extra_count).collect();
+		let async_culling = queues.next().map(AsyncCulling::new).transpose()?;
+		
+		let vs = shaders::vert::Shader::load(device.clone()).map_err(RendererCreationError::ShaderLoadError)?;
+		let fs = shaders::frag::Shader::load(device.clone()).map_err(RendererCreationError::ShaderLoadError)?;
+		let skybox_vs = shaders::skybox_vert::Shader::load(device.clone()).map_err(RendererCreationError::ShaderLoadError)?;
+		let skybox_fs = shaders::skybox_frag::Shader::load(device.clone()).map_err(RendererCreationError::ShaderLoadError)?;
+		let hidden_area_vs = shaders::hidden_area_vert::Shader::load(device.clone()).map_err(RendererCreationError::ShaderLoadError)?;
+		let hidden_area_fs = shaders::hidden_area_frag::Shader::load(device.clone()).map_err(RendererCreationError::ShaderLoadError)?;
+		let shadow_vs = shaders::shadow_vert::Shader::load(device.clone()).map_err(RendererCreationError::ShaderLoadError)?;
+		let shadow_fs = shaders::shadow_frag::Shader::load(device.clone()).map_err(RendererCreationError::ShaderLoadError)?;
+		let depth_vs = shaders::depth_vert::Shader::load(device.clone()).map_err(RendererCreationError::ShaderLoadError)?;
 		
-		let vs = shaders::vert::Shader::load(device.clone()).unwrap();
-		let fs = shaders::frag::Shader::load(device.clone()).unwrap();
-		
-		let render_pass = Arc::new(
-			vulkano::single_pass_renderpass!(device.clone(),
-				attachments: {
-					color: {
-						load: Clear,
-						store: Store,
-						format: eye::IMAGE_FORMAT,
-						samples: 1,
+		let depth_format = eye::depth_format(physical, &depth_formats).ok_or(RendererCreationError::NoDepthFormat)?;
+		let stencil = depth_format.ty() == FormatTy::DepthStencil;
+		let visible_stencil = if stencil { VISIBLE_STENCIL } else { Stencil::default() };
+		debug!("Depth format: {:?}", depth_format);
+		
+		let color_format = if hdr { tonemap::HDR_FORMAT } else { eye::IMAGE_FORMAT };
+		
+		// Depth is stored for the occlusion queries, which test against it after the pass. Velocity is kept for
+		// reprojection, see `Renderer::velocity_image`.
+		let render_pass: Arc<dyn RenderPassAbstract + Send + Sync> = if msaa > 1 {
+			Arc::new(
+				vulkano::single_pass_renderpass!(device.clone(),
+					attachments: {
+						color: {
+							load: Clear,
+							store: DontCare,
+							format: color_format,
+							samples: msaa,
+						},
+						velocity: {
+							load: Clear,
+							store: DontCare,
+							format: eye::VELOCITY_FORMAT,
+							samples: msaa,
+						},
+						depth: {
+							load: Clear,
+							store: Store,
+							format: depth_format,
+							samples: msaa,
+						},
+						resolve: {
+							load: DontCare,
+							store: Store,
+							format: color_format,
+							samples: 1,
+						},
+						velocity_resolve: {
+							load: DontCare,
+							store: Store,
+							format: eye::VELOCITY_FORMAT,
+							samples: 1,
+						}
 					},
-					depth: {
-						load: Clear,
-						store: DontCare,
-						format: eye::DEPTH_FORMAT,
-						samples: 1,
+					pass: {
+						color: [color, velocity],
+						depth_stencil: {depth},
+						resolve: [resolve, velocity_resolve]
 					}
-				},
-				pass: {
-					color: [color],
-					depth_stencil: {depth}
-				}
-			)?
+				)?
+			)
+		} else {
+			Arc::new(
+				vulkano::single_pass_renderpass!(device.clone(),
+					attachments: {
+						color: {
+							load: Clear,
+							store: Store,
+							format: color_format,
+							samples: 1,
+						},
+						velocity: {
+							load: Clear,
+							store: Store,
+							format: eye::VELOCITY_FORMAT,
+							samples: 1,
+						},
+						depth: {
+							load: Clear,
+							store: Store,
+							format: depth_format,
+							samples: 1,
+						}
+					},
+					pass: {
+						color: [color, velocity],
+						depth_stencil: {depth}
+					}
+				)?
+			)
+		};
+		
+		let pipeline_desc = PipelineDesc { render_pass: render_pass.clone(),
+		                                   cull_mode,
+		                                   front_face,
+		                                   visible_stencil };
+		
+		let ModelPipelines { pipeline, transparent_pipeline, wireframe_pipeline, points_pipeline, prepassed_pipeline } = pipeline_desc.build(&device, vs.main_entry_point(), fs.main_entry_point())?;
+		
+		#[cfg(feature = "indirect-draw")]
+		let indirect = if device.enabled_features().multi_draw_indirect && device.enabled_features().draw_indirect_first_instance {
+			               let indirect_vs = shaders::indirect_vert::Shader::load(device.clone()).map_err(RendererCreationError::ShaderLoadError)?;
+			               Some(Indirect::new(&device, &pipeline_desc, indirect_vs.main_entry_point(), fs.main_entry_point())?)
+		               } else {
+			               None
+		               };
+		
+		// Drawn first without writing depth, at the far plane so everything else ends up in front of it
+		let skybox_pipeline = Arc::new(
+			GraphicsPipeline::start()
+			                 .vertex_input_single_buffer::<model::Vertex>()
+			                 .vertex_shader(skybox_vs.main_entry_point(), ())
+			                 .viewports_dynamic_scissors_irrelevant(1)
+			                 .fragment_shader(skybox_fs.main_entry_point(), ())
+			                 .depth_stencil(DepthStencil { depth_compare: Compare::LessOrEqual,
+			                                               depth_write: false,
+			                                               stencil_front: visible_stencil,
+			                                               stencil_back: visible_stencil,
+			                                               ..DepthStencil::simple_depth_test() })
+			                 .cull_mode_disabled()
+			                 .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+			                 .build(device.clone())?
 		);
 		
-		let pipeline = Arc::new(
+		// Drawn before anything else, only marks the stencil. The mesh winding differs between HMDs and eyes.
+		// Without a stencil it's written at the near plane instead, where everything else fails the depth test.
+		let hidden_area_depth_stencil = if stencil {
+			                                DepthStencil { depth_compare: Compare::Always,
+			                                               depth_write: false,
+			                                               stencil_front: HIDDEN_STENCIL,
+			                                               stencil_back: HIDDEN_STENCIL,
+			                                               ..DepthStencil::disabled() }
+		                                } else {
+			                                DepthStencil { depth_compare: Compare::Always,
+			                                               depth_write: true,
+			                                               ..DepthStencil::disabled() }
+		                                };
+		
+		let hidden_area_pipeline = Arc::new(
+			GraphicsPipeline::start()
+			                 .vertex_input_single_buffer::<model::Vertex>()
+			                 .vertex_shader(hidden_area_vs.main_entry_point(), ())
+			                 .viewports_dynamic_scissors_irrelevant(1)
+			                 .fragment_shader(hidden_area_fs.main_entry_point(), ())
+			                 .depth_stencil(hidden_area_depth_stencil)
+			                 .blend_collective(AttachmentBlend { mask_red: false,
+			                                                     mask_green: false,
+			                                                     mask_blue: false,
+			                                                     mask_alpha: false,
+			                                                     ..AttachmentBlend::pass_through() })
+			                 .cull_mode_disabled()
+			                 .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+			                 .build(device.clone())?
+		);
+		
+		// Drawn last, over everything in the scene
+		let fade_fs = shaders::fade_frag::Shader::load(device.clone()).map_err(RendererCreationError::ShaderLoadError)?;
+		let fade_pipeline = Arc::new(
+			GraphicsPipeline::start()
+			                 .vertex_input_single_buffer::<model::Vertex>()
+			                 .vertex_shader(hidden_area_vs.main_entry_point(), ())
+			                 .viewports_dynamic_scissors_irrelevant(1)
+			                 .fragment_shader(fade_fs.main_entry_point(), ())
+			                 .blend_alpha_blending()
+			                 .cull_mode_disabled()
+			                 .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+			                 .build(device.clone())?
+		);
+		
+		let fade_triangle = CpuAccessibleBuffer::from_iter(device.clone(),
+		                                                   BufferUsage { vertex_buffer: true, ..BufferUsage::none() },
+		                                                   false,
+		                                                   [[-1.0, -1.0], [3.0, -1.0], [-1.0, 3.0]].iter().map(|&[x, y]| model::Vertex::new([x, y, 0.0], [0.0, 0.0, 1.0], [0.0, 0.0])))?;
+		
+		let prepass_pipeline = pipeline_desc.build_prepass(&device, depth_vs.main_entry_point(), shadow_fs.main_entry_point())?;
+		
+		let shadow_map = ShadowMap::new(&device, shadow_map_size)?;
+		
+		// Both sides cast shadows, so single sided geometry still blocks the light
+		let shadow_pipeline = Arc::new(
 			GraphicsPipeline::start()
 			                 .vertex_input_single_buffer::<model::Vertex>()
-			                 .vertex_shader(vs.main_entry_point(), ())
-			                 .viewports(Some(Viewport { origin: [0.0, 0.0],
-			                                            dimensions: [recommended_size.0 as f32, recommended_size.1 as f32],
-			                                            depth_range: 0.0 .. 1.0 }))
-			                 .fragment_shader(fs.main_entry_point(), ())
+			                 .vertex_shader(shadow_vs.main_entry_point(), ())
+			                 .viewports_dynamic_scissors_irrelevant(1)
+			                 .fragment_shader(shadow_fs.main_entry_point(), ())
 			                 .depth_stencil_simple_depth()
-			                 .render_pass(Subpass::from(render_pass.clone() as Arc<dyn RenderPassAbstract + Send + Sync>, 0).unwrap())
+			                 .cull_mode_disabled()
+			                 .render_pass(Subpass::from(shadow_map.render_pass.clone(), 0).unwrap())
 			                 .build(device.clone())?
 		);
 		
-		let eyes = {
-			let proj_left : Matrix4<f32> = CLIP
-			                             * Matrix4::from(system.projection_matrix(openvr::Eye::Left,  0.1, 1000.1)).transpose()
-			                             * mat4(&system.eye_to_head_transform(openvr::Eye::Left )).inverse_transform().unwrap();
-			let proj_right: Matrix4<f32> = CLIP
-			                             * Matrix4::from(system.projection_matrix(openvr::Eye::Right, 0.1, 1000.1)).transpose()
-			                             * mat4(&system.eye_to_head_transform(openvr::Eye::Right)).inverse_transform().unwrap();
-			
-			(
-				Eye::new(recommended_size, proj_left,  &queue, &render_pass)?,
-				Eye::new(recommended_size, proj_right, &queue, &render_pass)?,
-			)
+		let fxaa_pass = if fxaa { Some(Fxaa::new(&device)?) } else { None };
+		let tonemap = if hdr { Some(Tonemap::new(&device)?) } else { None };
+		let (lines, lines_upload) = Lines::new(&queue, &render_pass, visible_stencil)?;
+		let (text, text_upload) = Text::new(&queue, &render_pass, visible_stencil)?;
+		
+		let (eyes, eyes_upload) = {
+			let eye_buffers = eye_buffers.max(frames_in_flight);
+			let proj_left : Matrix4<f32> = clip * target.projection(openvr::Eye::Left,  near, far);
+			let proj_right: Matrix4<f32> = clip * target.projection(openvr::Eye::Right, near, far);
+			
+			let (left, left_upload) = Eye::new(render_size, proj_left, target.eye_to_head(openvr::Eye::Left), &target.hidden_area_mesh(openvr::Eye::Left),
+			                                   msaa, eye_buffers, depth_format, &queue, &render_pass, fxaa_pass.as_ref().map(|fxaa| &fxaa.render_pass),
+			                                   tonemap.as_ref().map(|tonemap| &tonemap.render_pass))?;
+			let (right, right_upload) = Eye::new(render_size, proj_right, target.eye_to_head(openvr::Eye::Right), &target.hidden_area_mesh(openvr::Eye::Right),
+			                                     msaa, eye_buffers, depth_format, &queue, &render_pass, fxaa_pass.as_ref().map(|fxaa| &fxaa.render_pass),
+			                                     tonemap.as_ref().map(|tonemap| &tonemap.render_pass))?;
+			
+			((left, right), left_upload.join(right_upload))
+		};
+		
+		let (occlusion, occlusion_upload): (_, Box<dyn GpuFuture>) = match Occlusion::new(&queue, (&eyes.0.depth_image, &eyes.1.depth_image), msaa, visible_stencil)? {
+			Some((occlusion, upload)) => (Some(occlusion), upload),
+			None => {
+				warn!("Query results can't be read back, occlusion culling is disabled");
+				(None, Box::new(sync::now(device.clone())))
+			},
 		};
 		
-		let previous_frame_end = Some(Box::new(sync::now(device.clone())) as Box<_>);
+		// Some drivers report 0 when they don't know
+		let refresh_rate = target.refresh_rate()
+		                         .filter(|rate| rate.is_finite() && *rate > 0.0)
+		                         .unwrap_or(DEFAULT_REFRESH_RATE);
+		let compositor = target.into_compositor();
+		
+		if let Some(compositor) = &compositor {
+			compositor.set_tracking_space(tracking_origin);
+		}
+		
+		// Without a compositor the mirror window is the only output, so it is paced by vsync instead of the HMD
+		let mirror = if mirror {
+			             Some(Mirror::new(&instance, &queue, compositor.is_none())?)
+		             } else {
+			             None
+		             };
+		
+		let adaptive_resolution = adaptive_resolution.map(|(min, max)| AdaptiveResolution::new(min, max, Duration::from_secs_f32(1.0 / refresh_rate)));
+		
+		let gpu_timer = if adaptive_resolution.is_some() || gpu_times {
+			                let gpu_timer = GpuTimer::new(&queue)?;
+			                if gpu_timer.is_none() { warn!("Graphics queue doesn't support timestamps, adaptive resolution and GPU times are disabled") }
+			                gpu_timer
+		                } else {
+			                None
+		                };
+		
+		let light_buffer = CpuBufferPool::uniform_buffer(device.clone());
+		let view_buffer = CpuBufferPool::uniform_buffer(device.clone());
+		let joint_buffer = CpuBufferPool::uniform_buffer(device.clone());
+		let transforms = Transforms::new(&device);
+		let material_sets = MaterialSets::new(&device, pipeline.descriptor_set_layout(0).ok_or(RendererCreationError::NoLayout)?.clone());
+		
+		// Shared by all model textures, their image views limit the LOD to the levels they actually have
+		let sampler = Sampler::new(device.clone(),
+		                           Filter::Linear,
+		                           Filter::Linear,
+		                           MipmapMode::Linear,
+		                           SamplerAddressMode::Repeat,
+		                           SamplerAddressMode::Repeat,
+		                           SamplerAddressMode::Repeat,
+		                           0.0,
+		                           anisotropy,
+		                           0.0,
+		                           MAX_LOD)?;
+		
+		// The first frame waits for the hidden area meshes and the grid
+		let previous_frame_end = Some(Box::new(eyes_upload.join(lines_upload).join(text_upload).join(occlusion_upload)) as Box<_>);
 		
 		Ok(Renderer {
 			instance,
+			debug_callback,
 			device,
 			queue,
 			load_queue,
+			extra_queues,
 			pipeline,
+			transparent_pipeline,
+			wireframe_pipeline,
+			points_pipeline,
+			prepassed_pipeline,
+			wireframe: false,
+			#[cfg(feature = "indirect-draw")]
+			indirect,
+			#[cfg(feature = "indirect-draw")]
+			indirect_draw: false,
+			skybox_pipeline,
+			skybox: None,
+			hidden_area_pipeline,
+			fade_pipeline,
+			fade_triangle,
+			shadow_pipeline,
+			shadow_map,
+			prepass_pipeline,
+			depth_prepass: false,
+			fxaa_pass,
+			fxaa,
+			tonemap,
+			exposure: 0.0,
+			lines,
+			line_width: 1.0,
+			show_grid: false,
+			show_axes: false,
+			debug_bounds: false,
+			chaperone: None,
+			show_boundary: false,
+			boundary_color: DEFAULT_BOUNDARY_COLOR,
+			text,
+			#[cfg(feature = "hot-reload")]
+			shader_watcher: ShaderWatcher::new(pipeline_desc.clone()),
+			pipeline_desc,
+			reflection: None,
 			eyes,
+			msaa,
+			clear_color: (clear_color, clear_color),
+			light_direction: DEFAULT_LIGHT_DIRECTION.normalize(),
+			ambient: DEFAULT_AMBIENT,
+			view: Matrix4::identity(),
+			ipd_scale,
+			frustum_culling: true,
+			occlusion,
+			occlusion_culling: false,
+			async_culling,
+			occluded: 0,
+			previous_views: None,
+			previous_matrices: Vec::new(),
+			static_scene: false,
+			prerecorded: None,
+			light_buffer,
+			view_buffer,
+			joint_buffer,
+			transforms,
+			material_sets,
+			sampler,
+			memory_budget,
+			system: None,
 			compositor,
+			haptics: None,
+			frame_timer: FrameTimer::new(),
+			update: None,
+			last_update: None,
+			gpu_timer,
+			gpu_times: (Duration::default(), Duration::default()),
+			adaptive_resolution,
+			scene: Scene::new(),
+			head_pose: IDENTITY_POSE,
+			predicted_poses: None,
+			frame_pose: IDENTITY_POSE,
+			tracking_loss,
+			tracking_lost: None,
+			eye_rotation: (Vector2::new(0.0, 0.0), Vector2::new(0.0, 0.0)),
+			mirror,
 			previous_frame_end,
+			in_flight: VecDeque::with_capacity(frames_in_flight),
+			frames_in_flight,
 		})
 	}
 	
-	pub fn render(&mut self, hmd_pose: &[[f32; 4]; 3], eye_rotation: (Vector2<f32>, Vector2<f32>), scene: &mut [(Model, Matrix4<f32>)]) -> Result<(), RenderError> {
-		self.previous_frame_end.as_mut().unwrap().cleanup_finished();
-		
-		let left_pv = self.eyes.0.projection
-		            * Matrix4::from(Euler { x: Rad(eye_rotation.0.x),
-		                                    y: Rad(eye_rotation.0.y),
-		                                    z: Rad(0.0) })
-		            * mat4(hmd_pose).inverse_transform().unwrap();
-		
-		let right_pv = self.eyes.1.projection
-		             * Matrix4::from(Euler { x: Rad(eye_rotation.1.x),
-		                                     y: Rad(eye_rotation.1.y),
-		                                     z: Rad(0.0) })
-		             * mat4(hmd_pose).inverse_transform().unwrap();
-		
-		let mut command_buffer = AutoCommandBufferBuilder::new(self.device.clone(), self.queue.family())?
-		                                                  .begin_render_pass(self.eyes.0.frame_buffer.clone(),
-		                                                                     false,
-		                                                                     vec![ [0.5, 0.5, 0.5, 1.0].into(),
-		                                                                           ClearValue::Depth(1.0) ])?;
-		
-		for (model, matrix) in scene.iter_mut() {
-			if !model.loaded() { continue };
-			command_buffer = command_buffer.draw_indexed(self.pipeline.clone(),
-			                                             &DynamicState::none(),
-			                                             model.vertices.clone(),
-			                                             model.indices.clone(),
-			                                             model.set.clone(),
-			                                             left_pv * *matrix)?;
-		}
-		
-		command_buffer = command_buffer.end_render_pass()?
-		                               .begin_render_pass(self.eyes.1.frame_buffer.clone(),
-		                                                  false,
-		                                                  vec![ [0.5, 0.5, 0.5, 1.0].into(),
-		                                                        ClearValue::Depth(1.0) ])?;
-		
-		for (model, matrix) in scene.iter_mut() {
-			if !model.loaded() { continue };
-			command_buffer = command_buffer.draw_indexed(self.pipeline.clone(),
-			                                             &DynamicState::none(),
-			                                             model.vertices.clone(),
-			                                             model.indices.clone(),
-			                                             model.set.clone(),
-			                                             right_pv * *matrix)?;
-		}
-		
-		let command_buffer = command_buffer.end_render_pass()?
-		                                   .build()?;
-		
-		let future = self.previous_frame_end.take()
-		                                    .unwrap()
-		                                    .then_execute(self.queue.clone(), command_buffer)?;
-		
-		unsafe {
-			self.compositor.submit(openvr::Eye::Left,  &self.eyes.0.texture, None, Some(hmd_pose.clone()))?;
-			self.compositor.submit(openvr::Eye::Right, &self.eyes.1.texture, None, Some(hmd_pose.clone()))?;
-		}
-		
-		let future = future.then_signal_fence_and_flush();
-		
-		match future {
-			Ok(future) => {
-				self.previous_frame_end = Some(Box::new(future) as Box<_>);
-			},
-			Err(FlushError::OutOfDate) => {
-				eprintln!("Flush Error: Out of date, ignoring");
-				self.previous_frame_end = Some(Box::new(sync::now(self.device.clone())) as Box<_>);
-			},
-			Err(err) => return Err(err.into()),
-		}
+	pub fn set_clear_color(&mut self, color: [f32; 4]) {
+		self.clear_color = (color, color);
+	}
+	
+	// Different colors per eye make it easy to tell which image ended up where
+	pub fn set_eye_clear_colors(&mut self, left: [f32; 4], right: [f32; 4]) {
+		self.clear_color = (left, right);
+	}
+	
+	// Direction the light travels in, in world space
+	pub fn set_light_direction(&mut self, direction: Vector3<f32>) {
+		self.light_direction = direction.normalize();
+	}
+	
+	pub fn set_ambient(&mut self, ambient: f32) {
+		self.ambient = ambient;
+	}
+	
+	// Faces in +X, -X, +Y, -Y, +Z, -Z order, drawn behind the scene instead of the clear color
+	pub fn set_skybox<P: AsRef<Path>>(&mut self, paths: &[P; 6]) -> Result<(), SkyboxError> {
+		let skybox = Skybox::load(paths, self)?;
+		self.use_skybox(skybox);
+		Ok(())
+	}
+	
+	// Same as `set_skybox`, but from a single equirectangular panorama
+	pub fn set_skybox_equirect<P: AsRef<Path>>(&mut self, path: P) -> Result<(), SkyboxError> {
+		let skybox = Skybox::load_equirect(path, self)?;
+		self.use_skybox(skybox);
+		Ok(())
+	}
+	
+	// Same as `set_skybox_equirect`, but from a Radiance HDR (.hdr) panorama that keeps its full range. Meant for
+	// renderers built with `RendererBuilder::hdr`, without it the sky clips at white.
+	pub fn set_skybox_equirect_hdr<P: AsRef<Path>>(&mut self, path: P) -> Result<(), SkyboxError> {
+		let skybox = Skybox::load_equirect_hdr(path, self)?;
+		self.use_skybox(skybox);
+		Ok(())
+	}
+	
+	pub fn clear_skybox(&mut self) {
+		self.skybox = None;
+	}
+	
+	// The next frame waits for the upload instead of polling it like models do
+	fn use_skybox(&mut self, (skybox, future): (Skybox, Box<dyn GpuFuture>)) {
+		let previous_frame_end = self.previous_frame_end.take().unwrap();
+		self.previous_frame_end = Some(Box::new(previous_frame_end.join(future)) as Box<_>);
+		self.skybox = Some(skybox);
+	}
+	
+	// Uses a throwaway instance, so it can be called before any renderer exists
+	pub fn available_devices() -> Result<Vec<DeviceInfo>, InstanceCreationError> {
+		let app_infos = app_info_from_cargo_toml!();
+		let instance = Instance::new(Some(&app_infos), &InstanceExtensions::none(), None)?;
+		
+		Ok(PhysicalDevice::enumerate(&instance).map(DeviceInfo::from).collect())
+	}
+	
+	pub fn device(&self) -> Arc<Device> {
+		self.device.clone()
+	}
+	
+	pub fn graphics_queue(&self) -> Arc<Queue> {
+		self.queue.clone()
+	}
+	
+	// Dedicated transfer queue if the device has one, used to upload models
+	pub fn load_queue(&self) -> Arc<Queue> {
+		self.load_queue.clone()
+	}
+	
+	// Queues requested with `RendererBuilder::extra_queues`, in the order they were requested. The renderer never
+	// submits to them.
+	pub fn extra_queues(&self) -> &[Arc<Queue>] {
+		&self.extra_queues
+	}
+	
+	// Needs the fill_mode_non_solid device feature
+	pub fn set_wireframe(&mut self, wireframe: bool) -> Result<(), WireframeUnsupportedError> {
+		if wireframe && self.wireframe_pipeline.is_none() {
+			return Err(WireframeUnsupportedError);
+		}
+		
+		self.wireframe = wireframe;
+		Ok(())
+	}
+	
+	// Draws opaque models that share their mesh, material and joints with one indirect draw per group instead of one
+	// draw each. Needs the multi_draw_indirect and draw_indirect_first_instance device features, ignored while drawing
+	// wireframes.
+	#[cfg(feature = "indirect-draw")]
+	pub fn set_indirect_draw(&mut self, indirect_draw: bool) -> Result<(), IndirectDrawUnsupportedError> {
+		if indirect_draw && self.indirect.is_none() {
+			return Err(IndirectDrawUnsupportedError);
+		}
+		
+		self.indirect_draw = indirect_draw;
+		Ok(())
+	}
+	
+	// Reflects the scene in a flat mirror, a 1 by 1 quad in the XY plane facing +Z placed by `transform`, `None` removes
+	// it. Only the reflection is drawn, the sky isn't part of it and wireframes skip it. Needs a depth format with a
+	// stencil, see `RendererBuilder::depth_formats`.
+	pub fn set_planar_reflection(&mut self, transform: Option<Matrix4<f32>>) -> Result<(), ReflectionError> {
+		let transform = match transform {
+			Some(transform) => transform,
+			None => {
+				self.reflection = None;
+				return Ok(());
+			},
+		};
+		
+		if vulkano::image::ImageAccess::format(&*self.eyes.0.depth_image).ty() != FormatTy::DepthStencil {
+			return Err(ReflectionError::NoStencil);
+		}
+		
+		// The pipelines are only built for the first mirror
+		if self.reflection.is_none() {
+			self.reflection = Some(Reflection::new(&self.device, &self.pipeline_desc)?);
+		}
+		
+		self.reflection.as_mut().unwrap().set_transform(transform);
+		Ok(())
+	}
+	
+	// See `RendererBuilder::ipd_scale`
+	pub fn set_ipd_scale(&mut self, ipd_scale: f32) {
+		self.ipd_scale = ipd_scale;
+	}
+	
+	// Draws the depth of all opaque models before shading them, so every pixel is shaded only once. Pays off when
+	// fragments are expensive and overlap a lot, otherwise the extra vertex work costs more than it saves. Compare
+	// `last_gpu_times` with and without it to tell, the demo's `--gpu-times` and `--depth-prepass` flags do that.
+	// Ignored while drawing wireframes.
+	pub fn set_depth_prepass(&mut self, depth_prepass: bool) {
+		self.depth_prepass = depth_prepass;
+	}
+	
+	// Only available if the renderer was built with `RendererBuilder::fxaa`
+	pub fn set_fxaa(&mut self, fxaa: bool) -> Result<(), FxaaUnavailableError> {
+		if fxaa && self.fxaa_pass.is_none() {
+			return Err(FxaaUnavailableError);
+		}
+		
+		self.fxaa = fxaa;
+		Ok(())
+	}
+	
+	// Brightens (positive) or darkens (negative) the scene by this many stops before tone mapping. Only has an effect
+	// if the renderer was built with `RendererBuilder::hdr`.
+	pub fn set_exposure(&mut self, exposure: f32) {
+		self.exposure = exposure;
+	}
+	
+	pub fn exposure(&self) -> f32 {
+		self.exposure
+	}
+	
+	// Floor grid at y = 0 with a line every meter, 20 meters across
+	pub fn show_grid(&mut self, show_grid: bool) {
+		self.show_grid = show_grid;
+	}
+	
+	// Width of the grid, axes and `draw_line` lines in pixels, clamped to the range the device supports. Needs the
+	// wide_lines device feature, lines stay a pixel wide without it.
+	pub fn set_line_width(&mut self, width: f32) {
+		if !self.device.enabled_features().wide_lines {
+			if width != 1.0 { warn!("Wide lines aren't supported, lines stay 1 pixel wide") }
+			self.line_width = 1.0;
+			return;
+		}
+		
+		let [min, max] = self.device.physical_device().limits().line_width_range();
+		self.line_width = width.max(min).min(max);
+	}
+	
+	pub fn line_width(&self) -> f32 {
+		self.line_width
+	}
+	
+	// One meter long X, Y and Z lines in red, green and blue at the origin
+	pub fn show_axes(&mut self, show_axes: bool) {
+		self.show_axes = show_axes;
+	}
+	
+	// Outlines the bounding box of every visible model, transformed along with it
+	pub fn debug_draw_bounds(&mut self, debug_bounds: bool) {
+		self.debug_bounds = debug_bounds;
+	}
+	
+	// Needed for `show_boundary`, the renderer only gets the system and compositor
+	pub fn set_chaperone(&mut self, chaperone: Chaperone) {
+		self.chaperone = Some(chaperone);
+	}
+	
+	// Needed for `recenter` and `trigger_haptic`, the system is only borrowed while building the renderer
+	pub fn set_system(&mut self, system: System) {
+		self.system = Some(system);
+	}
+	
+	// Needed for `trigger_haptic`, from `Input::haptics`
+	pub fn set_haptics(&mut self, haptics: Haptics) {
+		self.haptics = Some(haptics);
+	}
+	
+	// Outlines the play area on the floor, with walls that fade in as the head gets close. Read from the chaperone every
+	// frame, so changes from room setup show up right away. Nothing is drawn without a chaperone or a calibrated play area.
+	pub fn show_boundary(&mut self, show_boundary: bool) {
+		self.show_boundary = show_boundary;
+	}
+	
+	// Alpha is multiplied with the fade of the walls
+	pub fn set_boundary_color(&mut self, color: [f32; 4]) {
+		self.boundary_color = color;
+	}
+	
+	// World space line drawn in the next frame only, so lines that should stay have to be drawn again every frame.
+	// All lines of a frame share one vertex buffer.
+	pub fn draw_line(&mut self, a: Point3<f32>, b: Point3<f32>, color: [f32; 4]) {
+		self.lines.pending.push(LineVertex::new(a.into(), color));
+		self.lines.pending.push(LineVertex::new(b.into(), color));
+	}
+	
+	// White ASCII text drawn in the next frame only, like `draw_line`. Glyphs are a unit square each, laid out along
+	// +X from the origin and facing +Z, `transform` places and scales them, e.g. to a few centimeters in front of the head.
+	pub fn draw_text(&mut self, text: &str, transform: Matrix4<f32>) {
+		self.text.push(text, &transform);
+	}
+	
+	// Skips models whose bounding sphere is entirely outside of an eye's view
+	pub fn set_frustum_culling(&mut self, frustum_culling: bool) {
+		self.frustum_culling = frustum_culling;
+	}
+	
+	// Skips models whose bounding box was completely hidden behind others in both eyes a few frames ago, found with
+	// occlusion queries. Pays off in dense scenes with expensive models, costs a box per model and eye otherwise.
+	// Has no effect on devices the query results can't be read back from.
+	pub fn set_occlusion_culling(&mut self, occlusion_culling: bool) {
+		self.occlusion_culling = occlusion_culling;
+		
+		// Results from before a pause would hide models for a few frames
+		if let Some(occlusion) = &mut self.occlusion {
+			occlusion.clear();
+		}
+	}
+	
+	// Models the last frame skipped because of occlusion culling
+	pub fn occluded_models(&self) -> usize {
+		self.occluded
+	}
+	
+	// How many of the models' descriptor sets are taken, sets of removed models are reused by the next ones
+	pub fn material_set_usage(&self) -> MaterialSetUsage {
+		self.material_sets.usage()
+	}
+	
+	// Records the models' draws once and executes them again every frame, only the view and light get updated. Culling
+	// is skipped and transparent models keep the order they were recorded in, so it pays off for scenes with many models
+	// that hardly change. Adding, removing, moving or hiding models through the renderer records them again, anything
+	// else that changes them, like animations, colors or the models passed to `render_with_pose`, needs
+	// `mark_scene_dirty`.
+	pub fn set_static_scene(&mut self, static_scene: bool) {
+		self.static_scene = static_scene;
+		
+		if !static_scene {
+			self.prerecorded = None;
+		}
+	}
+	
+	// Records a static scene again in the next frame
+	pub fn mark_scene_dirty(&mut self) {
+		if let Some(prerecorded) = &mut self.prerecorded {
+			prerecorded.invalidate();
+		}
+	}
+	
+	// Size of each eye image, the recommended size adjusted by the render scale
+	pub fn render_target_size(&self) -> (u32, u32) {
+		let dimensions = self.eyes.0.target().image.dimensions();
+		(dimensions[0], dimensions[1])
+	}
+	
+	// Motion of every pixel of the eye since the previous frame, for reprojection. Two half floats, in 0..1 coordinates
+	// of the region rendered, pointing from where the surface was to where it is now. Overwritten by every frame, so it
+	// has to be used before the next one is rendered. Static scenes only move with the head.
+	pub fn velocity_image(&self, eye: openvr::Eye) -> &Arc<AttachmentImage<Format>> {
+		match eye {
+			openvr::Eye::Left => &self.eyes.0.velocity.image,
+			openvr::Eye::Right => &self.eyes.1.velocity.image,
+		}
+	}
+	
+	// Part of the render scale currently used by adaptive resolution, 1 without it
+	pub fn resolution_scale(&self) -> f32 {
+		self.adaptive_resolution.as_ref().map_or(1.0, AdaptiveResolution::scale)
+	}
+	
+	// Averaged over the last few frames
+	pub fn fps(&self) -> f32 {
+		self.frame_timer.fps()
+	}
+	
+	// GPU time of each eye in the last frame that has been measured, which lags a few frames behind. Zero unless
+	// `gpu_times` or adaptive resolution is enabled. Without stereo everything is drawn in the left eye's pass.
+	pub fn last_gpu_times(&self) -> (Duration, Duration) {
+		self.gpu_times
+	}
+	
+	// Wall clock time between the last two submits
+	pub fn last_frame_time(&self) -> Duration {
+		self.frame_timer.last_frame_time()
+	}
+	
+	// False once the mirror window has been closed, or if there never was one
+	pub fn mirror_open(&self) -> bool {
+		self.mirror.is_some()
+	}
+	
+	// Makes the current head position and heading the origin of the seated tracking space
+	pub fn recenter(&self) {
+		if let Some(system) = &self.system {
+			system.reset_seated_zero_pose();
+		}
+	}
+	
+	// Vibrates through the haptic output action, frequency is in Hz and amplitude from 0 to 1.
+	// Does nothing for devices that aren't held in a hand, or without `set_system` and `set_haptics`.
+	pub fn trigger_haptic(&self, device_index: TrackedDeviceIndex, duration_seconds: f32, frequency: f32, amplitude: f32) -> Result<(), InputError> {
+		if let (Some(system), Some(haptics)) = (&self.system, &self.haptics) {
+			if let Some(hand) = system.get_controller_role_for_tracked_device_index(device_index) {
+				haptics.vibrate(hand, duration_seconds, frequency, amplitude)?;
+			}
+		}
 		
 		Ok(())
 	}
+	
+	pub fn add_model(&mut self, model: Model) -> ModelHandle {
+		self.mark_scene_dirty();
+		self.scene.add(model)
+	}
+	
+	pub fn remove_model(&mut self, handle: ModelHandle) -> Result<Model, InvalidHandleError> {
+		self.mark_scene_dirty();
+		self.scene.remove(handle)
+	}
+	
+	// Relative to the parent set with `set_parent`, or the world for models without one
+	pub fn set_transform(&mut self, handle: ModelHandle, matrix: Matrix4<f32>) -> Result<(), InvalidHandleError> {
+		self.mark_scene_dirty();
+		self.scene.set_transform(handle, matrix)
+	}
+	
+	// `None` makes the model a root again
+	pub fn set_parent(&mut self, handle: ModelHandle, parent: Option<ModelHandle>) -> Result<(), ParentError> {
+		self.mark_scene_dirty();
+		self.scene.set_parent(handle, parent)
+	}
+	
+	pub fn world_transform(&mut self, handle: ModelHandle) -> Result<Matrix4<f32>, InvalidHandleError> {
+		self.scene.world_transform(handle)
+	}
+	
+	pub fn set_visible(&mut self, handle: ModelHandle, visible: bool) -> Result<(), InvalidHandleError> {
+		self.mark_scene_dirty();
+		self.scene.set_visible(handle, visible)
+	}
+	
+	// Picks among the models added with `add_model`, e.g. along a controller's pointing direction. Hits the bounding box
+	// of the model, skinned models are tested in their bind pose.
+	pub fn raycast(&mut self, origin: Point3<f32>, direction: Vector3<f32>) -> Option<(ModelHandle, f32)> {
+		self.scene.raycast(origin, direction)
+	}
+	
+	// Called at the start of every frame with the time since the previous call, zero the first time. Anything set
+	// through the renderer, like model transforms or the light direction, takes effect in the frame about to be drawn.
+	// Models passed to `render_with_pose` and `render_tracked` are already fixed by then, only `render` sees changes to them.
+	pub fn set_update<F>(&mut self, update: F)
+	                    where F: FnMut(&mut Renderer, Duration) + 'static {
+		self.update = Some(Box::new(update));
+	}
+	
+	pub fn clear_update(&mut self) {
+		self.update = None;
+	}
+	
+	// All devices as predicted for the display time of the frame being drawn, or the last one in between frames. Set by
+	// `render` and `render_tracked`, so the update callback and app logic after the frame see what's on screen.
+	// `None` until then, and without a compositor.
+	pub fn predicted_poses(&self) -> Option<&TrackedDevicePoses> {
+		self.predicted_poses.as_ref()
+	}
+	
+	// HMD pose the views of the frame being drawn, or the last one in between frames, are built from
+	pub fn frame_pose(&self) -> Matrix4<f32> {
+		mat4(&self.frame_pose)
+	}
+	
+	// Projection times view of each eye in the last frame drawn, left then right, for culling or placing UI exactly like
+	// the renderer did. `None` before the first frame. Flat targets only show the left eye.
+	pub fn eye_pv(&self) -> Option<(Matrix4<f32>, Matrix4<f32>)> {
+		self.previous_views.map(|(left, right)| (left.0 * left.1, right.0 * right.1))
+	}
+	
+	// Views of the last frame drawn, from the world to each eye, including the eye's offset from the head and its
+	// rotation from `set_eye_rotation`
+	pub fn eye_views(&self) -> Option<(Matrix4<f32>, Matrix4<f32>)> {
+		self.previous_views.map(|(left, right)| (left.1, right.1))
+	}
+	
+	// Projections of the eyes as the compositor gives them, taking eye space to clip space. They don't change from
+	// frame to frame.
+	pub fn eye_projections(&self) -> (Matrix4<f32>, Matrix4<f32>) {
+		(self.eyes.0.projection, self.eyes.1.projection)
+	}
+	
+	// Only used by `render` when there is no compositor to get the pose from
+	pub fn set_head_pose(&mut self, pose: [[f32; 4]; 3]) {
+		self.head_pose = pose;
+	}
+	
+	// Turns each eye, left then right, around its own position by x radians of pitch (up) and y radians of yaw (left),
+	// applied in the eye's view space after the HMD pose. Meant for small gaze or convergence adjustments, zero gives
+	// the views the compositor expects and anything else makes the eyes disagree about depth. Used by `render`, the
+	// other variants take it as an argument.
+	pub fn set_eye_rotation(&mut self, eye_rotation: (Vector2<f32>, Vector2<f32>)) {
+		self.eye_rotation = eye_rotation;
+	}
+	
+	// Waits for the poses itself, so the views always match the pose the frame gets submitted with.
+	// Models in `devices` are drawn at the pose of the tracked device with that index while it's connected and tracked.
+	// Returns the poses that were used, e.g. to find out which devices still need a model.
+	pub fn render_tracked(&mut self, eye_rotation: (Vector2<f32>, Vector2<f32>), scene: &[(Model, Matrix4<f32>)], devices: &HashMap<TrackedDeviceIndex, Model>) -> Result<TrackedDevicePoses, RenderError> {
+		let poses = self.compositor.as_ref().ok_or(RenderError::NoCompositor)?.wait_get_poses()?.render;
+		self.predicted_poses = Some(poses);
+		let mut frame = scene.to_vec();
+		
+		for (&index, model) in devices {
+			match poses.get(index as usize) {
+				Some(pose) if pose.device_is_connected() && pose.pose_is_valid() => {
+					frame.push((model.clone(), mat4(pose.device_to_absolute_tracking())));
+				},
+				_ => {},
+			}
+		}
+		
+		let hmd_pose = self.tracked_hmd_pose(&poses[tracked_device_index::HMD as usize]);
+		self.render_with_pose(&hmd_pose, eye_rotation, &mut frame)?;
+		
+		Ok(poses)
+	}
+	
+	// Draws the models added with `add_model`. The HMD pose comes from the compositor or, without one, `set_head_pose`.
+	// The compositor's render poses are predicted to when the frame shows up on the display, not sampled now.
+	pub fn render(&mut self) -> Result<(), RenderError> {
+		let hmd_pose = match &self.compositor {
+			Some(compositor) => {
+				let poses = compositor.wait_get_poses()?.render;
+				self.predicted_poses = Some(poses);
+				self.tracked_hmd_pose(&poses[tracked_device_index::HMD as usize])
+			},
+			None => self.head_pose,
+		};
+		
+		self.frame_pose = hmd_pose;
+		self.run_update();
+		let mut frame = self.scene.models();
+		
+		self.render_frame(&hmd_pose, self.eye_rotation, &mut frame)
+	}
+	
+	// For callers that wait for the poses themselves
+	pub fn render_with_pose(&mut self, hmd_pose: &[[f32; 4]; 3], eye_rotation: (Vector2<f32>, Vector2<f32>), scene: &mut [(Model, Matrix4<f32>)]) -> Result<(), RenderError> {
+		self.frame_pose = *hmd_pose;
+		self.run_update();
+		self.render_frame(hmd_pose, eye_rotation, scene)
+	}
+	
+	// Same as `render_with_pose`, but the frame isn't submitted. The returned future executes it, the caller can chain
+	// work of its own after it, like a post effect on the eye images, and has to hand it back with `submit_deferred`
+	// before anything else is rendered. Flushing it first is fine, the renderer only signals a fence after it.
+	pub fn render_deferred(&mut self, hmd_pose: &[[f32; 4]; 3], eye_rotation: (Vector2<f32>, Vector2<f32>), scene: &mut [(Model, Matrix4<f32>)])
+	                      -> Result<(Box<dyn GpuFuture>, PendingFrame), RenderError> {
+		self.frame_pose = *hmd_pose;
+		self.run_update();
+		self.record_frame(hmd_pose, eye_rotation, scene)
+	}
+	
+	// Submits the eyes to the compositor and the mirror window, and keeps `future` as the end of the frame
+	pub fn submit_deferred(&mut self, pending: PendingFrame, future: Box<dyn GpuFuture>) -> Result<(), RenderError> {
+		self.submit_frame(pending, future)
+	}
+	
+	// The callback is taken out while it runs, so it can use the renderer. Setting a new one from inside replaces it.
+	fn run_update(&mut self) {
+		let now = Instant::now();
+		let elapsed = self.last_update.replace(now).map_or(Duration::from_secs(0), |last| now - last);
+		
+		if let Some(mut update) = self.update.take() {
+			update(self, elapsed);
+			
+			if self.update.is_none() {
+				self.update = Some(update);
+			}
+		}
+	}
+	
+	fn render_frame(&mut self, hmd_pose: &[[f32; 4]; 3], eye_rotation: (Vector2<f32>, Vector2<f32>), scene: &mut [(Model, Matrix4<f32>)]) -> Result<(), RenderError> {
+		let (future, pending) = self.record_frame(hmd_pose, eye_rotation, scene)?;
+		self.submit_frame(pending, future)
+	}
+	
+	// Everything up to the submission, which `submit_frame` does with what's returned
+	fn record_frame(&mut self, hmd_pose: &[[f32; 4]; 3], eye_rotation: (Vector2<f32>, Vector2<f32>), scene: &mut [(Model, Matrix4<f32>)])
+	               -> Result<(Box<dyn GpuFuture>, PendingFrame), RenderError> {
+		self.previous_frame_end.as_mut().unwrap().cleanup_finished();
+		
+		// Frames still chain on each other, the fences only keep the CPU from running too far ahead
+		while self.in_flight.len() >= self.frames_in_flight {
+			match self.in_flight.pop_front().unwrap().wait(None) {
+				Ok(()) => {},
+				Err(FlushError::DeviceLost) => return Err(self.device_lost()),
+				Err(err) => return Err(err.into()),
+			}
+		}
+		
+		// Timestamps of the frames waited for above are usually in by now
+		if let Some(gpu_timer) = &mut self.gpu_timer {
+			// Split into the shadow map, both eyes and the rest of the frame by `mark_gpu_time`
+			for parts in gpu_timer.poll() {
+				self.gpu_times = (parts[1], parts[2]);
+				
+				if let Some(adaptive_resolution) = &mut self.adaptive_resolution {
+					adaptive_resolution.update(parts.iter().sum());
+				}
+			}
+		}
+		
+		if let Some(occlusion) = &mut self.occlusion {
+			occlusion.poll();
+		}
+		
+		self.eyes.0.advance();
+		self.eyes.1.advance();
+		
+		#[cfg(feature = "hot-reload")]
+		self.reload_shaders();
+		
+		// Degenerate poses show up during tracking loss, keep the last usable view until tracking recovers
+		if let Some(view) = mat4(hmd_pose).inverse_transform() {
+			self.view = view;
+		}
+		
+		let left_view = eye_view(&self.view, &self.head_to_eye(&self.eyes.0), eye_rotation.0);
+		let right_view = eye_view(&self.view, &self.head_to_eye(&self.eyes.1), eye_rotation.1);
+		
+		let left_projection = self.eyes.0.projection;
+		let right_projection = self.eyes.1.projection;
+		
+		let left_pv = left_projection * left_view;
+		let right_pv = right_projection * right_view;
+		
+		// The first frame has nothing to move from
+		let (left_previous, right_previous) = self.previous_views.unwrap_or(((left_projection, left_view), (right_projection, right_view)));
+		let left_previous_pv = left_previous.0 * left_previous.1;
+		let right_previous_pv = right_previous.0 * right_previous.1;
+		
+		// Wireframes don't blend, transparent models just keep their draw order
+		let prepass = self.depth_prepass && !self.wireframe;
+		let (pipeline, transparent_pipeline) = match &self.wireframe_pipeline {
+			Some(wireframe_pipeline) if self.wireframe => (wireframe_pipeline.clone(), wireframe_pipeline.clone()),
+			_ if prepass => (self.prepassed_pipeline.clone(), self.transparent_pipeline.clone()),
+			_ => (self.pipeline.clone(), self.transparent_pipeline.clone()),
+		};
+		
+		let left_frustum = Frustum::new(&left_pv);
+		let right_frustum = Frustum::new(&right_pv);
+		
+		// Flat targets only show the left eye
+		let stereo = self.compositor.is_some();
+		
+		// Everything in view gets queried, including what's skipped this frame, so it shows up again once uncovered
+		let queries: Vec<occlusion::Query> = match &self.occlusion {
+			Some(_) if self.occlusion_culling => scene.iter()
+			                                          .enumerate()
+			                                          .filter(|(_, (model, matrix))| model.visible() && !(self.culled(&left_frustum, model, matrix) && (!stereo || self.culled(&right_frustum, model, matrix))))
+			                                          .filter_map(|(index, (model, matrix))| Occlusion::query(index, model, matrix, (&left_pv, &right_pv)))
+			                                          .collect(),
+			_ => Vec::new(),
+		};
+		
+		self.occluded = scene.iter()
+		                     .enumerate()
+		                     .filter(|(index, (model, _))| model.visible() && self.occluded(*index, model))
+		                     .count();
+		
+		// Submitted before anything else is recorded, so the compute queue has the longest time to finish
+		let occluded: Vec<bool> = match &self.async_culling {
+			Some(_) if !self.static_scene => scene.iter().enumerate().map(|(index, (model, _))| self.occluded(index, model)).collect(),
+			_ => Vec::new(),
+		};
+		
+		let cull_dispatch = match &mut self.async_culling {
+			Some(async_culling) if !self.static_scene => match async_culling.dispatch(scene, (&left_frustum, &right_frustum), self.frustum_culling, &occluded) {
+				Ok(cull_dispatch) => Some(cull_dispatch),
+				Err(err) if err.device_lost() => return Err(self.device_lost()),
+				Err(err) => return Err(err.into()),
+			},
+			_ => None,
+		};
+		
+		let region = self.render_region();
+		let dynamic_state = self.dynamic_state(region);
+		
+		// Unskinned models share one palette, their vertices have no weights to read it with
+		let rest_joints = self.joint_set(&[])?;
+		let joint_sets = scene.iter()
+		                      .map(|(model, _)| match model.joints() {
+			                      Some(joints) => self.joint_set(joints),
+			                      None => Ok(rest_joints.clone()),
+		                      })
+		                      .collect::<Result<Vec<_>, _>>()?;
+		
+		// Models that weren't at the same place in the scene last frame start out still. Static scenes don't move, which
+		// also keeps the transforms recorded with them valid.
+		let previous_matrices: Vec<Matrix4<f32>> = scene.iter()
+		                                                .enumerate()
+		                                                .map(|(index, (model, matrix))| match self.previous_matrices.get(index) {
+			                                                Some(&(key, previous)) if !self.static_scene && key == model.id() => previous,
+			                                                _ => *matrix,
+		                                                })
+		                                                .collect();
+		
+		let transforms = self.transform_set(scene.iter().map(|(_, matrix)| matrix).zip(&previous_matrices))?;
+		
+		// Everything visible casts shadows, whether the eyes see it or not
+		let casters: Vec<(&(Model, Matrix4<f32>), &Arc<dyn DescriptorSet + Send + Sync>)> = scene.iter()
+		                                                                                         .zip(&joint_sets)
+		                                                                                         .filter(|((model, _), _)| model.visible() && !model.transparent() && model.indices.is_some() && model.loaded())
+		                                                                                         .collect();
+		
+		let bounds = shadow::enclosing_sphere(casters.iter().filter_map(|((model, matrix), _)| Some(model.bounding_sphere()?.transform(matrix))));
+		let light_pv = bounds.map_or_else(Matrix4::identity, |bounds| ShadowMap::light_pv(self.light_direction, &bounds));
+		
+		let light_uniform = LightUniform { direction: self.light_direction.into(),
+		                                   ambient: self.ambient,
+		                                   pv: light_pv.into() };
+		let light = self.light_buffer.next(light_uniform)?;
+		
+		if self.debug_bounds {
+			self.draw_bounds(scene);
+		}
+		
+		if self.show_boundary {
+			self.draw_boundary();
+		}
+		
+		// Taken even if the frame fails, so a broken frame doesn't pile up lines
+		let frame_lines = if self.lines.pending.is_empty() {
+			                  None
+		                  } else {
+			                  Some(self.lines.buffer.chunk(self.lines.pending.drain(..))?)
+		                  };
+		
+		let frame_text = if self.text.pending.is_empty() {
+			                 None
+		                 } else {
+			                 Some(self.text.buffer.chunk(self.text.pending.drain(..))?)
+		                 };
+		
+		let (left_set, left_view_set) = self.eye_sets(light.clone(), left_pv, left_previous_pv)?;
+		let (right_set, right_view_set) = self.eye_sets(light.clone(), right_pv, right_previous_pv)?;
+		
+		#[cfg(feature = "indirect-draw")]
+		let indirect = match &self.indirect {
+			Some(indirect) if self.indirect_draw && !self.wireframe => Some(indirect.frame(scene, &previous_matrices, prepass)?),
+			_ => None,
+		};
+		
+		let shadow_state = self.dynamic_state([self.shadow_map.size(), self.shadow_map.size()]);
+		let mut shadow_command_buffer = AutoCommandBufferBuilder::new(self.device.clone(), self.queue.family())?
+		                                                         .begin_render_pass(self.shadow_map.frame_buffer.clone(),
+		                                                                            false,
+		                                                                            self.shadow_map.clear_values())?;
+		
+		for ((model, matrix), joint_set) in casters {
+			let push_constants: [[f32; 4]; 4] = (light_pv * matrix).into();
+			
+			shadow_command_buffer = match &model.indices {
+				Some(Indices::U16(indices)) => shadow_command_buffer.draw_indexed(self.shadow_pipeline.clone(), &shadow_state, model.vertices.clone(), indices.clone(), joint_set.clone(), push_constants)?,
+				Some(Indices::U32(indices)) => shadow_command_buffer.draw_indexed(self.shadow_pipeline.clone(), &shadow_state, model.vertices.clone(), indices.clone(), joint_set.clone(), push_constants)?,
+				None => shadow_command_buffer,
+			};
+		}
+		
+		let shadow_command_buffer = shadow_command_buffer.end_render_pass()?
+		                                                 .build()?;
+		
+		if self.static_scene && self.prerecorded.is_none() {
+			self.prerecorded = Some(Prerecorded::new(&self.device, self.frames_in_flight)?);
+		}
+		
+		// Static scenes reuse what was recorded into this frame's slot, unless the models drawn have changed since
+		let slot = self.prerecorded.as_mut().map(Prerecorded::advance);
+		let key = slot.map(|_| RecordKey { drawn: scene.iter()
+		                                               .enumerate()
+		                                               .filter(|(_, (model, _))| model.visible() && model.loaded())
+		                                               .map(|(index, _)| index)
+		                                               .collect(),
+		                                   region,
+		                                   prepass,
+		                                   wireframe: self.wireframe,
+		                                   #[cfg(feature = "indirect-draw")]
+		                                   indirect_draw: indirect.is_some() });
+		
+		let eye_draws = |set, view_set| EyeDraws { device: &self.device,
+		                                           family: self.queue.family(),
+		                                           subpass: Subpass::from(self.pipeline.render_pass().clone(), 0).unwrap(),
+		                                           scene: &*scene,
+		                                           joint_sets: &joint_sets,
+		                                           set,
+		                                           view_set,
+		                                           transforms: transforms.clone(),
+		                                           pipeline: &pipeline,
+		                                           transparent_pipeline: &transparent_pipeline,
+		                                           points_pipeline: &self.points_pipeline,
+		                                           prepass_pipeline: &self.prepass_pipeline,
+		                                           dynamic_state: &dynamic_state,
+		                                           #[cfg(feature = "indirect-draw")]
+		                                           indirect: indirect.as_ref() };
+		
+		let visibility = match cull_dispatch.map(CullDispatch::wait) {
+			Some(Ok(visibility)) => Some(visibility),
+			Some(Err(err)) if err.device_lost() => return Err(self.device_lost()),
+			Some(Err(err)) => return Err(err.into()),
+			None => None,
+		};
+		
+		let (left_culling, right_culling) = match &visibility {
+			Some(visibility) => (Culling::Visibility(visibility, LEFT_VISIBLE), Culling::Visibility(visibility, RIGHT_VISIBLE)),
+			None => (Culling::Frustum(&left_frustum), Culling::Frustum(&right_frustum)),
+		};
+		
+		let (left_commands, right_commands, recorded): (SceneCommands, SceneCommands, bool) = match (&self.prerecorded, slot, &key) {
+			(Some(prerecorded), Some(slot), Some(key)) => {
+				let slot = prerecorded.slot(slot);
+				*slot.light.write()? = light_uniform;
+				*slot.views.0.write()? = ViewUniform { pv: left_pv.into(), previous_pv: left_previous_pv.into() };
+				*slot.views.1.write()? = ViewUniform { pv: right_pv.into(), previous_pv: right_previous_pv.into() };
+				
+				match slot.commands(key) {
+					Some((left, right)) => (left.clone(), right.clone(), false),
+					// Recorded without culling, which depends on the view. Transparent models keep the order they had.
+					None => {
+						let (set, view_set) = self.uniform_sets(slot.light.clone(), slot.views.0.clone())?;
+						let left = eye_draws(set, view_set).scene(&self.draw_order(Culling::None, &left_pv, scene), prepass, true)?;
+						
+						let right = if stereo {
+							let (set, view_set) = self.uniform_sets(slot.light.clone(), slot.views.1.clone())?;
+							eye_draws(set, view_set).scene(&self.draw_order(Culling::None, &right_pv, scene), prepass, true)?
+						} else {
+							(Vec::new(), Vec::new())
+						};
+						
+						(left, right, true)
+					},
+				}
+			},
+			_ => {
+				let left = eye_draws(left_set.clone(), left_view_set.clone()).scene(&self.draw_order(left_culling, &left_pv, scene), prepass, false)?;
+				
+				let right = if stereo {
+					eye_draws(right_set.clone(), right_view_set.clone()).scene(&self.draw_order(right_culling, &right_pv, scene), prepass, false)?
+				} else {
+					(Vec::new(), Vec::new())
+				};
+				
+				(left, right, false)
+			},
+		};
+		
+		// The mirror shows the scene from the eye reflected across it. Recorded every frame, it moves with the head even
+		// in static scenes.
+		let reflect = |(projection, view): (&Matrix4<f32>, &Matrix4<f32>), (previous_projection, previous_view): (&Matrix4<f32>, &Matrix4<f32>)|
+		              -> Result<Option<Vec<Arc<ModelCommandBuffer>>>, RenderError> {
+			let reflection = match &self.reflection {
+				Some(reflection) if !self.wireframe => reflection,
+				_ => return Ok(None),
+			};
+			
+			let pv = match reflection.reflect(projection, view) {
+				Some(pv) => pv,
+				None => return Ok(None),
+			};
+			
+			let previous_pv = reflection.reflect(previous_projection, previous_view).unwrap_or(pv);
+			let (set, view_set) = self.eye_sets(light.clone(), pv, previous_pv)?;
+			let draws = EyeDraws { pipeline: &reflection.pipelines.pipeline,
+			                       transparent_pipeline: &reflection.pipelines.transparent_pipeline,
+			                       points_pipeline: &reflection.pipelines.points_pipeline,
+			                       #[cfg(feature = "indirect-draw")]
+			                       indirect: None,
+			                       ..eye_draws(set, view_set) };
+			
+			let (_, models) = draws.scene(&self.draw_order(Culling::Reflection(&Frustum::new(&pv)), &pv, scene), false, false)?;
+			Ok(Some(models))
+		};
+		
+		let left_reflected = reflect((&left_projection, &left_view), (&left_previous.0, &left_previous.1))?;
+		let right_reflected = if stereo { reflect((&right_projection, &right_view), (&right_previous.0, &right_previous.1))? } else { None };
+		
+		// One command buffer per eye, so GPU times can be measured between them
+		let left_command_buffer = AutoCommandBufferBuilder::new(self.device.clone(), self.queue.family())?;
+		let left_command_buffer = self.draw_eye(left_command_buffer, &self.eyes.0, self.clear_color.0, &eye_draws(left_set, left_view_set), &left_commands,
+		                                        left_reflected.as_deref(), (&left_projection, &left_view), (&left_previous.0, &left_previous.1), &frame_lines, &frame_text)?
+		                              .build()?;
+		
+		let right_command_buffer = if stereo {
+			let right_command_buffer = AutoCommandBufferBuilder::new(self.device.clone(), self.queue.family())?;
+			Some(self.draw_eye(right_command_buffer, &self.eyes.1, self.clear_color.1, &eye_draws(right_set, right_view_set), &right_commands,
+			                   right_reflected.as_deref(), (&right_projection, &right_view), (&right_previous.0, &right_previous.1), &frame_lines, &frame_text)?
+			         .build()?)
+		} else {
+			None
+		};
+		
+		if let (Some(prerecorded), Some(slot), Some(key), true) = (&mut self.prerecorded, slot, key, recorded) {
+			prerecorded.store(slot, key, (left_commands, right_commands));
+		}
+		
+		// Submitted right away, so it ends up in front of everything the frame's flush submits
+		if let Some(gpu_timer) = &mut self.gpu_timer {
+			match gpu_timer.begin() {
+				Ok(()) => {},
+				Err(err) if err.device_lost() => return Err(self.device_lost()),
+				Err(err) => return Err(err.into()),
+			}
+		}
+		
+		// vulkano doesn't synchronize what secondary command buffers use, the semaphore makes the eyes wait for the
+		// shadow map they sample
+		let future = self.previous_frame_end.take()
+		                                    .unwrap()
+		                                    .then_execute(self.queue.clone(), shadow_command_buffer)?
+		                                    .then_signal_semaphore();
+		
+		let future = self.mark_gpu_time(Box::new(future))?;
+		let future = self.mark_gpu_time(Box::new(future.then_execute(self.queue.clone(), left_command_buffer)?))?;
+		let future = match right_command_buffer {
+			Some(right_command_buffer) => Box::new(future.then_execute(self.queue.clone(), right_command_buffer)?),
+			None => future,
+		};
+		let future = self.mark_gpu_time(future)?;
+		
+		// Stands in for the frame until it's submitted, whatever else gets queued in the meantime is joined to it then
+		self.previous_frame_end = Some(Box::new(sync::now(self.device.clone())) as Box<_>);
+		
+		// What the next frame's velocity is measured from
+		self.previous_views = Some(((left_projection, left_view), (right_projection, right_view)));
+		self.previous_matrices = scene.iter().map(|(model, matrix)| (model.id(), *matrix)).collect();
+		
+		Ok((future, PendingFrame { hmd_pose: *hmd_pose, region, queries, stereo }))
+	}
+	
+	fn submit_frame(&mut self, pending: PendingFrame, future: Box<dyn GpuFuture>) -> Result<(), RenderError> {
+		let PendingFrame { hmd_pose, region, queries, stereo } = pending;
+		let mut future: Box<dyn GpuFuture> = Box::new(self.previous_frame_end.take().unwrap().join(future));
+		
+		if let Some(compositor) = &self.compositor {
+			let dimensions = self.eyes.0.target().image.dimensions();
+			let bounds = Bounds { min: (0.0, 0.0),
+			                      max: (region[0] as f32 / dimensions[0] as f32, region[1] as f32 / dimensions[1] as f32) };
+			
+			unsafe {
+				compositor.submit(openvr::Eye::Left,  &self.eyes.0.target().texture, Some(&bounds), Some(hmd_pose))?;
+				compositor.submit(openvr::Eye::Right, &self.eyes.1.target().texture, Some(&bounds), Some(hmd_pose))?;
+			}
+		}
+		
+		self.frame_timer.tick();
+		
+		if let Some(mirror) = &mut self.mirror {
+			future = match mirror.present(future, &self.queue, &self.eyes.0.target().image, region) {
+				Ok(future) => future,
+				Err(err) if err.device_lost() => return Err(self.device_lost()),
+				Err(err) => return Err(err.into()),
+			};
+			
+			if mirror.closed() {
+				self.mirror = None;
+			}
+		}
+		
+		let future = future.then_signal_fence_and_flush();
+		
+		match future {
+			Ok(future) => {
+				let future = Arc::new(future);
+				self.in_flight.push_back(future.clone());
+				self.previous_frame_end = Some(Box::new(future) as Box<_>);
+			},
+			Err(FlushError::OutOfDate) => {
+				warn!("Flush Error: Out of date, ignoring");
+				if let Some(mirror) = &mut self.mirror {
+					mirror.invalidate();
+				}
+				self.previous_frame_end = Some(Box::new(sync::now(self.device.clone())) as Box<_>);
+			},
+			Err(FlushError::DeviceLost) => return Err(self.device_lost()),
+			Err(err) => return Err(err.into()),
+		}
+		
+		if let Some(gpu_timer) = &mut self.gpu_timer {
+			match gpu_timer.end() {
+				Ok(()) => {},
+				Err(err) if err.device_lost() => return Err(self.device_lost()),
+				Err(err) => return Err(err.into()),
+			}
+		}
+		
+		if let Some(occlusion) = &mut self.occlusion {
+			match occlusion.submit(&queries, region, stereo) {
+				Ok(()) => {},
+				Err(err) if err.device_lost() => return Err(self.device_lost()),
+				Err(err) => return Err(err.into()),
+			}
+		}
+		
+		Ok(())
+	}
+	
+	// Timestamps can only be written between submissions, so a measured frame is flushed in parts. Flushing a
+	// command buffer's future and executing more after it would submit it again, a semaphore in between avoids that.
+	fn mark_gpu_time(&mut self, future: Box<dyn GpuFuture>) -> Result<Box<dyn GpuFuture>, RenderError> {
+		if !self.gpu_timer.as_ref().map_or(false, GpuTimer::measuring) {
+			return Ok(future);
+		}
+		
+		let future = match future.then_signal_semaphore_and_flush() {
+			Ok(future) => future,
+			Err(FlushError::DeviceLost) => return Err(self.device_lost()),
+			Err(err) => return Err(err.into()),
+		};
+		
+		match self.gpu_timer.as_mut().unwrap().mark() {
+			Ok(()) => Ok(Box::new(future)),
+			Err(err) if err.device_lost() => Err(self.device_lost()),
+			Err(err) => Err(err.into()),
+		}
+	}
+	
+	// Everything created on a lost device is unusable, vulkano 0.18 can't move the scene over to a new one.
+	// Further frames keep failing, the caller has to create a new renderer and reload its models.
+	fn device_lost(&mut self) -> RenderError {
+		self.in_flight.clear();
+		self.previous_frame_end = Some(Box::new(sync::now(self.device.clone())) as Box<_>);
+		RenderError::DeviceLost
+	}
+	
+	// Pose the frame is drawn from, following the tracking loss policy while the HMD's pose isn't valid
+	fn tracked_hmd_pose(&mut self, pose: &TrackedDevicePose) -> [[f32; 4]; 3] {
+		if pose.pose_is_valid() {
+			if self.tracking_lost.take().is_some() {
+				info!("HMD tracking recovered");
+			}
+			
+			return *pose.device_to_absolute_tracking();
+		}
+		
+		if self.tracking_lost.is_none() {
+			warn!("HMD tracking lost, {:?}", self.tracking_loss);
+			self.tracking_lost = Some(Instant::now());
+		}
+		
+		// The frame pose is the last valid one, frames drawn since tracking got lost were drawn from it as well
+		match self.tracking_loss {
+			TrackingLossPolicy::Freeze | TrackingLossPolicy::FadeToBlack => self.frame_pose,
+			TrackingLossPolicy::Identity => IDENTITY_POSE,
+		}
+	}
+	
+	// Opacity of the black drawn over the frame, `None` while there's nothing to fade
+	fn tracking_loss_fade(&self) -> Option<f32> {
+		match (self.tracking_loss, self.tracking_lost) {
+			(TrackingLossPolicy::FadeToBlack, Some(since)) => Some((since.elapsed().as_secs_f32() / TRACKING_LOSS_FADE).min(1.0)),
+			_ => None,
+		}
+	}
+	
+	// Head to eye space transformation, with the eye offset scaled by the IPD multiplier
+	fn head_to_eye(&self, eye: &Eye) -> Matrix4<f32> {
+		let mut eye_to_head = eye.eye_to_head;
+		eye_to_head.w = (eye_to_head.w.truncate() * self.ipd_scale).extend(1.0);
+		
+		eye_to_head.inverse_transform().unwrap_or_else(Matrix4::identity)
+	}
+	
+	// Opaque models first, then transparent ones back to front so they blend over what's behind them.
+	// `Culling::None` keeps every visible, loaded model. `Frustum` also skips models outside the frustum or occluded
+	// in the last frame, `Reflection` only those outside the frustum, and `Visibility` those `AsyncCulling` culled.
+	fn draw_order(&self, culling: Culling, pv: &Matrix4<f32>, scene: &[(Model, Matrix4<f32>)]) -> Vec<usize> {
+		let mut order = Vec::with_capacity(scene.len());
+		let mut transparent = Vec::new();
+		
+		for (index, (model, matrix)) in scene.iter().enumerate() {
+			let culled = match culling {
+				Culling::None => false,
+				Culling::Frustum(frustum) => self.culled(frustum, model, matrix) || self.occluded(index, model),
+				Culling::Reflection(frustum) => self.culled(frustum, model, matrix),
+				Culling::Visibility(visibility, eye) => visibility.get(index).map_or(false, |visible| visible & eye == 0),
+			};
+			if !model.visible() || !model.loaded() || culled { continue };
+			
+			match model.bounding_sphere() {
+				Some(sphere) if model.transparent() => {
+					// Clip space w is the depth along the eye's view direction
+					let depth = (pv * sphere.transform(matrix).center.to_homogeneous()).w;
+					transparent.push((depth, index));
+				},
+				_ => order.push(index),
+			}
+		}
+		
+		transparent.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+		order.extend(transparent.into_iter().map(|(_, index)| index));
+		order
+	}
+	
+	fn occluded(&self, index: usize, model: &Model) -> bool {
+		match &self.occlusion {
+			Some(occlusion) if self.occlusion_culling => occlusion.occluded(index, model),
+			_ => false,
+		}
+	}
+	
+	fn culled(&self, frustum: &Frustum, model: &Model, matrix: &Matrix4<f32>) -> bool {
+		if !self.frustum_culling {
+			return false;
+		}
+		
+		match model.bounding_sphere() {
+			Some(sphere) => {
+				let sphere = sphere.transform(matrix);
+				!frustum.intersects_sphere(sphere.center, sphere.radius)
+			},
+			None => false,
+		}
+	}
+	
+	// Light, shadow map and the eye's projection and view, shared by all models drawn for that eye.
+	// The second set only has the projection and view, for the depth prepass and lines.
+	fn eye_sets<L>(&self, light: L, pv: Matrix4<f32>, previous_pv: Matrix4<f32>) -> Result<(Arc<dyn DescriptorSet + Send + Sync>, Arc<dyn DescriptorSet + Send + Sync>), RenderError>
+	              where L: BufferAccess + TypedBufferAccess<Content = LightUniform> + Send + Sync + 'static {
+		let view = self.view_buffer.next(ViewUniform { pv: pv.into(), previous_pv: previous_pv.into() })?;
+		self.uniform_sets(light, view)
+	}
+	
+	// Same sets around uniforms the caller manages itself
+	fn uniform_sets<L, V>(&self, light: L, view: V) -> Result<(Arc<dyn DescriptorSet + Send + Sync>, Arc<dyn DescriptorSet + Send + Sync>), RenderError>
+	                     where L: BufferAccess + TypedBufferAccess<Content = LightUniform> + Send + Sync + 'static,
+	                           V: BufferAccess + TypedBufferAccess<Content = ViewUniform> + Clone + Send + Sync + 'static {
+		let set = Arc::new(
+			PersistentDescriptorSet::start(self.pipeline.descriptor_set_layout(1).ok_or(RenderError::NoLayout)?.clone())
+			                        .add_buffer(light)?
+			                        .add_sampled_image(self.shadow_map.image.clone(), self.shadow_map.sampler.clone())?
+			                        .add_buffer(view.clone())?
+			                        .build()?
+		);
+		
+		let view_set = Arc::new(
+			PersistentDescriptorSet::start(self.prepass_pipeline.descriptor_set_layout(0).ok_or(RenderError::NoLayout)?.clone())
+			                        .add_buffer(view)?
+			                        .build()?
+		);
+		
+		Ok((set, view_set))
+	}
+	
+	// Palette of one model for a frame, missing joints are left as identities. The shadow and prepass pipelines declare
+	// the same block in a set of its own, so the set is bound to them as well.
+	fn joint_set(&self, joints: &[Matrix4<f32>]) -> Result<Arc<dyn DescriptorSet + Send + Sync>, RenderError> {
+		let mut matrices = [Matrix4::identity().into(); skin::MAX_JOINTS];
+		for (matrix, joint) in matrices.iter_mut().zip(joints) {
+			*matrix = (*joint).into();
+		}
+		
+		let joints = self.joint_buffer.next(JointUniform { matrices })?;
+		
+		Ok(Arc::new(
+			PersistentDescriptorSet::start(self.pipeline.descriptor_set_layout(2).ok_or(RenderError::NoLayout)?.clone())
+			                        .add_buffer(joints)?
+			                        .build()?
+		))
+	}
+	
+	// Model and normal matrices for a frame, along with the model matrices of the previous one, in the order of
+	// `matrices`. The depth prepass pipeline declares the start of the same block as set 2.
+	fn transform_set<'a, I>(&self, matrices: I) -> Result<Arc<TransformSet>, RenderError>
+	                       where I: IntoIterator<Item = (&'a Matrix4<f32>, &'a Matrix4<f32>)> {
+		self.transforms.set(self.pipeline.descriptor_set_layout(3).ok_or(RenderError::NoLayout)?, matrices)
+	}
+	
+	// Distances are measured from the head pose of the frame, to both ends of every short segment
+	fn draw_boundary(&mut self) {
+		let corners = match self.chaperone.as_ref().and_then(Chaperone::get_play_area_rect) {
+			Some(corners) => corners,
+			None => return,
+		};
+		
+		let head = Point3::new(self.frame_pose[0][3], self.frame_pose[1][3], self.frame_pose[2][3]);
+		let color = self.boundary_color;
+		let faded = |point: Point3<f32>| {
+			let fade = ((BOUNDARY_FADE.1 - (point - head).magnitude()) / (BOUNDARY_FADE.1 - BOUNDARY_FADE.0)).max(0.0).min(1.0);
+			LineVertex::new(point.into(), [color[0], color[1], color[2], color[3] * fade])
+		};
+		
+		let up = Vector3::unit_y();
+		let rows = (BOUNDARY_HEIGHT / BOUNDARY_SPACING).round() as usize;
+		let mut walls = Vec::new();
+		
+		for index in 0 .. corners.len() {
+			let start = Point3::from(corners[index]);
+			let end = Point3::from(corners[(index + 1) % corners.len()]);
+			
+			// The floor outline doesn't fade
+			self.draw_line(start, end, color);
+			
+			for row in 1 ..= rows {
+				let height = up * (row as f32 * BOUNDARY_SPACING);
+				walls.push((start + height, end + height));
+			}
+			
+			let columns = ((end - start).magnitude() / BOUNDARY_SPACING).round().max(1.0) as usize;
+			for column in 0 .. columns {
+				let bottom = start + (end - start) * (column as f32 / columns as f32);
+				walls.push((bottom, bottom + up * BOUNDARY_HEIGHT));
+			}
+		}
+		
+		for (start, end) in walls {
+			let segments = ((end - start).magnitude() / BOUNDARY_SEGMENT).ceil().max(1.0) as usize;
+			
+			for segment in 0 .. segments {
+				self.lines.pending.push(faded(start + (end - start) * (segment as f32 / segments as f32)));
+				self.lines.pending.push(faded(start + (end - start) * ((segment + 1) as f32 / segments as f32)));
+			}
+		}
+	}
+	
+	// Including models culled by one eye, the other one may still see them. Models still loading have no bounds yet.
+	fn draw_bounds(&mut self, scene: &[(Model, Matrix4<f32>)]) {
+		for (model, matrix) in scene {
+			let aabb = match model.aabb() {
+				Some(aabb) if model.visible() => aabb,
+				_ => continue,
+			};
+			
+			let corners = aabb.corners();
+			
+			// Edges connect the corners that differ in a single bit
+			for index in 0 .. corners.len() {
+				for &bit in &[1, 2, 4] {
+					if index & bit == 0 {
+						self.draw_line(matrix.transform_point(corners[index]), matrix.transform_point(corners[index | bit]), BOUNDS_COLOR);
+					}
+				}
+			}
+		}
+	}
+	
+	// Everything inside the eye's render pass goes into secondary command buffers, the depth prepass and the models come
+	// recorded already
+	// `reflected` are the models seen in the mirror, if the eye sees it
+	fn draw_eye(&self, command_buffer: AutoCommandBufferBuilder, eye: &Eye, clear_color: [f32; 4], draws: &EyeDraws, (prepass, models): &SceneCommands,
+	            reflected: Option<&[Arc<ModelCommandBuffer>]>, view: (&Matrix4<f32>, &Matrix4<f32>), previous_view: (&Matrix4<f32>, &Matrix4<f32>), frame_lines: &Option<CpuBufferPoolChunk<LineVertex, Arc<StdMemoryPool>>>,
+	            frame_text: &Option<CpuBufferPoolChunk<TextVertex, Arc<StdMemoryPool>>>)
+	           -> Result<AutoCommandBufferBuilder, RenderError> {
+		let mut hidden_area = draws.builder()?;
+		if let Some(vertices) = &eye.hidden_area {
+			hidden_area = hidden_area.draw(self.hidden_area_pipeline.clone(), draws.dynamic_state, vertices.clone(), (), ())?;
+		}
+		
+		let reflection = match (&self.reflection, reflected) {
+			(Some(reflection), Some(reflected)) => Some((reflection, reflected)),
+			_ => None,
+		};
+		
+		// The mirror goes after the hidden area, so it isn't marked where the lens hides it
+		let pv = view.0 * view.1;
+		let cover = match reflection {
+			Some((reflection, _)) => {
+				hidden_area = reflection.mark(hidden_area, &pv, draws.dynamic_state)?;
+				Some(reflection.cover(draws.builder()?, &pv, draws.dynamic_state)?.build()?)
+			},
+			None => None,
+		};
+		
+		let hidden_area = hidden_area.build()?;
+		
+		let skybox = match &self.skybox {
+			Some(skybox) => Some(draws.builder()?
+			                          .draw_indexed(self.skybox_pipeline.clone(),
+			                                        draws.dynamic_state,
+			                                        skybox.vertices.clone(),
+			                                        skybox.indices.clone(),
+			                                        skybox.set.clone(),
+			                                        skybox.push_constants(view, previous_view))?
+			                          .build()?),
+			None => None,
+		};
+		
+		let overlays = self.draw_lines(draws.builder()?, frame_lines, &draws.view_set, draws.dynamic_state)?;
+		let mut overlays = self.draw_frame_text(overlays, frame_text, &draws.view_set, draws.dynamic_state)?;
+		if let Some(alpha) = self.tracking_loss_fade() {
+			overlays = overlays.draw(self.fade_pipeline.clone(), draws.dynamic_state, self.fade_triangle.clone(), (), alpha)?;
+		}
+		
+		let overlays = overlays.build()?;
+		
+		let command_buffer = command_buffer.begin_render_pass(self.scene_frame_buffer(eye), true, self.clear_values(clear_color))?;
+		
+		// Unsafe because vulkano doesn't synchronize the secondary command buffers. They only sample the shadow map,
+		// which the frame waits for, and otherwise read buffers and images that never change once uploaded.
+		let command_buffer = unsafe {
+			let mut command_buffer = command_buffer.execute_commands(hidden_area)?;
+			
+			// Everything else is drawn in front of the reflection, against the mirror's depth
+			if let (Some((_, reflected)), Some(cover)) = (reflection, cover) {
+				command_buffer = command_buffer.execute_commands_from_vec(reflected.to_vec())?
+				                               .execute_commands(cover)?;
+			}
+			
+			// The prepass goes before the skybox as well, which then only covers what's left at the far plane
+			let mut command_buffer = command_buffer.execute_commands_from_vec(prepass.clone())?;
+			
+			if let Some(skybox) = skybox {
+				command_buffer = command_buffer.execute_commands(skybox)?;
+			}
+			
+			command_buffer.execute_commands_from_vec(models.clone())?
+			              .execute_commands(overlays)?
+		};
+		
+		let command_buffer = command_buffer.end_render_pass()?;
+		let command_buffer = self.draw_tonemap(command_buffer, eye, draws.dynamic_state)?;
+		self.draw_fxaa(command_buffer, eye, draws.dynamic_state)
+	}
+	
+	// Axes first, so they win against the grid lines they overlap. Lines from `draw_line` come last.
+	fn draw_lines(&self, mut command_buffer: AutoCommandBufferBuilder, frame_lines: &Option<CpuBufferPoolChunk<LineVertex, Arc<StdMemoryPool>>>,
+	              view_set: &Arc<dyn DescriptorSet + Send + Sync>, dynamic_state: &DynamicState)
+	             -> Result<AutoCommandBufferBuilder, RenderError> {
+		// The line pipeline is the only one with a dynamic width
+		let dynamic_state = &DynamicState { line_width: Some(self.line_width), ..dynamic_state.clone() };
+		
+		if self.show_axes {
+			command_buffer = command_buffer.draw(self.lines.pipeline.clone(), dynamic_state, self.lines.axes.clone(), view_set.clone(), ())?;
+		}
+		
+		if self.show_grid {
+			command_buffer = command_buffer.draw(self.lines.pipeline.clone(), dynamic_state, self.lines.grid.clone(), view_set.clone(), ())?;
+		}
+		
+		if let Some(frame_lines) = frame_lines {
+			command_buffer = command_buffer.draw(self.lines.pipeline.clone(), dynamic_state, frame_lines.clone(), view_set.clone(), ())?;
+		}
+		
+		Ok(command_buffer)
+	}
+	
+	fn draw_frame_text(&self, command_buffer: AutoCommandBufferBuilder, frame_text: &Option<CpuBufferPoolChunk<TextVertex, Arc<StdMemoryPool>>>,
+	                   view_set: &Arc<dyn DescriptorSet + Send + Sync>, dynamic_state: &DynamicState)
+	                  -> Result<AutoCommandBufferBuilder, RenderError> {
+		Ok(match frame_text {
+			Some(frame_text) => command_buffer.draw(self.text.pipeline.clone(), dynamic_state, frame_text.clone(), (view_set.clone(), self.text.set.clone()), ())?,
+			None => command_buffer,
+		})
+	}
+	
+	// Where the eye's scene is drawn, its own image if tone mapping or FXAA still have to run over it
+	fn scene_frame_buffer(&self, eye: &Eye) -> Arc<dyn FramebufferAbstract + Send + Sync> {
+		match &eye.hdr {
+			Some(hdr) => hdr.frame_buffer.clone(),
+			None => self.first_frame_buffer(eye),
+		}
+	}
+	
+	// Writes the image FXAA reads if it's on, otherwise the eye's current target
+	fn first_frame_buffer(&self, eye: &Eye) -> Arc<dyn FramebufferAbstract + Send + Sync> {
+		match &eye.fxaa {
+			Some(fxaa) if self.fxaa => fxaa.frame_buffer.clone(),
+			_ => eye.target().frame_buffer.clone(),
+		}
+	}
+	
+	// Tone maps the HDR image the scene was drawn into, does nothing without HDR
+	fn draw_tonemap(&self, command_buffer: AutoCommandBufferBuilder, eye: &Eye, dynamic_state: &DynamicState) -> Result<AutoCommandBufferBuilder, RenderError> {
+		match &eye.hdr {
+			Some(hdr) => self.tonemap_into(command_buffer, &hdr.image, self.first_frame_buffer(eye), dynamic_state),
+			None => Ok(command_buffer),
+		}
+	}
+	
+	// `frame_buffer` has to belong to the tonemap render pass
+	fn tonemap_into(&self, command_buffer: AutoCommandBufferBuilder, image: &Arc<AttachmentImage<Format>>, frame_buffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+	                dynamic_state: &DynamicState)
+	               -> Result<AutoCommandBufferBuilder, RenderError> {
+		let tonemap = match &self.tonemap {
+			Some(tonemap) => tonemap,
+			None => return Ok(command_buffer),
+		};
+		
+		let set = Arc::new(
+			PersistentDescriptorSet::start(tonemap.pipeline.descriptor_set_layout(0).ok_or(RenderError::NoLayout)?.clone())
+			                        .add_sampled_image(image.clone(), tonemap.sampler.clone())?
+			                        .build()?
+		);
+		
+		Ok(command_buffer.begin_render_pass(frame_buffer, false, vec![ClearValue::None])?
+		                 .draw(tonemap.pipeline.clone(),
+		                       dynamic_state,
+		                       BufferlessVertices { vertices: 3, instances: 1 },
+		                       set,
+		                       self.exposure.exp2())?
+		                 .end_render_pass()?)
+	}
+	
+	// Writes the eye's current target from the image the scene was drawn into, does nothing without FXAA
+	fn draw_fxaa(&self, command_buffer: AutoCommandBufferBuilder, eye: &Eye, dynamic_state: &DynamicState) -> Result<AutoCommandBufferBuilder, RenderError> {
+		let (fxaa_pass, fxaa, frame_buffer) = match (&self.fxaa_pass, &eye.fxaa, &eye.target().fxaa_frame_buffer) {
+			(Some(fxaa_pass), Some(fxaa), Some(frame_buffer)) if self.fxaa => (fxaa_pass, fxaa, frame_buffer),
+			_ => return Ok(command_buffer),
+		};
+		
+		let set = Arc::new(
+			PersistentDescriptorSet::start(fxaa_pass.pipeline.descriptor_set_layout(0).ok_or(RenderError::NoLayout)?.clone())
+			                        .add_sampled_image(fxaa.image.clone(), fxaa_pass.sampler.clone())?
+			                        .build()?
+		);
+		
+		Ok(command_buffer.begin_render_pass(frame_buffer.clone(), false, vec![ClearValue::None])?
+		                 .draw(fxaa_pass.pipeline.clone(),
+		                       dynamic_state,
+		                       BufferlessVertices { vertices: 3, instances: 1 },
+		                       set,
+		                       ())?
+		                 .end_render_pass()?)
+	}
+	
+	// Top left part of the eye images that gets rendered to, all of it without adaptive resolution
+	fn render_region(&self) -> [u32; 2] {
+		let dimensions = self.eyes.0.target().image.dimensions();
+		let fraction = self.adaptive_resolution.as_ref().map_or(1.0, AdaptiveResolution::fraction);
+		
+		[((dimensions[0] as f32 * fraction).round() as u32).max(1).min(dimensions[0]),
+		 ((dimensions[1] as f32 * fraction).round() as u32).max(1).min(dimensions[1])]
+	}
+	
+	// Pipelines leave the viewport dynamic, so the render size can change without rebuilding them
+	fn dynamic_state(&self, region: [u32; 2]) -> DynamicState {
+		DynamicState {
+			viewports: Some(vec![Viewport { origin: [0.0, 0.0],
+			                                dimensions: [region[0] as f32, region[1] as f32],
+			                                depth_range: 0.0 .. 1.0 }]),
+			..DynamicState::none()
+		}
+	}
+	
+	// Attachment order: color, velocity, depth/stencil and, with MSAA, the resolve targets which are never cleared
+	fn clear_values(&self, color: [f32; 4]) -> Vec<ClearValue> {
+		// Nothing moved where nothing is drawn
+		if self.msaa > 1 {
+			vec![ color.into(), [0.0; 4].into(), ClearValue::DepthStencil((1.0, 0)), ClearValue::None, ClearValue::None ]
+		} else {
+			vec![ color.into(), [0.0; 4].into(), ClearValue::DepthStencil((1.0, 0)) ]
+		}
+	}
 }
 
+// View of one eye, `view` being the inverse of the HMD pose and `rotation` the eye's pitch and yaw from
+// `Renderer::set_eye_rotation`. The eye turns yaw first, then pitch around its own sideways axis.
+fn eye_view(view: &Matrix4<f32>, head_to_eye: &Matrix4<f32>, rotation: Vector2<f32>) -> Matrix4<f32> {
+	let gaze = Matrix4::from_angle_y(Rad(rotation.y)) * Matrix4::from_angle_x(Rad(rotation.x));
+	
+	// Rotations are orthonormal, the transpose undoes them
+	gaze.transpose() * head_to_eye * view
+}
 
 #[derive(Debug, Error)]
 pub enum RendererCreationError {
 	#[error(display = "No devices available.")] NoDevices,
 	#[error(display = "No compute queue available.")] NoQueue,
+	#[error(display = "No queue family matches {:?}.", _0)] NoQueueFamily(QueueFamilySelector),
+	#[error(display = "Queue family {} has {} queues, {} were requested.", _0, _1, _2)] TooManyQueues(u32, usize, usize),
+	#[error(display = "Queue priority {} is outside 0 to 1.", _0)] InvalidQueuePriority(f32),
+	#[error(display = "Device doesn't support any of the depth formats.")] NoDepthFormat,
+	#[error(display = "Device doesn't support {}x MSAA.", _0)] UnsupportedSampleCount(u32),
+	#[error(display = "Device doesn't support features: {}.", _0)] UnsupportedFeatures(String),
+	#[error(display = "Pipeline doesn't have layout set 0")] NoLayout,
 	#[error(display = "{}", _0)] LayersListError(#[error(source)] LayersListError),
 	#[error(display = "{}", _0)] InstanceCreationError(#[error(source)] InstanceCreationError),
+	#[error(display = "{}", _0)] DebugCallbackCreationError(#[error(source)] DebugCallbackCreationError),
 	#[error(display = "{}", _0)] DeviceCreationError(#[error(source)] DeviceCreationError),
 	#[error(display = "{}", _0)] OomError(#[error(source)] OomError),
+	#[error(display = "Failed to load shader: {}", _0)] ShaderLoadError(#[error(source, no_from)] OomError),
 	#[error(display = "{}", _0)] RenderPassCreationError(#[error(source)] RenderPassCreationError),
 	#[error(display = "{}", _0)] GraphicsPipelineCreationError(#[error(source)] GraphicsPipelineCreationError),
+	#[error(display = "{}", _0)] SamplerCreationError(#[error(source)] SamplerCreationError),
+	#[error(display = "{}", _0)] DeviceMemoryAllocError(#[error(source)] DeviceMemoryAllocError),
 	#[error(display = "{}", _0)] EyeCreationError(#[error(source)] EyeCreationError),
+	#[error(display = "{}", _0)] MirrorCreationError(#[error(source)] MirrorCreationError),
+	#[error(display = "{}", _0)] GpuTimerError(#[error(source)] GpuTimerError),
+	#[error(display = "{}", _0)] ShadowMapError(#[error(source)] ShadowMapError),
+	#[error(display = "{}", _0)] FxaaError(#[error(source)] FxaaError),
+	#[error(display = "{}", _0)] TonemapError(#[error(source)] TonemapError),
+	#[error(display = "{}", _0)] OcclusionError(#[error(source)] OcclusionError),
+	#[error(display = "{}", _0)] CullingError(#[error(source)] CullingError),
+	#[error(display = "{}", _0)] LinesError(#[error(source)] LinesError),
+	#[error(display = "{}", _0)] TextError(#[error(source)] TextError),
 }
 
+#[derive(Debug, Error)]
+#[error(display = "Device doesn't support wireframe rendering.")]
+pub struct WireframeUnsupportedError;
+
+#[cfg(feature = "indirect-draw")]
+#[derive(Debug, Error)]
+#[error(display = "Device doesn't support indirect draws.")]
+pub struct IndirectDrawUnsupportedError;
+
+#[derive(Debug, Error)]
+#[error(display = "Renderer was built without FXAA.")]
+pub struct FxaaUnavailableError;
+
 #[derive(Debug, Error)]
 pub enum RenderError {
+	#[error(display = "Pipeline doesn't have layout set 1")] NoLayout,
+	#[error(display = "Renderer doesn't have a compositor to get poses from")] NoCompositor,
+	#[error(display = "GPU device was lost, the renderer has to be recreated")] DeviceLost,
 	#[error(display = "{}", _0)] OomError(#[error(source)] OomError),
 	#[error(display = "{}", _0)] BeginRenderPassError(#[error(source)] BeginRenderPassError),
 	#[error(display = "{}", _0)] DrawIndexedError(#[error(source)] DrawIndexedError),
+	#[error(display = "{}", _0)] DrawError(#[error(source)] DrawError),
+	#[error(display = "{}", _0)] ExecuteCommandsError(#[error(source)] ExecuteCommandsError),
+	#[error(display = "{}", _0)] SyncCommandBufferBuilderError(#[error(source)] SyncCommandBufferBuilderError),
 	#[error(display = "{}", _0)] AutoCommandBufferBuilderContextError(#[error(source)] AutoCommandBufferBuilderContextError),
 	#[error(display = "{}", _0)] BuildError(#[error(source)] BuildError),
 	#[error(display = "{}", _0)] CommandBufferExecError(#[error(source)] CommandBufferExecError),
 	#[error(display = "{}", _0)] CompositorError(#[error(source)] CompositorError),
 	#[error(display = "{}", _0)] FlushError(#[error(source)] FlushError),
+	#[error(display = "{}", _0)] MirrorError(#[error(source)] MirrorError),
+	#[error(display = "{}", _0)] GpuTimerError(#[error(source)] GpuTimerError),
+	#[error(display = "{}", _0)] OcclusionError(#[error(source)] OcclusionError),
+	#[error(display = "{}", _0)] CullingError(#[error(source)] CullingError),
+	#[error(display = "{}", _0)] DeviceMemoryAllocError(#[error(source)] DeviceMemoryAllocError),
+	#[error(display = "{}", _0)] WriteLockError(#[error(source)] WriteLockError),
+	#[error(display = "{}", _0)] PersistentDescriptorSetError(#[error(source)] PersistentDescriptorSetError),
+	#[error(display = "{}", _0)] PersistentDescriptorSetBuildError(#[error(source)] PersistentDescriptorSetBuildError),
+}
+
+#[cfg(test)]
+mod tests {
+	use cgmath::{Matrix4, Vector2, Vector3, Point3, Rad, Deg, Transform, InnerSpace, EuclideanSpace};
+	use super::eye_view;
+	
+	fn hmd_view() -> Matrix4<f32> {
+		let pose = Matrix4::from_translation(Vector3::new(0.3, 1.7, -0.2)) * Matrix4::from_angle_y(Rad(0.8)) * Matrix4::from_angle_x(Rad(-0.3));
+		pose.inverse_transform().unwrap()
+	}
+	
+	#[test]
+	fn zero_eye_rotation_is_standard_view() {
+		let head_to_eye = Matrix4::from_translation(Vector3::new(0.032, 0.0, -0.015));
+		let view = eye_view(&hmd_view(), &head_to_eye, Vector2::new(0.0, 0.0));
+		let standard = head_to_eye * hmd_view();
+		
+		for (a, b) in [view.x, view.y, view.z, view.w].iter().zip(&[standard.x, standard.y, standard.z, standard.w]) {
+			assert!((a - b).magnitude() < 1e-5, "{:?} != {:?}", view, standard);
+		}
+	}
+	
+	#[test]
+	fn eye_rotation_keeps_eye_position() {
+		let head_to_eye = Matrix4::from_translation(Vector3::new(-0.032, 0.0, -0.015));
+		let eye_position = |rotation| eye_view(&hmd_view(), &head_to_eye, rotation).inverse_transform().unwrap().transform_point(Point3::origin());
+		
+		let standard = eye_position(Vector2::new(0.0, 0.0));
+		let rotated = eye_position(Vector2::new(0.2, -0.4));
+		assert!((standard - rotated).magnitude() < 1e-5, "{:?} != {:?}", standard, rotated);
+	}
+	
+	#[test]
+	fn eye_rotation_turns_the_gaze() {
+		let identity = Matrix4::from_scale(1.0);
+		let view = eye_view(&identity, &identity, Vector2::new(0.0, Rad::from(Deg(90.0)).0));
+		
+		// Yawing left by a quarter turn puts what was on the left, -X, straight ahead, -Z
+		let ahead = view.transform_point(Point3::new(-1.0, 0.0, 0.0));
+		assert!((ahead.to_vec() - Vector3::new(0.0, 0.0, -1.0)).magnitude() < 1e-5, "{:?}", ahead);
+	}
 }
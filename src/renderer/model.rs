@@ -1,18 +1,32 @@
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::time::Duration;
+use std::path::Path;
+use std::collections::HashMap;
 use err_derive::Error;
-use image::{DynamicImage, GenericImageView};
-use vulkano::buffer::{ImmutableBuffer, BufferUsage};
-use vulkano::image::{ImmutableImage, Dimensions, ImageCreationError};
-use vulkano::sync::{GpuFuture, FlushError, FenceSignalFuture};
+use image::{DynamicImage, GenericImageView, ImageBuffer, RgbaImage, imageops};
+use image::imageops::FilterType;
+use vulkano::buffer::{ImmutableBuffer, CpuAccessibleBuffer, BufferUsage, BufferAccess};
+use vulkano::image::{ImmutableImage, Dimensions, ImageCreationError, ImageUsage, ImageLayout, ImageAccess, MipmapsCount};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBuffer, CopyBufferImageError, CopyImageError, BlitImageError, BuildError, CommandBufferExecError};
+use vulkano::device::Queue;
+use vulkano::sampler::Filter;
+use vulkano::OomError;
+use vulkano::sync::{self, GpuFuture, FlushError, FenceSignalFuture};
 use vulkano::format::Format;
 use vulkano::memory::DeviceMemoryAllocError;
-use vulkano::sampler::Sampler;
-use vulkano::descriptor::descriptor_set::{DescriptorSet, PersistentDescriptorSet, PersistentDescriptorSetError, PersistentDescriptorSetBuildError};
-use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::pipeline::input_assembly::IndexType;
 use arc_swap::ArcSwap;
+use log::{warn, error};
+use cgmath::{Matrix3, Matrix4, Point3, Vector3, Transform, InnerSpace, EuclideanSpace, SquareMatrix};
 
 use crate::renderer::Renderer;
+use crate::renderer::material_sets::MaterialSetError;
+use crate::renderer::stl::{self, StlError};
+use crate::renderer::ply::{self, PlyError};
+use crate::renderer::skin::{Skin, MAX_JOINTS};
+use crate::renderer::ktx2::{Ktx2, Ktx2Error};
 use obj::TexturedVertex;
 use openvr::render_models;
 
@@ -20,124 +34,1232 @@ use openvr::render_models;
 pub const SCENE_OBJ: &[u8] = include_bytes!("../../assets/scene.obj");
 pub const SCENE_PNG: &[u8] = include_bytes!("../../assets/scene.png");
 
+// Color textures are stored in sRGB like any image file, sampling decodes them so lighting happens in linear space
+const COLOR_FORMAT: Format = Format::R8G8B8A8Srgb;
+// Normal maps hold directions, decoding them as sRGB would bend every normal
+const DATA_FORMAT: Format = Format::R8G8B8A8Unorm;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
 #[derive(Clone)]
 pub struct Model {
+	// Unique to each upload, clones share it
+	id: u64,
 	pub vertices: Arc<ImmutableBuffer<[Vertex]>>,
-	pub indices: Arc<ImmutableBuffer<[u16]>>,
+	// Point clouds don't have any
+	pub indices: Option<Indices>,
 	pub image: Arc<ImmutableImage<Format>>,
+	pub normal_map: Arc<ImmutableImage<Format>>,
+	pub emissive_map: Arc<ImmutableImage<Format>>,
 	pub set: Arc<dyn DescriptorSet + Send + Sync>,
-	fence: ArcSwap<FenceCheck>,
+	color: [f32; 4],
+	emissive: [f32; 3],
+	transparent: bool,
+	visible: bool,
+	aabb: Aabb,
+	bounding_sphere: BoundingSphere,
+	// Shared by clones, so `LoadProgress` and every copy see the upload finish
+	fence: Arc<ArcSwap<FenceCheck>>,
+	skin: Option<Arc<Skin>>,
+	// Joint matrices uploaded for the next frame, empty without a skin
+	joints: Vec<Matrix4<f32>>,
+}
+
+// 16 bit whenever the mesh is small enough, halving the index bandwidth
+#[derive(Clone)]
+pub enum Indices {
+	U16(Arc<ImmutableBuffer<[u16]>>),
+	U32(Arc<ImmutableBuffer<[u32]>>),
+}
+
+impl Indices {
+	pub fn index_type(&self) -> IndexType {
+		match self {
+			Indices::U16(_) => IndexType::U16,
+			Indices::U32(_) => IndexType::U32,
+		}
+	}
+	
+	// In bytes
+	pub fn size(&self) -> usize {
+		match self {
+			Indices::U16(buffer) => buffer.size(),
+			Indices::U32(buffer) => buffer.size(),
+		}
+	}
 }
 
 impl Model {
-	pub fn new(vertices: &[Vertex], indices: &[u16], source_image: DynamicImage, renderer: &Renderer) -> Result<Model, ModelError> {
-		let width = source_image.width();
-		let height = source_image.height();
+	// Models without a texture are sampled from a single white pixel
+	pub fn new<I: Copy + Into<u32>>(vertices: &[Vertex], indices: &[I], source_image: Option<DynamicImage>, renderer: &Renderer) -> Result<Model, ModelError> {
+		Model::upload(vertices, Some(&to_u32(indices)), source_image.map(Texture::Image), None, None, renderer)
+	}
+	
+	// Tangent space normal map, used where the vertices have tangents. Missing ones are generated from the texture coordinates.
+	pub fn with_normal_map<I: Copy + Into<u32>>(vertices: &[Vertex], indices: &[I], source_image: Option<DynamicImage>, normal_map: DynamicImage, renderer: &Renderer) -> Result<Model, ModelError> {
+		Model::upload(vertices, Some(&to_u32(indices)), source_image.map(Texture::Image), Some(normal_map), None, renderer)
+	}
+	
+	// Any of the maps can be left out. The emissive map is multiplied with `set_emissive`, which starts out black.
+	pub fn with_maps<I: Copy + Into<u32>>(vertices: &[Vertex], indices: &[I], source_image: Option<DynamicImage>, normal_map: Option<DynamicImage>, emissive_map: Option<DynamicImage>, renderer: &Renderer) -> Result<Model, ModelError> {
+		Model::upload(vertices, Some(&to_u32(indices)), source_image.map(Texture::Image), normal_map, emissive_map, renderer)
+	}
+	
+	// Color texture from a KTX2 file, uploaded as it is along with its mip levels where the device supports BC formats
+	pub fn with_ktx2<I: Copy + Into<u32>>(vertices: &[Vertex], indices: &[I], texture: Ktx2, renderer: &Renderer) -> Result<Model, ModelError> {
+		Model::upload(vertices, Some(&to_u32(indices)), Some(Texture::Ktx2(texture)), None, None, renderer)
+	}
+	
+	// Every vertex is drawn as a single pixel, colored by its vertex color. No index buffer means no 16 bit limit either.
+	pub fn points(vertices: &[Vertex], renderer: &Renderer) -> Result<Model, ModelError> {
+		Model::upload(vertices, None, None, None, None, renderer)
+	}
+	
+	fn upload(vertices: &[Vertex], indices: Option<&[u32]>, texture: Option<Texture>, normal_map: Option<DynamicImage>, emissive_map: Option<DynamicImage>, renderer: &Renderer)
+	         -> Result<Model, ModelError> {
+		let texture = texture.unwrap_or_else(|| Texture::Image(white_image()));
+		let normal_map = normal_map.unwrap_or_else(flat_normal_image);
+		let emissive_map = emissive_map.unwrap_or_else(white_image);
+		
+		let mut vertices = vertices.to_vec();
+		if let Some(indices) = indices {
+			if vertices.iter().all(|vertex| vertex.tangent == [0.0; 4]) {
+				generate_tangents(&mut vertices, indices);
+			}
+		}
+		
+		let vertices = &vertices;
+		let aabb = Aabb::from_vertices(vertices);
+		let bounding_sphere = BoundingSphere::from_vertices(vertices, &aabb);
+		// Uploads run on their own queue, so loading doesn't hold up the frames in flight on the graphics queue.
+		// vulkano 0.18 can't record queue family ownership transfers, resources are shared concurrently by all
+		// families the device was created with instead. Models aren't drawn before `loaded` sees the upload's fence
+		// signaled, which is all the synchronization the render frame needs.
 		let queue = &renderer.load_queue;
 		
 		let (vertices, vertices_promise) = ImmutableBuffer::from_iter(vertices.iter().cloned(),
 		                                                              BufferUsage{ vertex_buffer: true, ..BufferUsage::none() },
 		                                                              queue.clone())?;
 		
-		let (indices, indices_promise) = ImmutableBuffer::from_iter(indices.iter().cloned(),
-		                                                            BufferUsage{ index_buffer: true, ..BufferUsage::none() },
-		                                                            queue.clone())?;
-		
-		let (image, image_promise) = ImmutableImage::from_iter(source_image.to_rgba().into_vec().into_iter(),
-		                                                       Dimensions::Dim2d{ width, height },
-		                                                       Format::R8G8B8A8Unorm,
-		                                                       queue.clone())?;
+		let (indices, indices_promise): (_, Box<dyn GpuFuture + Send + Sync>) = match indices {
+			// Checking the largest index is enough, u16 can hold any smaller one
+			Some(indices) if indices.iter().all(|&index| index <= u16::MAX as u32) => {
+				let (indices, promise) = ImmutableBuffer::from_iter(indices.iter().map(|&index| index as u16),
+				                                                    BufferUsage{ index_buffer: true, ..BufferUsage::none() },
+				                                                    queue.clone())?;
+				(Some(Indices::U16(indices)), Box::new(promise))
+			},
+			Some(indices) => {
+				let (indices, promise) = ImmutableBuffer::from_iter(indices.iter().cloned(),
+				                                                    BufferUsage{ index_buffer: true, ..BufferUsage::none() },
+				                                                    queue.clone())?;
+				(Some(Indices::U32(indices)), Box::new(promise))
+			},
+			None => (None, Box::new(sync::now(queue.device().clone()))),
+		};
 		
-		let sampler = Sampler::simple_repeat_linear_no_mipmap(queue.device().clone());
+		let (image, image_promise) = match &texture {
+			Texture::Image(source_image) => upload_texture(source_image, COLOR_FORMAT, queue)?,
+			Texture::Ktx2(texture) => upload_ktx2(texture, queue)?,
+		};
+		let (normal_map, normal_map_promise) = upload_texture(&normal_map, DATA_FORMAT, queue)?;
+		// A color like the texture, so it's decoded from sRGB as well
+		let (emissive_map, emissive_map_promise) = upload_texture(&emissive_map, COLOR_FORMAT, queue)?;
 		
-		let set = Arc::new(
-			PersistentDescriptorSet::start(renderer.pipeline.descriptor_set_layout(0).ok_or(ModelError::NoLayout)?.clone())
-			                        .add_sampled_image(image.clone(), sampler.clone())?
-			                        .build()?
-		);
+		let set = renderer.material_sets.alloc(image.clone(), normal_map.clone(), emissive_map.clone(), renderer.sampler.clone())?;
 		
-		let fence = ArcSwap::new(Arc::new(FenceCheck::new(vertices_promise.join(indices_promise).join(image_promise))?));
+		let fence = Arc::new(ArcSwap::new(Arc::new(FenceCheck::new(vertices_promise.join(indices_promise).join(image_promise).join(normal_map_promise).join(emissive_map_promise))?)));
 		
 		Ok(Model {
+			id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
 			vertices,
 			indices,
 			image,
+			normal_map,
+			emissive_map,
 			set,
+			color: [1.0, 1.0, 1.0, 1.0],
+			emissive: [0.0, 0.0, 0.0],
+			transparent: false,
+			visible: true,
+			aabb,
+			bounding_sphere,
 			fence,
+			skin: None,
+			joints: Vec::new(),
 		})
 	}
 	
-	pub fn loaded(&self) -> bool {
-		match &**self.fence.load() {
-			FenceCheck::Done(result) => *result,
-			FenceCheck::Pending(fence) => {
-				match fence.wait(Some(Duration::new(0, 0))) {
-					Err(FlushError::Timeout) => false,
-					Ok(()) => {
-						self.fence.swap(Arc::new(FenceCheck::Done(true)));
-						true
+	// Render models without a diffuse texture are left white
+	pub fn from_render_model(model: &render_models::Model, texture: Option<&render_models::Texture>, renderer: &Renderer) -> Result<Model, ModelError> {
+		let vertices: Vec<Vertex> = model.vertices().iter().map(Into::into).collect();
+		
+		let image = match texture {
+			Some(texture) => {
+				let (width, height) = texture.dimensions();
+				let image = ImageBuffer::from_raw(width as u32, height as u32, texture.data().into()).ok_or(ModelError::UnsupportedImageFormat)?;
+				Some(DynamicImage::ImageRgba8(image))
+			},
+			None => None,
+		};
+		
+		Model::new(&vertices, model.indices(), image, renderer)
+	}
+	
+	// Every model in the file paired with its world transformation, picking the loader by the file's extension. glTF
+	// node hierarchies are flattened and each primitive keeps its material. OBJ, STL and PLY have no transformations,
+	// their models sit at the origin and are read as Y up and right-handed, their own loaders take other axes.
+	pub fn load_scene<P: AsRef<Path>>(path: P, renderer: &Renderer) -> Result<Vec<(Model, Matrix4<f32>)>, ModelError> {
+		let path = path.as_ref();
+		let extension = path.extension().map_or_else(String::new, |extension| extension.to_string_lossy().to_lowercase());
+		let at_origin = |models: Vec<Model>| -> Vec<(Model, Matrix4<f32>)> { models.into_iter().map(|model| (model, Matrix4::identity())).collect() };
+		
+		match extension.as_str() {
+			"gltf" | "glb" => Model::load_gltf(path, renderer),
+			"obj" => Ok(at_origin(Model::load_obj(path, Axes::default(), renderer)?)),
+			"stl" => Ok(at_origin(vec![Model::load_stl(path, false, Axes::default(), renderer)?])),
+			"ply" => Ok(at_origin(vec![Model::load_ply(path, Axes::default(), renderer)?])),
+			_ => Err(ModelError::UnsupportedFormat(path.display().to_string())),
+		}
+	}
+	
+	// Loads every mesh primitive of the default scene as a separate model, paired with its node's transformation.
+	// Skinned meshes start out in their rest pose, see `update_animation`.
+	pub fn load_gltf<P: AsRef<Path>>(path: P, renderer: &Renderer) -> Result<Vec<(Model, Matrix4<f32>)>, ModelError> {
+		Model::load_gltf_cancellable(path, renderer, &CancelToken::new())
+	}
+	
+	// Gives up before the next primitive once `cancel` is cancelled, see `CancelToken`
+	pub fn load_gltf_cancellable<P: AsRef<Path>>(path: P, renderer: &Renderer, cancel: &CancelToken) -> Result<Vec<(Model, Matrix4<f32>)>, ModelError> {
+		let (document, buffers, images) = gltf::import(path)?;
+		Model::gltf_models(&document, &buffers, |index| gltf_image(&images[index]), renderer, cancel)
+	}
+	
+	// Parses a .glb or .gltf file in memory. Buffers and images it refers to by URI are asked of `resolve`, e.g. looked up
+	// among embedded assets or fetched over the network. Data URIs are decoded without it.
+	pub fn load_gltf_from_slice<R>(data: &[u8], resolve: R, renderer: &Renderer) -> Result<Vec<(Model, Matrix4<f32>)>, ModelError>
+	                              where R: Fn(&str) -> Option<Vec<u8>> {
+		let gltf::Gltf { document, mut blob } = gltf::Gltf::from_slice(data)?;
+		let read = |uri: &str| read_uri(uri, &resolve);
+		
+		let mut buffers = Vec::new();
+		for buffer in document.buffers() {
+			let data = match buffer.source() {
+				gltf::buffer::Source::Bin => blob.take().ok_or_else(|| ModelError::MissingResource("GLB binary chunk".to_string()))?,
+				gltf::buffer::Source::Uri(uri) => read(uri)?,
+			};
+			
+			if data.len() < buffer.length() {
+				return Err(gltf::Error::BufferLength { buffer: buffer.index(), expected: buffer.length(), actual: data.len() }.into());
+			}
+			
+			buffers.push(gltf::buffer::Data(data));
+		}
+		
+		// The format is guessed from the contents, the MIME types are optional anyway
+		let mut images = Vec::new();
+		for image in document.images() {
+			images.push(match image.source() {
+				gltf::image::Source::View { view, .. } => image::load_from_memory(view_slice(&buffers, &view).ok_or(ModelError::ViewOutOfRange(view.index()))?)?,
+				gltf::image::Source::Uri { uri, .. } => image::load_from_memory(&read(uri)?)?,
+			});
+		}
+		
+		Model::gltf_models(&document, &buffers, |index| Ok(images[index].clone()), renderer, &CancelToken::new())
+	}
+	
+	// Shared by the glTF loaders, `load_image` decodes the image with the given index
+	fn gltf_models<I>(document: &gltf::Document, buffers: &[gltf::buffer::Data], load_image: I, renderer: &Renderer, cancel: &CancelToken) -> Result<Vec<(Model, Matrix4<f32>)>, ModelError>
+	                 where I: Fn(usize) -> Result<DynamicImage, ModelError> {
+		check_accessors(document, buffers)?;
+		
+		let mut models = Vec::new();
+		
+		let scene = document.default_scene()
+		                    .or_else(|| document.scenes().next())
+		                    .ok_or(ModelError::NoScene)?;
+		
+		let mut nodes: Vec<_> = scene.nodes().map(|node| (node, Matrix4::from_scale(1.0))).collect();
+		
+		while let Some((node, parent)) = nodes.pop() {
+			let transform = parent * Matrix4::from(node.transform().matrix());
+			
+			if let Some(mesh) = node.mesh() {
+				let skin = match node.skin() {
+					Some(skin) => Some(Arc::new(Skin::load(document, buffers, &node, &skin)?)),
+					None => None,
+				};
+				
+				for primitive in mesh.primitives() {
+					cancel.check()?;
+					
+					let reader = primitive.reader(|buffer| Some(&*buffers[buffer.index()]));
+					let material = primitive.material().pbr_metallic_roughness();
+					let texture = material.base_color_texture();
+					let tex_coord = texture.as_ref().map_or(0, |info| info.tex_coord());
+					
+					let positions: Vec<[f32; 3]> = reader.read_positions().ok_or(ModelError::NoPositions)?.collect();
+					let normals: Vec<[f32; 3]> = reader.read_normals().map_or_else(Vec::new, Iterator::collect);
+					let uvs: Vec<[f32; 2]> = reader.read_tex_coords(tex_coord).map_or_else(Vec::new, |uvs| uvs.into_f32().collect());
+					let colors: Vec<[f32; 4]> = reader.read_colors(0).map_or_else(Vec::new, |colors| colors.into_rgba_f32().collect());
+					let tangents: Vec<[f32; 4]> = reader.read_tangents().map_or_else(Vec::new, Iterator::collect);
+					let joints: Vec<[u16; 4]> = reader.read_joints(0).map_or_else(Vec::new, |joints| joints.into_u16().collect());
+					let weights: Vec<[f32; 4]> = reader.read_weights(0).map_or_else(Vec::new, |weights| weights.into_f32().collect());
+					
+					// The shader reads every joint, even those with a weight of zero
+					if let Some(&joint) = joints.iter().flatten().find(|&&joint| joint as usize >= MAX_JOINTS) {
+						return Err(ModelError::JointOutOfRange(joint));
 					}
-					Err(err) => {
-						eprintln!("Error while loading model: {:?}", err);
-						self.fence.swap(Arc::new(FenceCheck::Done(false)));
-						false
+					
+					let vertices: Vec<Vertex> = positions.iter()
+					                                     .enumerate()
+					                                     .map(|(i, &pos)| Vertex::new(pos,
+					                                                                  normals.get(i).cloned().unwrap_or_default(),
+					                                                                  uvs.get(i).cloned().unwrap_or_default())
+					                                                      .with_color(colors.get(i).cloned().unwrap_or([1.0, 1.0, 1.0, 1.0]))
+					                                                      .with_tangent(tangents.get(i).cloned().unwrap_or_default())
+					                                                      .with_skin(joints.get(i).map_or([0; 4], |joints| [joints[0] as u32, joints[1] as u32, joints[2] as u32, joints[3] as u32]),
+					                                                                 weights.get(i).cloned().unwrap_or_default()))
+					                                     .collect();
+					
+					let indices: Vec<u32> = match reader.read_indices() {
+						Some(indices) => indices.into_u32().collect(),
+						None => (0 .. vertices.len() as u32).collect(),
+					};
+					
+					if let Some(&index) = indices.iter().find(|&&index| index as usize >= vertices.len()) {
+						return Err(ModelError::IndexOutOfRange(index));
 					}
+					
+					let image = match texture {
+						Some(info) => Some(load_image(info.texture().source().index())?),
+						None => None,
+					};
+					
+					// Sampled with the base color's texture coordinates, a separate set for the other maps is rare
+					let normal_map = match primitive.material().normal_texture() {
+						Some(normal) => Some(load_image(normal.texture().source().index())?),
+						None => None,
+					};
+					
+					let emissive_map = match primitive.material().emissive_texture() {
+						Some(emissive) => Some(load_image(emissive.texture().source().index())?),
+						None => None,
+					};
+					
+					let mut model = Model::with_maps(&vertices, &indices, image, normal_map, emissive_map, renderer)?;
+					model.set_color(material.base_color_factor());
+					model.set_emissive(primitive.material().emissive_factor());
+					
+					if let Some(skin) = &skin {
+						model.joints = skin.rest_palette();
+						model.skin = Some(skin.clone());
+					}
+					
+					models.push((model, transform));
 				}
 			}
+			
+			nodes.extend(node.children().map(|child| (child, transform)));
 		}
+		
+		Ok(models)
+	}
+	
+	// tobj splits faces using different materials into separate meshes, each becomes its own model
+	pub fn load_obj<P: AsRef<Path>>(path: P, axes: Axes, renderer: &Renderer) -> Result<Vec<Model>, ModelError> {
+		Model::load_obj_cancellable(path, axes, renderer, &CancelToken::new())
+	}
+	
+	// Gives up before the next mesh once `cancel` is cancelled, see `CancelToken`
+	pub fn load_obj_cancellable<P: AsRef<Path>>(path: P, axes: Axes, renderer: &Renderer, cancel: &CancelToken) -> Result<Vec<Model>, ModelError> {
+		let path = path.as_ref();
+		let directory = path.parent().unwrap_or_else(|| Path::new(""));
+		
+		let (meshes, materials) = tobj::load_obj(path, &obj_load_options())?;
+		
+		let materials = materials.unwrap_or_else(|err| {
+			warn!("Failed to load materials for {}: {}", path.display(), err);
+			Vec::new()
+		});
+		
+		Model::obj_models(meshes, &materials, axes, |texture| Ok(image::open(directory.join(texture))?), renderer, cancel)
+	}
+	
+	// Material libraries and textures are asked of `resolve` by the paths the file refers to them with
+	pub fn load_obj_from_slice<R>(data: &[u8], axes: Axes, resolve: R, renderer: &Renderer) -> Result<Vec<Model>, ModelError>
+	                             where R: Fn(&str) -> Option<Vec<u8>> {
+		let (meshes, materials) = tobj::load_obj_buf(&mut &*data, &obj_load_options(), |path| {
+			                          let data = resolve(&path.to_string_lossy()).ok_or(tobj::LoadError::OpenFileFailed)?;
+			                          tobj::load_mtl_buf(&mut &*data)
+		                          })?;
+		
+		let materials = materials.unwrap_or_else(|err| {
+			warn!("Failed to load materials: {}", err);
+			Vec::new()
+		});
+		
+		Model::obj_models(meshes, &materials, axes, |texture| Ok(image::load_from_memory(&read_uri(texture, &resolve)?)?), renderer, &CancelToken::new())
 	}
+	
+	// Shared by the OBJ loaders, `load_image` loads a texture by the path the material refers to it with
+	fn obj_models<I>(meshes: Vec<tobj::Model>, materials: &[tobj::Material], axes: Axes, load_image: I, renderer: &Renderer, cancel: &CancelToken) -> Result<Vec<Model>, ModelError>
+	                where I: Fn(&str) -> Result<DynamicImage, ModelError> {
+		let mut models = Vec::new();
+		
+		for tobj::Model { mut mesh, .. } in meshes {
+			cancel.check()?;
+			
+			let mut vertices = Vec::with_capacity(mesh.positions.len() / 3);
+			
+			for i in 0..mesh.positions.len() / 3 {
+				let normal = mesh.normals.get(i * 3 .. i * 3 + 3).unwrap_or(&[0.0, 0.0, 0.0]);
+				let uv = mesh.texcoords.get(i * 2 .. i * 2 + 2).unwrap_or(&[0.0, 1.0]);
+				// Written after the position by some exporters, without alpha
+				let color = mesh.vertex_color.get(i * 3 .. i * 3 + 3).unwrap_or(&[1.0, 1.0, 1.0]);
+				
+				vertices.push(Vertex::new([mesh.positions[i * 3], mesh.positions[i * 3 + 1], mesh.positions[i * 3 + 2]],
+				                          [normal[0], normal[1], normal[2]],
+				                          [uv[0], 1.0 - uv[1]])
+				                    .with_color([color[0], color[1], color[2], 1.0]));
+			}
+			
+			axes.apply(&mut vertices, &mut mesh.indices);
+			
+			let material = mesh.material_id.and_then(|id| materials.get(id));
+			
+			let image = match material {
+				Some(material) if !material.diffuse_texture.is_empty() => Some(load_image(&material.diffuse_texture)?),
+				_ => None,
+			};
+			
+			let mut model = Model::new(&vertices, &mesh.indices, image, renderer)?;
+			
+			if let Some(material) = material {
+				model.set_color([material.diffuse[0], material.diffuse[1], material.diffuse[2], material.dissolve]);
+			}
+			
+			models.push(model);
+		}
+		
+		Ok(models)
+	}
+	
+	// STL has neither texture coordinates nor materials, so the model is plain white.
+	// Triangles get their face normal, `smooth` averages the normals of all triangles sharing a vertex instead.
+	pub fn load_stl<P: AsRef<Path>>(path: P, smooth: bool, axes: Axes, renderer: &Renderer) -> Result<Model, ModelError> {
+		Model::from_triangles(&stl::load(path)?, smooth, axes, renderer)
+	}
+	
+	pub fn load_stl_from_slice(data: &[u8], smooth: bool, axes: Axes, renderer: &Renderer) -> Result<Model, ModelError> {
+		Model::from_triangles(&stl::parse(data)?, smooth, axes, renderer)
+	}
+	
+	fn from_triangles(triangles: &[stl::Triangle], smooth: bool, axes: Axes, renderer: &Renderer) -> Result<Model, ModelError> {
+		let (mut vertices, mut indices) = index_triangles(triangles, smooth);
+		axes.apply(&mut vertices, &mut indices);
+		
+		Model::new(&vertices, &indices, None, renderer)
+	}
+	
+	// Files without faces are point clouds. PLY has no materials, vertex colors are all there is.
+	// The vertex buffer is uploaded on the load queue like any other, the file is parsed on the calling thread though.
+	pub fn load_ply<P: AsRef<Path>>(path: P, axes: Axes, renderer: &Renderer) -> Result<Model, ModelError> {
+		Model::from_ply_mesh(ply::load(path)?, axes, renderer)
+	}
+	
+	pub fn load_ply_from_slice(data: &[u8], axes: Axes, renderer: &Renderer) -> Result<Model, ModelError> {
+		Model::from_ply_mesh(ply::parse(data)?, axes, renderer)
+	}
+	
+	fn from_ply_mesh(mut mesh: ply::Mesh, axes: Axes, renderer: &Renderer) -> Result<Model, ModelError> {
+		axes.apply(&mut mesh.vertices, &mut mesh.indices);
+		
+		if mesh.indices.is_empty() {
+			return Model::points(&mesh.vertices, renderer);
+		}
+		
+		Model::new(&mesh.vertices, &mesh.indices, None, renderer)
+	}
+	
+	// Poses the joints of a skinned glTF model `time` seconds into the file's first animation, looping over its
+	// length. Linear blend skinning, the bounds stay those of the rest pose. Does nothing for models without joints.
+	pub fn update_animation(&mut self, time: f32) {
+		if let Some(skin) = &self.skin {
+			self.joints = skin.palette(time);
+		}
+	}
+	
+	// Relative to the model matrix, `None` for models without a skin
+	// Tells models apart from whatever takes their place in the scene, unlike Vulkan handles ids are never reused
+	pub fn id(&self) -> u64 {
+		self.id
+	}
+	
+	pub fn joints(&self) -> Option<&[Matrix4<f32>]> {
+		if self.skin.is_some() { Some(&self.joints) } else { None }
+	}
+	
+	// `None` for point clouds
+	pub fn index_type(&self) -> Option<IndexType> {
+		self.indices.as_ref().map(Indices::index_type)
+	}
+	
+	// Multiplied with the texture, white leaves it unchanged. Linear, like every color that isn't an image.
+	pub fn set_color(&mut self, color: [f32; 4]) {
+		self.color = color;
+	}
+	
+	pub fn color(&self) -> [f32; 4] {
+		self.color
+	}
+	
+	// Light the model gives off regardless of the lighting, added after shading and multiplied with the emissive map.
+	// Linear and not limited to 1, with `RendererBuilder::hdr` brighter colors glow harder.
+	pub fn set_emissive(&mut self, emissive: [f32; 3]) {
+		self.emissive = emissive;
+	}
+	
+	pub fn emissive(&self) -> [f32; 3] {
+		self.emissive
+	}
+	
+	// Transparent models are alpha blended without writing depth, after all opaque ones
+	pub fn set_transparent(&mut self, transparent: bool) {
+		self.transparent = transparent;
+	}
+	
+	pub fn transparent(&self) -> bool {
+		self.transparent
+	}
+	
+	// Hidden models stay in the scene but aren't drawn
+	pub fn set_visible(&mut self, visible: bool) {
+		self.visible = visible;
+	}
+	
+	pub fn visible(&self) -> bool {
+		self.visible
+	}
+	
+	// Bounds are in model space and only available once the model has loaded
+	pub fn aabb(&self) -> Option<Aabb> {
+		if self.loaded() { Some(self.aabb) } else { None }
+	}
+	
+	pub fn bounding_sphere(&self) -> Option<BoundingSphere> {
+		if self.loaded() { Some(self.bounding_sphere) } else { None }
+	}
+	
+	// Centers the model on the origin and scales it so its largest side is `size` long, when put in front of the placement:
+	// `scene.set_transform(handle, placement * model.fit(1.0))`. Bounds are known before the upload finishes.
+	pub fn fit(&self, size: f32) -> Matrix4<f32> {
+		self.aabb.fit(size)
+	}
+	
+	pub fn loaded(&self) -> bool {
+		poll_fence(&self.fence) == Some(true)
+	}
+}
+
+// GPU uploads of a batch of models, e.g. everything one `load_gltf` returned, for loading screens. Files are parsed
+// before the models exist, this only covers the part running on the load queue. Keeps unfinished uploads alive, drop
+// it along with the models when abandoning a load.
+pub struct LoadProgress {
+	pending: Vec<Arc<ArcSwap<FenceCheck>>>,
+	total: usize,
+	failed: bool,
+	on_complete: Option<Box<dyn FnOnce(bool)>>,
 }
 
+impl LoadProgress {
+	pub fn new<'a, I: IntoIterator<Item = &'a Model>>(models: I) -> LoadProgress {
+		let pending: Vec<_> = models.into_iter().map(|model| model.fence.clone()).collect();
+		
+		LoadProgress {
+			total: pending.len(),
+			pending,
+			failed: false,
+			on_complete: None,
+		}
+	}
+	
+	// Called by `poll` once all uploads finished, with whether every one of them succeeded
+	pub fn on_complete<F: FnOnce(bool) + 'static>(mut self, callback: F) -> LoadProgress {
+		self.on_complete = Some(Box::new(callback));
+		self
+	}
+	
+	// Checks the uploads without blocking and returns the fraction that finished, failed ones included.
+	// Meant to be called every frame, e.g. from the update callback.
+	pub fn poll(&mut self) -> f32 {
+		let mut failed = false;
+		self.pending.retain(|fence| match poll_fence(fence) {
+			Some(loaded) => {
+				failed |= !loaded;
+				false
+			},
+			None => true,
+		});
+		self.failed |= failed;
+		
+		if self.pending.is_empty() {
+			if let Some(on_complete) = self.on_complete.take() {
+				on_complete(!self.failed);
+			}
+		}
+		
+		self.progress()
+	}
+	
+	// As of the last `poll`
+	pub fn progress(&self) -> f32 {
+		if self.total == 0 {
+			1.0
+		} else {
+			(self.total - self.pending.len()) as f32 / self.total as f32
+		}
+	}
+	
+	pub fn finished(&self) -> bool {
+		self.pending.is_empty()
+	}
+	
+	// Failed models never count as loaded and are skipped when drawing, the error is logged
+	pub fn failed(&self) -> bool {
+		self.failed
+	}
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UpAxis {
+	Y,
+	Z,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Handedness {
+	Right,
+	Left,
+}
+
+// Coordinate system a file was authored in, for the formats that don't pin it down. glTF is always Y up and right-handed.
+// Imports are converted to world space, which is OpenVR's: Y up, right-handed, -Z forward. That happens before any
+// view or projection, CLIP only converts OpenGL clip space to Vulkan's afterwards and doesn't care about model axes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Axes {
+	pub up: UpAxis,
+	pub handedness: Handedness,
+}
+
+impl Default for Axes {
+	fn default() -> Axes {
+		Axes { up: UpAxis::Y, handedness: Handedness::Right }
+	}
+}
+
+impl Axes {
+	// Blender, 3ds Max and most CAD tools
+	pub const Z_UP: Axes = Axes { up: UpAxis::Z, handedness: Handedness::Right };
+	
+	// Left-handed sources are mirrored along their depth axis first. Z up is rotated the way Blender's glTF exporter
+	// does it, so a model's -Y front ends up facing +Z.
+	pub fn matrix(&self) -> Matrix3<f32> {
+		let mirror = match (self.handedness, self.up) {
+			(Handedness::Right, _) => Matrix3::identity(),
+			(Handedness::Left, UpAxis::Y) => Matrix3::from_cols(Vector3::unit_x(), Vector3::unit_y(), -Vector3::unit_z()),
+			(Handedness::Left, UpAxis::Z) => Matrix3::from_cols(Vector3::unit_x(), -Vector3::unit_y(), Vector3::unit_z()),
+		};
+		
+		let rotate = match self.up {
+			UpAxis::Y => Matrix3::identity(),
+			UpAxis::Z => Matrix3::from_cols(Vector3::unit_x(), -Vector3::unit_z(), Vector3::unit_y()),
+		};
+		
+		rotate * mirror
+	}
+	
+	// The matrix is orthonormal, so normals and tangents take it as is. Mirroring reverses the winding and the bitangents.
+	fn apply(&self, vertices: &mut [Vertex], indices: &mut [u32]) {
+		if *self == Axes::default() {
+			return;
+		}
+		
+		let matrix = self.matrix();
+		let mirrored = self.handedness == Handedness::Left;
+		
+		for vertex in vertices {
+			vertex.pos = (matrix * Vector3::from(vertex.pos)).into();
+			vertex.normal = (matrix * Vector3::from(vertex.normal)).into();
+			
+			let [x, y, z, w] = vertex.tangent;
+			let tangent = matrix * Vector3::new(x, y, z);
+			vertex.tangent = [tangent.x, tangent.y, tangent.z, if mirrored { -w } else { w }];
+		}
+		
+		if mirrored {
+			for triangle in indices.chunks_exact_mut(3) {
+				triangle.swap(1, 2);
+			}
+		}
+	}
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Aabb {
+	pub min: Point3<f32>,
+	pub max: Point3<f32>,
+}
+
+impl Aabb {
+	fn from_vertices(vertices: &[Vertex]) -> Aabb {
+		if vertices.is_empty() {
+			return Aabb { min: Point3::origin(), max: Point3::origin() };
+		}
+		
+		let mut min = Point3::from(vertices[0].pos);
+		let mut max = min;
+		
+		for vertex in vertices {
+			min = Point3::new(min.x.min(vertex.pos[0]), min.y.min(vertex.pos[1]), min.z.min(vertex.pos[2]));
+			max = Point3::new(max.x.max(vertex.pos[0]), max.y.max(vertex.pos[1]), max.z.max(vertex.pos[2]));
+		}
+		
+		Aabb { min, max }
+	}
+	
+	pub fn center(&self) -> Point3<f32> {
+		self.min.midpoint(self.max)
+	}
+	
+	pub fn size(&self) -> Vector3<f32> {
+		self.max - self.min
+	}
+	
+	// Bit 0 of the index picks the max x, bit 1 the max y and bit 2 the max z
+	pub fn corners(&self) -> [Point3<f32>; 8] {
+		let corner = |index: usize| Point3::new(if index & 1 == 0 { self.min.x } else { self.max.x },
+		                                        if index & 2 == 0 { self.min.y } else { self.max.y },
+		                                        if index & 4 == 0 { self.min.z } else { self.max.z });
+		
+		[corner(0), corner(1), corner(2), corner(3), corner(4), corner(5), corner(6), corner(7)]
+	}
+	
+	// Multiple of `direction` at which the ray enters the box, zero when it starts inside. `None` if it misses the box or
+	// the box is behind it.
+	pub fn intersect_ray(&self, origin: Point3<f32>, direction: Vector3<f32>) -> Option<f32> {
+		let mut near = 0.0f32;
+		let mut far = f32::INFINITY;
+		
+		// Slabs between the min and max planes of each axis, the ray is inside the box where it's inside all three
+		for axis in 0 .. 3 {
+			let inverse = 1.0 / direction[axis];
+			let a = (self.min[axis] - origin[axis]) * inverse;
+			let b = (self.max[axis] - origin[axis]) * inverse;
+			near = near.max(a.min(b));
+			far = far.min(a.max(b));
+		}
+		
+		if near <= far { Some(near) } else { None }
+	}
+	
+	// Encloses both, for fitting models that were loaded in several parts as a whole
+	pub fn union(&self, other: &Aabb) -> Aabb {
+		Aabb {
+			min: Point3::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y), self.min.z.min(other.min.z)),
+			max: Point3::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y), self.max.z.max(other.max.z)),
+		}
+	}
+	
+	// Uniform scale, so proportions are kept. Empty boxes are only moved.
+	pub fn fit(&self, size: f32) -> Matrix4<f32> {
+		let extent = self.size();
+		let largest = extent.x.max(extent.y).max(extent.z);
+		let scale = if largest > 0.0 { size / largest } else { 1.0 };
+		
+		Matrix4::from_scale(scale) * Matrix4::from_translation(Point3::origin() - self.center())
+	}
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct BoundingSphere {
+	pub center: Point3<f32>,
+	pub radius: f32,
+}
+
+impl BoundingSphere {
+	// Centered on the bounding box, not the tightest fit but cheap
+	fn from_vertices(vertices: &[Vertex], aabb: &Aabb) -> BoundingSphere {
+		let center = aabb.center();
+		let radius = vertices.iter()
+		                     .map(|vertex| (Point3::from(vertex.pos) - center).magnitude())
+		                     .fold(0.0, f32::max);
+		
+		BoundingSphere { center, radius }
+	}
+	
+	// Encloses the sphere after `matrix` is applied, as long as it has no shear
+	pub fn transform(&self, matrix: &Matrix4<f32>) -> BoundingSphere {
+		let scale = matrix.x.truncate().magnitude()
+		                    .max(matrix.y.truncate().magnitude())
+		                    .max(matrix.z.truncate().magnitude());
+		
+		BoundingSphere {
+			center: matrix.transform_point(self.center),
+			radius: self.radius * scale,
+		}
+	}
+}
+
+// Color texture of a model
+enum Texture {
+	Image(DynamicImage),
+	Ktx2(Ktx2),
+}
+
+// Send and Sync, so models can be drawn from the threads recording the eyes
 enum FenceCheck {
 	Done(bool),
-	Pending(FenceSignalFuture<Box<dyn GpuFuture>>)
+	Pending(FenceSignalFuture<Box<dyn GpuFuture + Send + Sync>>)
 }
 
 impl FenceCheck {
 	fn new<GF>(future: GF)
 	          -> Result<FenceCheck, FlushError>
-	          where GF: GpuFuture + 'static {
-		Ok(FenceCheck::Pending((Box::new(future) as Box<dyn GpuFuture>).then_signal_fence_and_flush()?))
+	          where GF: GpuFuture + Send + Sync + 'static {
+		Ok(FenceCheck::Pending((Box::new(future) as Box<dyn GpuFuture + Send + Sync>).then_signal_fence_and_flush()?))
+	}
+}
+
+// Stops the multi-model loaders, e.g. from another thread when the user navigates away. Uploads already submitted to
+// the load queue can't be aborted, the models created so far are dropped, which waits for their copies to finish
+// before freeing them and their staging buffers. Dropping any model that's still loading does the same.
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+	pub fn new() -> CancelToken {
+		CancelToken::default()
+	}
+	
+	pub fn cancel(&self) {
+		self.0.store(true, Ordering::Relaxed);
+	}
+	
+	pub fn cancelled(&self) -> bool {
+		self.0.load(Ordering::Relaxed)
+	}
+	
+	fn check(&self) -> Result<(), ModelError> {
+		if self.cancelled() { Err(ModelError::Cancelled) } else { Ok(()) }
+	}
+}
+
+// Whether the upload succeeded, `None` while it's still running. The result replaces the fence once it signaled.
+fn poll_fence(fence: &ArcSwap<FenceCheck>) -> Option<bool> {
+	match &**fence.load() {
+		FenceCheck::Done(result) => Some(*result),
+		FenceCheck::Pending(pending) => {
+			match pending.wait(Some(Duration::new(0, 0))) {
+				Err(FlushError::Timeout) => None,
+				Ok(()) => {
+					fence.swap(Arc::new(FenceCheck::Done(true)));
+					Some(true)
+				}
+				Err(err) => {
+					error!("Error while loading model: {:?}", err);
+					fence.swap(Arc::new(FenceCheck::Done(false)));
+					Some(false)
+				}
+			}
+		}
+	}
+}
+
+// Uploads the texture with a full mip chain, each level blitted from the previous one.
+// vulkano 0.18 refuses blits within a single image, so levels are read back from a scratch copy of the chain instead.
+// Transfer only queues can't blit, there the levels are downsampled on the CPU and copied like the base level.
+fn upload_texture(source_image: &DynamicImage, format: Format, queue: &Arc<Queue>) -> Result<(Arc<ImmutableImage<Format>>, Box<dyn GpuFuture + Send + Sync>), ModelError> {
+	let device = queue.device();
+	let (width, height) = source_image.dimensions();
+	let dimensions = Dimensions::Dim2d{ width, height };
+	let usage = ImageUsage { transfer_source: true,
+	                         transfer_destination: true,
+	                         sampled: true,
+	                         ..ImageUsage::none() };
+	
+	let source_image = source_image.to_rgba();
+	let staging = |image: &RgbaImage| CpuAccessibleBuffer::from_iter(device.clone(),
+	                                                                 BufferUsage::transfer_source(),
+	                                                                 false,
+	                                                                 image.as_raw().iter().cloned());
+	
+	let pixels = staging(&source_image)?;
+	
+	let create = |levels: u32, layout: ImageLayout| ImmutableImage::uninitialized(device.clone(),
+	                                                                              dimensions,
+	                                                                              format,
+	                                                                              MipmapsCount::Specific(levels),
+	                                                                              usage,
+	                                                                              layout,
+	                                                                              device.active_queue_families());
+	
+	let levels = 32 - width.max(height).leading_zeros();
+	let (mut image, mut init) = create(levels, ImageLayout::ShaderReadOnlyOptimal)?;
+	
+	let blittable = {
+		let inner = image.inner().image;
+		inner.supports_blit_source() && inner.supports_blit_destination() && inner.supports_linear_filtering()
+	};
+	
+	let blit = queue.family().supports_graphics();
+	
+	if levels > 1 && blit && !blittable {
+		warn!("{:?} doesn't support linear blits, uploading texture without mipmaps", format);
+		let (single, single_init) = create(1, ImageLayout::ShaderReadOnlyOptimal)?;
+		image = single;
+		init = single_init;
+	}
+	
+	let levels = image.mipmap_levels();
+	let init = Arc::new(init);
+	let level_size = |level: u32| [(width >> level).max(1), (height >> level).max(1), 1];
+	
+	let mut command_buffer = AutoCommandBufferBuilder::primary_one_time_submit(device.clone(), queue.family())?
+	                                                  .copy_buffer_to_image_dimensions(pixels.clone(), init.clone(), [0, 0, 0], [width, height, 1], 0, 1, 0)?;
+	
+	if levels > 1 && !blit {
+		let mut previous = source_image;
+		
+		for level in 1 .. levels {
+			let [level_width, level_height, _] = level_size(level);
+			previous = imageops::resize(&previous, level_width, level_height, FilterType::Triangle);
+			
+			command_buffer = command_buffer.copy_buffer_to_image_dimensions(staging(&previous)?, init.clone(), [0, 0, 0], level_size(level), 0, 1, level)?;
+		}
+	} else if levels > 1 {
+		let (_, scratch) = create(levels, ImageLayout::TransferSrcOptimal)?;
+		let scratch = Arc::new(scratch);
+		
+		command_buffer = command_buffer.copy_buffer_to_image_dimensions(pixels, scratch.clone(), [0, 0, 0], [width, height, 1], 0, 1, 0)?;
+		
+		for level in 1 .. levels {
+			let [src_width, src_height, _] = level_size(level - 1);
+			let [dst_width, dst_height, _] = level_size(level);
+			
+			command_buffer = command_buffer.blit_image(scratch.clone(), [0, 0, 0], [src_width as i32, src_height as i32, 1], 0, level - 1,
+			                                           init.clone(), [0, 0, 0], [dst_width as i32, dst_height as i32, 1], 0, level,
+			                                           1, Filter::Linear)?;
+			
+			// The last level is never read back
+			if level + 1 < levels {
+				command_buffer = command_buffer.copy_image(init.clone(), [0, 0, 0], 0, level,
+				                                           scratch.clone(), [0, 0, 0], 0, level,
+				                                           level_size(level), 1)?;
+			}
+		}
+	}
+	
+	let future = command_buffer.build()?.execute(queue.clone())?;
+	
+	Ok((image, Box::new(future)))
+}
+
+// Merges identical vertices, flat shaded ones only when their face normals match as well.
+// Smooth normals are weighted by triangle area, which the unnormalized cross product already is.
+fn index_triangles(triangles: &[stl::Triangle], smooth: bool) -> (Vec<Vertex>, Vec<u32>) {
+	let mut vertices = Vec::new();
+	let mut normals: Vec<Vector3<f32>> = Vec::new();
+	let mut indices = Vec::with_capacity(triangles.len() * 3);
+	let mut lookup = HashMap::new();
+	
+	let bits = |vector: [f32; 3]| [vector[0].to_bits(), vector[1].to_bits(), vector[2].to_bits()];
+	
+	for triangle in triangles {
+		let [a, b, c] = [Vector3::from(triangle[0]), Vector3::from(triangle[1]), Vector3::from(triangle[2])];
+		let normal = (b - a).cross(c - a);
+		let face_normal = if normal == Vector3::new(0.0, 0.0, 0.0) { normal } else { normal.normalize() };
+		
+		for &pos in triangle {
+			let key = if smooth { (bits(pos), [0; 3]) } else { (bits(pos), bits(face_normal.into())) };
+			
+			let index = match lookup.get(&key) {
+				Some(&index) => index,
+				None => {
+					let index = vertices.len() as u32;
+					vertices.push(Vertex::new(pos, face_normal.into(), [0.0, 0.0]));
+					normals.push(Vector3::new(0.0, 0.0, 0.0));
+					lookup.insert(key, index);
+					index
+				},
+			};
+			
+			normals[index as usize] += normal;
+			indices.push(index);
+		}
+	}
+	
+	if smooth {
+		for (vertex, normal) in vertices.iter_mut().zip(normals) {
+			if normal != Vector3::new(0.0, 0.0, 0.0) {
+				vertex.normal = normal.normalize().into();
+			}
+		}
 	}
+	
+	(vertices, indices)
+}
+
+// Lengyel's method, tangents follow the direction of increasing u and w flips the bitangent for mirrored UVs.
+// Triangles with degenerate texture coordinates contribute nothing, meshes without any are left without tangents.
+fn generate_tangents(vertices: &mut [Vertex], indices: &[u32]) {
+	let mut tangents = vec![Vector3::new(0.0, 0.0, 0.0); vertices.len()];
+	let mut bitangents = vec![Vector3::new(0.0, 0.0, 0.0); vertices.len()];
+	
+	for triangle in indices.chunks_exact(3) {
+		let [a, b, c] = [triangle[0] as usize, triangle[1] as usize, triangle[2] as usize];
+		let edge1 = Vector3::from(vertices[b].pos) - Vector3::from(vertices[a].pos);
+		let edge2 = Vector3::from(vertices[c].pos) - Vector3::from(vertices[a].pos);
+		let [du1, dv1] = [vertices[b].tex_coord[0] - vertices[a].tex_coord[0], vertices[b].tex_coord[1] - vertices[a].tex_coord[1]];
+		let [du2, dv2] = [vertices[c].tex_coord[0] - vertices[a].tex_coord[0], vertices[c].tex_coord[1] - vertices[a].tex_coord[1]];
+		
+		let determinant = du1 * dv2 - du2 * dv1;
+		if determinant.abs() < f32::EPSILON {
+			continue;
+		}
+		
+		let tangent = (edge1 * dv2 - edge2 * dv1) / determinant;
+		let bitangent = (edge2 * du1 - edge1 * du2) / determinant;
+		
+		for &index in &[a, b, c] {
+			tangents[index] += tangent;
+			bitangents[index] += bitangent;
+		}
+	}
+	
+	for (vertex, (tangent, bitangent)) in vertices.iter_mut().zip(tangents.into_iter().zip(bitangents)) {
+		let normal = Vector3::from(vertex.normal);
+		// Gram-Schmidt, so the basis stays orthogonal to the normal
+		let tangent = tangent - normal * normal.dot(tangent);
+		
+		if tangent.magnitude2() > 0.0 && normal.magnitude2() > 0.0 {
+			let handedness = if normal.cross(tangent).dot(bitangent) < 0.0 { -1.0 } else { 1.0 };
+			vertex.tangent = tangent.normalize().extend(handedness).into();
+		}
+	}
+}
+
+// Compressed levels are copied as they are. Without BC support, and for uncompressed files, the largest level is
+// uploaded like any other image, mipmaps included. Compressed files without mip levels stay without them, compressed
+// formats can't be blitted.
+fn upload_ktx2(texture: &Ktx2, queue: &Arc<Queue>) -> Result<(Arc<ImmutableImage<Format>>, Box<dyn GpuFuture + Send + Sync>), ModelError> {
+	let device = queue.device();
+	
+	if !texture.compressed() || !device.enabled_features().texture_compression_bc {
+		let format = if texture.srgb() { COLOR_FORMAT } else { DATA_FORMAT };
+		return upload_texture(&DynamicImage::ImageRgba8(texture.decompress()), format, queue);
+	}
+	
+	let (image, init) = ImmutableImage::uninitialized(device.clone(),
+	                                                  Dimensions::Dim2d{ width: texture.width, height: texture.height },
+	                                                  texture.format,
+	                                                  MipmapsCount::Specific(texture.levels.len() as u32),
+	                                                  ImageUsage { transfer_destination: true,
+	                                                               sampled: true,
+	                                                               ..ImageUsage::none() },
+	                                                  ImageLayout::ShaderReadOnlyOptimal,
+	                                                  device.active_queue_families())?;
+	
+	let init = Arc::new(init);
+	let mut command_buffer = AutoCommandBufferBuilder::primary_one_time_submit(device.clone(), queue.family())?;
+	
+	for (level, data) in texture.levels.iter().enumerate() {
+		let level = level as u32;
+		let staging = CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::transfer_source(), false, data.iter().cloned())?;
+		let size = [(texture.width >> level).max(1), (texture.height >> level).max(1), 1];
+		
+		command_buffer = command_buffer.copy_buffer_to_image_dimensions(staging, init.clone(), [0, 0, 0], size, 0, 1, level)?;
+	}
+	
+	let future = command_buffer.build()?.execute(queue.clone())?;
+	
+	Ok((image, Box::new(future)))
+}
+
+fn obj_load_options() -> tobj::LoadOptions {
+	tobj::LoadOptions {
+		single_index: true,
+		triangulate: true,
+		..Default::default()
+	}
+}
+
+// Data URIs are decoded, anything else is up to `resolve`
+fn read_uri<R: Fn(&str) -> Option<Vec<u8>>>(uri: &str, resolve: &R) -> Result<Vec<u8>, ModelError> {
+	if uri.starts_with("data:") {
+		if let Some(start) = uri.find(";base64,") {
+			return Ok(base64::decode(&uri[start + ";base64,".len() ..])?);
+		}
+	}
+	
+	resolve(uri).ok_or_else(|| ModelError::MissingResource(uri.to_string()))
+}
+
+// `None` if the view reaches past the end of its buffer
+fn view_slice<'a>(buffers: &'a [gltf::buffer::Data], view: &gltf::buffer::View) -> Option<&'a [u8]> {
+	buffers.get(view.buffer().index())
+	       .and_then(|buffer| buffer.get(view.offset() .. view.offset().checked_add(view.length())?))
+}
+
+// gltf's reader slices the views and accessors without checking their bounds, malformed files would panic in it
+fn check_accessors(document: &gltf::Document, buffers: &[gltf::buffer::Data]) -> Result<(), ModelError> {
+	for view in document.views() {
+		view_slice(buffers, &view).ok_or(ModelError::ViewOutOfRange(view.index()))?;
+	}
+	
+	for accessor in document.accessors() {
+		// View, offset, element count and element size of the dense data and the sparse indices and values
+		let mut ranges = Vec::new();
+		if let Some(view) = accessor.view() {
+			ranges.push((view, accessor.offset(), accessor.count(), accessor.size()));
+		}
+		if let Some(sparse) = accessor.sparse() {
+			let count = sparse.count() as usize;
+			ranges.push((sparse.indices().view(), sparse.indices().offset() as usize, count, sparse.indices().index_type().size()));
+			ranges.push((sparse.values().view(), sparse.values().offset() as usize, count, accessor.size()));
+		}
+		
+		for (view, offset, count, size) in ranges {
+			let stride = view.stride().unwrap_or(size);
+			// Without any elements gltf's end of the last one would underflow
+			let end = count.checked_sub(1)
+			               .and_then(|last| last.checked_mul(stride))
+			               .and_then(|start| start.checked_add(offset)?.checked_add(size));
+			
+			if end.map_or(true, |end| end > view.length()) {
+				return Err(ModelError::AccessorOutOfRange(accessor.index()));
+			}
+		}
+	}
+	
+	Ok(())
+}
+
+fn to_u32<I: Copy + Into<u32>>(indices: &[I]) -> Vec<u32> {
+	indices.iter().map(|&index| index.into()).collect()
+}
+
+fn white_image() -> DynamicImage {
+	DynamicImage::ImageRgba8(ImageBuffer::from_pixel(1, 1, image::Rgba([255, 255, 255, 255])))
+}
+
+// Points straight along the normal, leaving it unchanged
+fn flat_normal_image() -> DynamicImage {
+	DynamicImage::ImageRgba8(ImageBuffer::from_pixel(1, 1, image::Rgba([128, 128, 255, 255])))
+}
+
+fn gltf_image(data: &gltf::image::Data) -> Result<DynamicImage, ModelError> {
+	use gltf::image::Format;
+	
+	let pixels = data.pixels.clone();
+	let image = match data.format {
+		Format::R8       => ImageBuffer::from_raw(data.width, data.height, pixels).map(DynamicImage::ImageLuma8),
+		Format::R8G8     => ImageBuffer::from_raw(data.width, data.height, pixels).map(DynamicImage::ImageLumaA8),
+		Format::R8G8B8   => ImageBuffer::from_raw(data.width, data.height, pixels).map(DynamicImage::ImageRgb8),
+		Format::R8G8B8A8 => ImageBuffer::from_raw(data.width, data.height, pixels).map(DynamicImage::ImageRgba8),
+		Format::B8G8R8   => ImageBuffer::from_raw(data.width, data.height, pixels).map(DynamicImage::ImageBgr8),
+		Format::B8G8R8A8 => ImageBuffer::from_raw(data.width, data.height, pixels).map(DynamicImage::ImageBgra8),
+		_ => None,
+	};
+	
+	image.ok_or(ModelError::UnsupportedImageFormat)
 }
 
 
 #[derive(Debug, Error)]
 pub enum ModelError {
 	#[error(display = "Pipeline doesn't have layout set 0")] NoLayout,
+	#[error(display = "glTF file doesn't contain any scene")] NoScene,
+	#[error(display = "glTF primitive doesn't have vertex positions")] NoPositions,
+	#[error(display = "Unsupported texture format")] UnsupportedImageFormat,
+	#[error(display = "Unsupported model format of {}", _0)] UnsupportedFormat(String),
+	#[error(display = "Loading was cancelled")] Cancelled,
+	#[error(display = "Couldn't resolve {}", _0)] MissingResource(String),
+	#[error(display = "Skin has {} joints, at most {} are supported", _0, MAX_JOINTS)] TooManyJoints(usize),
+	#[error(display = "Vertex refers to joint {}, at most {} are supported", _0, MAX_JOINTS)] JointOutOfRange(u16),
+	#[error(display = "glTF buffer view {} reaches past the end of its buffer", _0)] ViewOutOfRange(usize),
+	#[error(display = "glTF accessor {} reaches past the end of its buffer view", _0)] AccessorOutOfRange(usize),
+	#[error(display = "Index {} refers to a vertex past the end of the mesh", _0)] IndexOutOfRange(u32),
+	#[error(display = "{}", _0)] GltfError(#[error(source)] gltf::Error),
+	#[error(display = "{}", _0)] ObjLoadError(#[error(source)] tobj::LoadError),
+	#[error(display = "{}", _0)] StlError(#[error(source)] StlError),
+	#[error(display = "{}", _0)] PlyError(#[error(source)] PlyError),
+	#[error(display = "{}", _0)] Ktx2Error(#[error(source)] Ktx2Error),
+	#[error(display = "{}", _0)] ImageError(#[error(source)] image::ImageError),
+	#[error(display = "{}", _0)] Base64Error(#[error(source)] base64::DecodeError),
 	#[error(display = "{}", _0)] DeviceMemoryAllocError(#[error(source)] DeviceMemoryAllocError),
 	#[error(display = "{}", _0)] ImageCreationError(#[error(source)] ImageCreationError),
 	#[error(display = "{}", _0)] FlushError(#[error(source)] FlushError),
-	#[error(display = "{}", _0)] PersistentDescriptorSetError(#[error(source)] PersistentDescriptorSetError),
-	#[error(display = "{}", _0)] PersistentDescriptorSetBuildError(#[error(source)] PersistentDescriptorSetBuildError),
+	#[error(display = "{}", _0)] OomError(#[error(source)] OomError),
+	#[error(display = "{}", _0)] CopyBufferImageError(#[error(source)] CopyBufferImageError),
+	#[error(display = "{}", _0)] BlitImageError(#[error(source)] BlitImageError),
+	#[error(display = "{}", _0)] CopyImageError(#[error(source)] CopyImageError),
+	#[error(display = "{}", _0)] BuildError(#[error(source)] BuildError),
+	#[error(display = "{}", _0)] CommandBufferExecError(#[error(source)] CommandBufferExecError),
+	#[error(display = "{}", _0)] MaterialSetError(#[error(source)] MaterialSetError),
 }
 
 
 #[derive(Default, Copy, Clone)]
 pub struct Vertex {
 	pos: [f32; 3],
-	uv: [f32; 2],
+	normal: [f32; 3],
+	tex_coord: [f32; 2],
+	color: [f32; 4],
+	// Handedness of the bitangent in w, all zero leaves the normal map unused
+	tangent: [f32; 4],
+	// Up to four joints influencing the vertex, all zero weights leave it unskinned
+	joints: [u32; 4],
+	weights: [f32; 4],
 }
 
-vulkano::impl_vertex!(Vertex, pos, uv);
+vulkano::impl_vertex!(Vertex, pos, normal, tex_coord, color, tangent, joints, weights);
 
 impl Vertex {
-	pub const fn new(x: f32, y: f32, z: f32, u: f32, v: f32) -> Self {
+	// A zero normal marks the vertex as unlit
+	pub const fn new(pos: [f32; 3], normal: [f32; 3], tex_coord: [f32; 2]) -> Self {
 		Vertex {
-			pos: [x, y, z],
-			uv: [u, v],
+			pos,
+			normal,
+			tex_coord,
+			color: [1.0, 1.0, 1.0, 1.0],
+			tangent: [0.0, 0.0, 0.0, 0.0],
+			joints: [0, 0, 0, 0],
+			weights: [0.0, 0.0, 0.0, 0.0],
 		}
 	}
+	
+	// Multiplied with the texture and the model color. Together with a zero normal this shows baked colors as they are.
+	pub fn with_color(self, color: [f32; 4]) -> Self {
+		Vertex { color, ..self }
+	}
+	
+	pub fn with_tangent(self, tangent: [f32; 4]) -> Self {
+		Vertex { tangent, ..self }
+	}
+	
+	// Indices into the model's joints, the weights should add up to one
+	pub fn with_skin(self, joints: [u32; 4], weights: [f32; 4]) -> Self {
+		Vertex { joints, weights, ..self }
+	}
 }
 
 impl From<&TexturedVertex> for Vertex {
 	fn from(vertex: &TexturedVertex) -> Self {
 		Vertex::new(
-			vertex.position[0],
-			vertex.position[1],
-			vertex.position[2],
-			vertex.texture[0],
-			1.0 - vertex.texture[1],
+			vertex.position,
+			vertex.normal,
+			[vertex.texture[0], 1.0 - vertex.texture[1]],
 		)
 	}
 }
@@ -145,11 +1267,75 @@ impl From<&TexturedVertex> for Vertex {
 impl From<&render_models::Vertex> for Vertex {
 	fn from(vertex: &render_models::Vertex) -> Self {
 		Vertex::new(
-			vertex.position[0],
-			vertex.position[1],
-			vertex.position[2],
-			vertex.texture_coord[0],
-			vertex.texture_coord[1],
+			vertex.position,
+			vertex.normal,
+			vertex.texture_coord,
 		)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use cgmath::{Point3, Vector3};
+	use super::{Aabb, ModelError, check_accessors};
+	
+	fn unit_box() -> Aabb {
+		Aabb { min: Point3::new(-1.0, -1.0, -1.0), max: Point3::new(1.0, 1.0, 1.0) }
+	}
+	
+	#[test]
+	fn ray_hits_box() {
+		let distance = unit_box().intersect_ray(Point3::new(0.5, 0.2, 5.0), Vector3::new(0.0, 0.0, -1.0));
+		assert_eq!(distance, Some(4.0));
+	}
+	
+	#[test]
+	fn ray_misses_box() {
+		assert_eq!(unit_box().intersect_ray(Point3::new(0.0, 3.0, 5.0), Vector3::new(0.0, 0.0, -1.0)), None);
+		// Pointing away from it
+		assert_eq!(unit_box().intersect_ray(Point3::new(0.0, 0.0, 5.0), Vector3::new(0.0, 0.0, 1.0)), None);
+	}
+	
+	#[test]
+	fn ray_starting_inside_box() {
+		assert_eq!(unit_box().intersect_ray(Point3::new(0.2, -0.3, 0.1), Vector3::new(0.6, 0.0, 0.8)), Some(0.0));
+	}
+	
+	#[test]
+	fn axis_parallel_ray() {
+		// The other two axes divide by zero, only the slabs the origin lies in can be hit
+		assert_eq!(unit_box().intersect_ray(Point3::new(-4.0, 0.5, -0.5), Vector3::new(1.0, 0.0, 0.0)), Some(3.0));
+		assert_eq!(unit_box().intersect_ray(Point3::new(-4.0, 1.5, -0.5), Vector3::new(1.0, 0.0, 0.0)), None);
+	}
+	
+	#[test]
+	fn diagonal_ray_hits_corner() {
+		let distance = unit_box().intersect_ray(Point3::new(-3.0, -3.0, -3.0), Vector3::new(1.0, 1.0, 1.0)).unwrap();
+		assert!((distance - 2.0).abs() < 1e-6, "{}", distance);
+	}
+	
+	// A buffer of 12 bytes with one view and one accessor of `count` positions in it
+	fn check_gltf(view_offset: usize, count: usize) -> Result<(), ModelError> {
+		let json = format!(r#"{{
+			"asset": {{ "version": "2.0" }},
+			"buffers": [{{ "byteLength": 12 }}],
+			"bufferViews": [{{ "buffer": 0, "byteOffset": {}, "byteLength": 12 }}],
+			"accessors": [{{ "bufferView": 0, "componentType": 5126, "count": {}, "type": "VEC3" }}]
+		}}"#, view_offset, count);
+		let gltf = gltf::Gltf::from_slice(json.as_bytes()).unwrap();
+		
+		check_accessors(&gltf.document, &[gltf::buffer::Data(vec![0; 12])])
+	}
+	
+	#[test]
+	fn gltf_accessors_in_bounds() {
+		assert!(check_gltf(0, 1).is_ok());
+	}
+	
+	#[test]
+	fn gltf_accessors_out_of_bounds() {
+		assert!(matches!(check_gltf(4, 1), Err(ModelError::ViewOutOfRange(0))));
+		assert!(matches!(check_gltf(0, 2), Err(ModelError::AccessorOutOfRange(0))));
+		assert!(matches!(check_gltf(0, 0), Err(ModelError::AccessorOutOfRange(0))));
+	}
+}
@@ -0,0 +1,335 @@
+use std::sync::Arc;
+use std::collections::VecDeque;
+use std::mem;
+use err_derive::Error;
+use vulkano::device::{Device, Queue};
+use vulkano::buffer::{ImmutableBuffer, BufferUsage};
+use vulkano::command_buffer::pool::StandardCommandPool;
+use vulkano::command_buffer::pool::standard::StandardCommandPoolAlloc;
+use vulkano::command_buffer::sys::{UnsafeCommandBufferBuilder, UnsafeCommandBuffer, UnsafeCommandBufferBuilderPipelineBarrier, UnsafeCommandBufferBuilderBindVertexBuffer, Kind, Flags};
+use vulkano::command_buffer::submit::{SubmitCommandBufferBuilder, SubmitCommandBufferError};
+use vulkano::framebuffer::{Framebuffer, FramebufferAbstract, FramebufferCreationError, Subpass, RenderPassAbstract, RenderPassCreationError, SubpassContents};
+use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineCreationError};
+use vulkano::pipeline::vertex::SingleBufferDefinition;
+use vulkano::pipeline::viewport::Viewport;
+use vulkano::pipeline::input_assembly::IndexType;
+use vulkano::pipeline::depth_stencil::{DepthStencil, Compare, Stencil};
+use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::descriptor::descriptor::ShaderStages;
+use vulkano::query::{UnsafeQueryPool, QueryType, QueryPoolCreationError};
+use vulkano::image::{AttachmentImage, ImageAccess};
+use vulkano::format::{ClearValue, Format};
+use vulkano::memory::DeviceMemoryAllocError;
+use vulkano::sync::{GpuFuture, PipelineStages, AccessFlagBits};
+use vulkano::OomError;
+use cgmath::{Matrix4, EuclideanSpace};
+
+use crate::shaders;
+use crate::renderer::model::{Model, Vertex};
+use crate::renderer::skybox::{CUBE_VERTICES, CUBE_INDICES};
+use crate::renderer::query_results::{QueryResults, QueryStatus};
+
+// Models queried per eye and frame, the rest are always drawn
+const MAX_QUERIES: u32 = 1024;
+// Frames that can be waiting for their results, further frames aren't queried
+const SLOTS: u32 = 4;
+
+type OcclusionPipelineType = GraphicsPipeline<
+	SingleBufferDefinition<Vertex>,
+	Box<dyn PipelineLayoutAbstract + Send + Sync>,
+	Arc<dyn RenderPassAbstract + Send + Sync>
+>;
+
+// Bounding boxes of the models drawn against the depth a frame left behind, counting the samples that pass. Models
+// none of whose samples passed in either eye are skipped until a later query finds them again, so results are a few
+// frames old and something coming out from behind an occluder can show up a little late.
+// vulkano 0.18 can't record queries into its command buffers, so like the GPU timer this records and submits its own,
+// right after the frame. Only zero or not matters, which the non-precise queries every device supports can tell.
+pub struct Occlusion {
+	queue: Arc<Queue>,
+	command_pool: Arc<StandardCommandPool>,
+	query_pool: UnsafeQueryPool,
+	query_results: QueryResults,
+	pipeline: Arc<OcclusionPipelineType>,
+	vertices: Arc<ImmutableBuffer<[Vertex]>>,
+	indices: Arc<ImmutableBuffer<[u16]>>,
+	// Per eye, testing against its depth image without writing it
+	frame_buffers: (Arc<dyn FramebufferAbstract + Send + Sync>, Arc<dyn FramebufferAbstract + Send + Sync>),
+	next: u32,
+	// Oldest first
+	pending: VecDeque<Pending>,
+	// Visibility by scene index from the latest results, along with the vertex buffer of the model it was found for
+	results: Vec<Option<(u64, bool)>>,
+}
+
+struct Pending {
+	slot: u32,
+	queried: Vec<(usize, u64)>,
+	stereo: bool,
+	// Has to stay alive until the GPU is done with it
+	_command_buffer: UnsafeCommandBuffer<StandardCommandPoolAlloc>,
+}
+
+// A model to query, with its box mapped to clip space for each eye
+pub struct Query {
+	pub index: usize,
+	pub key: u64,
+	pub mvp: (Matrix4<f32>, Matrix4<f32>),
+}
+
+impl Occlusion {
+	// `None` if the query results can't be read back. The returned future finishes the upload of the box.
+	pub fn new(queue: &Arc<Queue>, depth_images: (&Arc<AttachmentImage<Format>>, &Arc<AttachmentImage<Format>>), samples: u32, visible_stencil: Stencil)
+	          -> Result<Option<(Occlusion, Box<dyn GpuFuture>)>, OcclusionError> {
+		let device = queue.device();
+		
+		let query_results = match QueryResults::load(device) {
+			Some(query_results) => query_results,
+			None => return Ok(None),
+		};
+		
+		let vs = shaders::occlusion_vert::Shader::load(device.clone()).map_err(OcclusionError::ShaderLoadError)?;
+		let fs = shaders::shadow_frag::Shader::load(device.clone()).map_err(OcclusionError::ShaderLoadError)?;
+		
+		// Picks up the depth where the frame left it. It's discarded afterwards, the next frame clears it before reading.
+		let render_pass: Arc<dyn RenderPassAbstract + Send + Sync> = Arc::new(
+			vulkano::single_pass_renderpass!(device.clone(),
+				attachments: {
+					depth: {
+						load: Load,
+						store: DontCare,
+						format: depth_images.0.format(),
+						samples: samples,
+					}
+				},
+				pass: {
+					color: [],
+					depth_stencil: {depth}
+				}
+			)?
+		);
+		
+		// Both sides, so a box still gets drawn when the eye is close to it. The hidden area fails the stencil test.
+		let pipeline = Arc::new(
+			GraphicsPipeline::start()
+			                 .vertex_input_single_buffer::<Vertex>()
+			                 .vertex_shader(vs.main_entry_point(), ())
+			                 .viewports_dynamic_scissors_irrelevant(1)
+			                 .fragment_shader(fs.main_entry_point(), ())
+			                 .depth_stencil(DepthStencil { depth_compare: Compare::LessOrEqual,
+			                                               depth_write: false,
+			                                               stencil_front: visible_stencil,
+			                                               stencil_back: visible_stencil,
+			                                               ..DepthStencil::simple_depth_test() })
+			                 .cull_mode_disabled()
+			                 .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+			                 .build(device.clone())?
+		);
+		
+		let frame_buffer = |depth_image: &Arc<AttachmentImage<Format>>| -> Result<Arc<dyn FramebufferAbstract + Send + Sync>, FramebufferCreationError> {
+			Ok(Arc::new(Framebuffer::start(render_pass.clone())
+			                .add(depth_image.clone())?
+			                .build()?))
+		};
+		
+		let (vertices, vertices_promise) = ImmutableBuffer::from_iter(CUBE_VERTICES.iter().cloned(),
+		                                                              BufferUsage{ vertex_buffer: true, ..BufferUsage::none() },
+		                                                              queue.clone())?;
+		
+		let (indices, indices_promise) = ImmutableBuffer::from_iter(CUBE_INDICES.iter().cloned(),
+		                                                            BufferUsage{ index_buffer: true, ..BufferUsage::none() },
+		                                                            queue.clone())?;
+		
+		Ok(Some((Occlusion {
+			queue: queue.clone(),
+			command_pool: Device::standard_command_pool(device, queue.family()),
+			query_pool: UnsafeQueryPool::new(device.clone(), QueryType::Occlusion, SLOTS * MAX_QUERIES * 2)?,
+			query_results,
+			pipeline,
+			vertices,
+			indices,
+			frame_buffers: (frame_buffer(depth_images.0)?, frame_buffer(depth_images.1)?),
+			next: 0,
+			pending: VecDeque::new(),
+			results: Vec::new(),
+		}, Box::new(vertices_promise.join(indices_promise)))))
+	}
+	
+	// Forgets the latest results, until new ones come in nothing is occluded
+	pub fn clear(&mut self) {
+		self.results.clear();
+	}
+	
+	// Whether the latest results found the model at `index` hidden. Models that weren't queried aren't.
+	pub fn occluded(&self, index: usize, model: &Model) -> bool {
+		match self.results.get(index) {
+			Some(&Some((key, visible))) => key == model.id() && !visible,
+			_ => false,
+		}
+	}
+	
+	// `None` if the box of the model reaches in front of the near plane, its faces might get clipped away while the
+	// model is in plain view. Skinned models can leave their box, they aren't queried either.
+	pub fn query(index: usize, model: &Model, matrix: &Matrix4<f32>, pv: (&Matrix4<f32>, &Matrix4<f32>)) -> Option<Query> {
+		let aabb = match model.aabb() {
+			Some(aabb) if model.joints().is_none() => aabb,
+			_ => return None,
+		};
+		
+		let half = (aabb.max - aabb.min) * 0.5;
+		let cube = Matrix4::from_translation((aabb.min + half).to_vec()) * Matrix4::from_nonuniform_scale(half.x, half.y, half.z);
+		
+		// Depth is 0..1 after CLIP, anything below is in front of the near plane
+		let clipped = |pv: &Matrix4<f32>| aabb.corners().iter().any(|&corner| {
+			let clip = pv * matrix * corner.to_homogeneous();
+			clip.z < 0.0 || clip.w <= 0.0
+		});
+		
+		if clipped(pv.0) || clipped(pv.1) {
+			return None;
+		}
+		
+		Some(Query { index, key: model.id(), mvp: (pv.0 * matrix * cube, pv.1 * matrix * cube) })
+	}
+	
+	// Takes in the results of the frames the GPU finished since the last call. Never waits for the GPU.
+	pub fn poll(&mut self) {
+		while let Some(pending) = self.pending.front() {
+			let count = pending.queried.len();
+			let mut samples = vec![0u64; count * 2];
+			
+			let (left, right) = samples.split_at_mut(count);
+			let left_status = self.query_results.read(&self.query_pool, pending.slot * MAX_QUERIES * 2, left);
+			let right_status = if pending.stereo {
+				self.query_results.read(&self.query_pool, pending.slot * MAX_QUERIES * 2 + MAX_QUERIES, right)
+			} else {
+				QueryStatus::Ready
+			};
+			
+			// Results come in order, so the later frames aren't ready either
+			if left_status == QueryStatus::NotReady || right_status == QueryStatus::NotReady {
+				break;
+			}
+			
+			let pending = self.pending.pop_front().unwrap();
+			
+			// Whatever went wrong, drawing everything is always correct
+			self.results.clear();
+			
+			if left_status != QueryStatus::Ready || right_status != QueryStatus::Ready {
+				continue;
+			}
+			
+			for (number, &(index, key)) in pending.queried.iter().enumerate() {
+				if self.results.len() <= index {
+					self.results.resize(index + 1, None);
+				}
+				
+				self.results[index] = Some((key, samples[number] > 0 || samples[count + number] > 0));
+			}
+		}
+	}
+	
+	// Submitted right after the frame whose depth the boxes are tested against, skipped while all slots are waiting
+	// for results. `region` is the part of the depth images the frame rendered to. Only the left eye is queried
+	// unless `stereo` is set.
+	pub fn submit(&mut self, queries: &[Query], region: [u32; 2], stereo: bool) -> Result<(), OcclusionError> {
+		if queries.is_empty() || self.pending.len() >= SLOTS as usize {
+			return Ok(());
+		}
+		
+		let queries = &queries[.. queries.len().min(MAX_QUERIES as usize)];
+		let slot = self.next;
+		self.next = (self.next + 1) % SLOTS;
+		
+		let viewport = Viewport { origin: [0.0, 0.0],
+		                          dimensions: [region[0] as f32, region[1] as f32],
+		                          depth_range: 0.0 .. 1.0 };
+		
+		let command_buffer = unsafe {
+			let mut builder = UnsafeCommandBufferBuilder::new(&self.command_pool, Kind::primary(), Flags::OneTimeSubmit)?;
+			builder.reset_query_pool(self.query_pool.queries_range(slot * MAX_QUERIES * 2, MAX_QUERIES * 2).unwrap());
+			
+			// The frame's depth writes and the upload of the box have to land before the boxes are drawn
+			let mut barrier = UnsafeCommandBufferBuilderPipelineBarrier::new();
+			barrier.add_memory_barrier(PipelineStages { late_fragment_tests: true, transfer: true, ..PipelineStages::none() },
+			                           AccessFlagBits { depth_stencil_attachment_write: true, transfer_write: true, ..AccessFlagBits::none() },
+			                           PipelineStages { vertex_input: true, early_fragment_tests: true, late_fragment_tests: true, ..PipelineStages::none() },
+			                           AccessFlagBits { index_read: true, vertex_attribute_read: true, depth_stencil_attachment_read: true, ..AccessFlagBits::none() },
+			                           false);
+			builder.pipeline_barrier(&barrier);
+			
+			let eyes = if stereo { 2 } else { 1 };
+			
+			for eye in 0 .. eyes {
+				let frame_buffer = if eye == 0 { &self.frame_buffers.0 } else { &self.frame_buffers.1 };
+				
+				builder.begin_render_pass(frame_buffer, SubpassContents::Inline, vec![ClearValue::None].into_iter());
+				builder.bind_pipeline_graphics(&self.pipeline);
+				builder.set_viewport(0, vec![viewport.clone()].into_iter());
+				
+				let mut vertex_buffers = UnsafeCommandBufferBuilderBindVertexBuffer::new();
+				vertex_buffers.add(&self.vertices);
+				builder.bind_vertex_buffers(0, vertex_buffers);
+				builder.bind_index_buffer(&self.indices, IndexType::U16);
+				
+				for (number, query) in queries.iter().enumerate() {
+					let mvp: [[f32; 4]; 4] = if eye == 0 { query.mvp.0.into() } else { query.mvp.1.into() };
+					let query = slot * MAX_QUERIES * 2 + eye * MAX_QUERIES + number as u32;
+					
+					builder.push_constants(&self.pipeline, ShaderStages { vertex: true, ..ShaderStages::none() }, 0, mem::size_of_val(&mvp) as u32, &mvp);
+					builder.begin_query(self.query_pool.query(query).unwrap(), false);
+					builder.draw_indexed(CUBE_INDICES.len() as u32, 1, 0, 0, 0);
+					builder.end_query(self.query_pool.query(query).unwrap());
+				}
+				
+				builder.end_render_pass();
+			}
+			
+			// The next frame clears the depth, only once the boxes are done with it
+			let mut barrier = UnsafeCommandBufferBuilderPipelineBarrier::new();
+			barrier.add_execution_dependency(PipelineStages { early_fragment_tests: true, late_fragment_tests: true, ..PipelineStages::none() },
+			                                 PipelineStages { early_fragment_tests: true, late_fragment_tests: true, ..PipelineStages::none() },
+			                                 false);
+			builder.pipeline_barrier(&barrier);
+			
+			builder.build()?
+		};
+		
+		unsafe {
+			let mut submit = SubmitCommandBufferBuilder::new();
+			submit.add_command_buffer(&command_buffer);
+			submit.submit(&self.queue)?;
+		}
+		
+		self.pending.push_back(Pending {
+			slot,
+			queried: queries.iter().map(|query| (query.index, query.key)).collect(),
+			stereo,
+			_command_buffer: command_buffer,
+		});
+		
+		Ok(())
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum OcclusionError {
+	#[error(display = "Failed to load shader: {}", _0)] ShaderLoadError(#[error(source, no_from)] OomError),
+	#[error(display = "{}", _0)] OomError(#[error(source)] OomError),
+	#[error(display = "{}", _0)] RenderPassCreationError(#[error(source)] RenderPassCreationError),
+	#[error(display = "{}", _0)] GraphicsPipelineCreationError(#[error(source)] GraphicsPipelineCreationError),
+	#[error(display = "{}", _0)] FramebufferCreationError(#[error(source)] FramebufferCreationError),
+	#[error(display = "{}", _0)] DeviceMemoryAllocError(#[error(source)] DeviceMemoryAllocError),
+	#[error(display = "{}", _0)] QueryPoolCreationError(#[error(source)] QueryPoolCreationError),
+	#[error(display = "{}", _0)] SubmitCommandBufferError(#[error(source)] SubmitCommandBufferError),
+}
+
+impl OcclusionError {
+	pub fn device_lost(&self) -> bool {
+		match self {
+			OcclusionError::SubmitCommandBufferError(SubmitCommandBufferError::DeviceLost) => true,
+			_ => false,
+		}
+	}
+}
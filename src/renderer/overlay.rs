@@ -0,0 +1,199 @@
+use std::sync::Arc;
+use std::ffi::{CString, CStr, NulError};
+use std::os::raw::c_void;
+use std::mem;
+use err_derive::Error;
+use vulkano::device::Queue;
+use vulkano::image::{AttachmentImage, ImageAccess};
+use vulkano::format;
+use openvr::compositor::texture::{vulkan, ColorSpace};
+use openvr::system::EventInfo;
+use openvr::TrackingUniverseOrigin;
+use openvr_sys as sys;
+use cgmath::{Matrix4, Matrix};
+
+use crate::openvr_vulkan::OpenVRPtr;
+use crate::renderer::Renderer;
+
+// 2D panel shown by the compositor on top of the scene, either in the world or in the SteamVR dashboard.
+// openvr 0.6 doesn't wrap the overlay interface, so this calls into openvr_sys the same way openvr does.
+// Needs an OpenVR context that outlives the overlay.
+pub struct Overlay {
+	table: &'static sys::VR_IVROverlay_FnTable,
+	handle: sys::VROverlayHandle_t,
+	// Icon shown in the dashboard's bar, only dashboard overlays have one
+	thumbnail: Option<sys::VROverlayHandle_t>,
+	queue: Arc<Queue>,
+	// The compositor keeps reading the image until it gets another one
+	image: Option<Arc<AttachmentImage<format::R8G8B8A8Srgb>>>,
+}
+
+impl Overlay {
+	// Placed in the world with `set_transform`. `key` has to be unique among all running applications.
+	pub fn new(key: &str, name: &str, renderer: &Renderer) -> Result<Overlay, OverlayError> {
+		let table = overlay_table()?;
+		let key = CString::new(key)?;
+		let name = CString::new(name)?;
+		let mut handle = 0;
+		
+		check(unsafe { table.CreateOverlay.unwrap()(key.as_ptr() as *mut _, name.as_ptr() as *mut _, &mut handle) })?;
+		
+		Ok(Overlay {
+			table,
+			handle,
+			thumbnail: None,
+			queue: renderer.graphics_queue(),
+			image: None,
+		})
+	}
+	
+	// Shown as a tab of the SteamVR dashboard, which places it and shows or hides it
+	pub fn dashboard(key: &str, name: &str, renderer: &Renderer) -> Result<Overlay, OverlayError> {
+		let table = overlay_table()?;
+		let key = CString::new(key)?;
+		let name = CString::new(name)?;
+		let mut handle = 0;
+		let mut thumbnail = 0;
+		
+		check(unsafe { table.CreateDashboardOverlay.unwrap()(key.as_ptr() as *mut _, name.as_ptr() as *mut _, &mut handle, &mut thumbnail) })?;
+		
+		Ok(Overlay {
+			table,
+			handle,
+			thumbnail: Some(thumbnail),
+			queue: renderer.graphics_queue(),
+			image: None,
+		})
+	}
+	
+	pub fn show(&self) -> Result<(), OverlayError> {
+		check(unsafe { self.table.ShowOverlay.unwrap()(self.handle) })
+	}
+	
+	pub fn hide(&self) -> Result<(), OverlayError> {
+		check(unsafe { self.table.HideOverlay.unwrap()(self.handle) })
+	}
+	
+	pub fn visible(&self) -> bool {
+		unsafe { self.table.IsOverlayVisible.unwrap()(self.handle) }
+	}
+	
+	// The height follows from the aspect ratio of the image
+	pub fn set_width(&self, meters: f32) -> Result<(), OverlayError> {
+		check(unsafe { self.table.SetOverlayWidthInMeters.unwrap()(self.handle, meters) })
+	}
+	
+	// Center of the overlay in tracking space, it faces along +Z
+	pub fn set_transform(&self, origin: TrackingUniverseOrigin, matrix: Matrix4<f32>) -> Result<(), OverlayError> {
+		let mut matrix = transform34(matrix);
+		check(unsafe { self.table.SetOverlayTransformAbsolute.unwrap()(self.handle, origin as sys::ETrackingUniverseOrigin, &mut matrix) })
+	}
+	
+	// Any image created on the renderer's device, e.g. one rendered into with its pipelines. The compositor samples
+	// it whenever it draws the overlay, so it has to be finished rendering before this is called.
+	pub fn set_texture(&mut self, image: Arc<AttachmentImage<format::R8G8B8A8Srgb>>) -> Result<(), OverlayError> {
+		let device = self.queue.device();
+		let dimensions = image.dimensions();
+		
+		let mut data = vulkan::Texture {
+			image: (*image).as_ptr(),
+			device: device.as_ptr(),
+			physical_device: device.physical_device().as_ptr(),
+			instance: device.instance().as_ptr(),
+			queue: self.queue.as_ptr(),
+			queue_family_index: self.queue.family().id(),
+			width: dimensions.width(),
+			height: dimensions.height(),
+			format: image.format() as u32,
+			sample_count: image.samples(),
+		};
+		
+		let mut texture = sys::Texture_t {
+			handle: &mut data as *mut _ as *mut c_void,
+			eType: sys::ETextureType_TextureType_Vulkan,
+			eColorSpace: ColorSpace::Gamma as sys::EColorSpace,
+		};
+		
+		check(unsafe { self.table.SetOverlayTexture.unwrap()(self.handle, &mut texture) })?;
+		
+		// Laser pointer positions are reported in pixels of the image
+		let mut scale = sys::HmdVector2_t { v: [dimensions.width() as f32, dimensions.height() as f32] };
+		check(unsafe { self.table.SetOverlayMouseScale.unwrap()(self.handle, &mut scale) })?;
+		
+		self.image = Some(image);
+		Ok(())
+	}
+	
+	// Lets controllers point at the overlay with a laser, which shows up as mouse events
+	pub fn set_laser_pointer(&self, enabled: bool) -> Result<(), OverlayError> {
+		let method = if enabled { sys::VROverlayInputMethod_Mouse } else { sys::VROverlayInputMethod_None };
+		check(unsafe { self.table.SetOverlayInputMethod.unwrap()(self.handle, method) })
+	}
+	
+	// Events of this overlay only, e.g. `Event::MouseMove` with the position of the laser pointer, origin at the
+	// bottom left. Call until it returns `None` every frame.
+	pub fn poll_event(&self) -> Option<EventInfo> {
+		let mut event: sys::VREvent_t = unsafe { mem::zeroed() };
+		
+		if unsafe { self.table.PollNextOverlayEvent.unwrap()(self.handle, &mut event, mem::size_of_val(&event) as u32) } {
+			Some(event.into())
+		} else {
+			None
+		}
+	}
+}
+
+impl Drop for Overlay {
+	fn drop(&mut self) {
+		unsafe {
+			self.table.DestroyOverlay.unwrap()(self.handle);
+			if let Some(thumbnail) = self.thumbnail {
+				self.table.DestroyOverlay.unwrap()(thumbnail);
+			}
+		}
+	}
+}
+
+fn overlay_table() -> Result<&'static sys::VR_IVROverlay_FnTable, OverlayError> {
+	let mut magic = Vec::from(&b"FnTable:"[..]);
+	magic.extend_from_slice(sys::IVROverlay_Version);
+	
+	let mut error = sys::EVRInitError_VRInitError_None;
+	let table = unsafe { sys::VR_GetGenericInterface(magic.as_ptr() as *const _, &mut error) };
+	
+	if error != sys::EVRInitError_VRInitError_None || table == 0 {
+		return Err(OverlayError::InterfaceNotFound);
+	}
+	
+	Ok(unsafe { &*(table as *const sys::VR_IVROverlay_FnTable) })
+}
+
+// Rows of the matrix without the last one, like the poses OpenVR hands out
+fn transform34(matrix: Matrix4<f32>) -> sys::HmdMatrix34_t {
+	let rows: [[f32; 4]; 4] = matrix.transpose().into();
+	sys::HmdMatrix34_t { m: [rows[0], rows[1], rows[2]] }
+}
+
+fn check(error: sys::EVROverlayError) -> Result<(), OverlayError> {
+	if error == sys::EVROverlayError_VROverlayError_None {
+		return Ok(());
+	}
+	
+	let table = overlay_table()?;
+	let name = unsafe { table.GetOverlayErrorNameFromEnum.unwrap()(error) };
+	
+	let name = if name.is_null() {
+		error.to_string()
+	} else {
+		unsafe { CStr::from_ptr(name) }.to_string_lossy().into_owned()
+	};
+	
+	Err(OverlayError::CallError(name))
+}
+
+#[derive(Debug, Error)]
+pub enum OverlayError {
+	#[error(display = "OpenVR overlay interface is not available")] InterfaceNotFound,
+	#[error(display = "Overlay error: {}", _0)] CallError(String),
+	#[error(display = "{}", _0)] NulError(#[error(source)] NulError),
+}
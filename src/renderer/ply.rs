@@ -0,0 +1,311 @@
+use std::fs;
+use std::io;
+use std::convert::TryInto;
+use std::path::Path;
+use err_derive::Error;
+
+use crate::renderer::model::Vertex;
+
+// Triangulated faces, empty for point clouds
+pub struct Mesh {
+	pub vertices: Vec<Vertex>,
+	pub indices: Vec<u32>,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Encoding {
+	Ascii,
+	LittleEndian,
+	BigEndian,
+}
+
+#[derive(Copy, Clone)]
+enum Type {
+	I8, U8, I16, U16, I32, U32, F32, F64,
+}
+
+enum Property {
+	Scalar(String, Type),
+	List(String, Type, Type),
+}
+
+struct Element {
+	name: String,
+	count: usize,
+	properties: Vec<Property>,
+}
+
+// Unknown elements and properties are skipped, polygons are triangulated as fans
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Mesh, PlyError> {
+	parse(&fs::read(path)?)
+}
+
+pub fn parse(data: &[u8]) -> Result<Mesh, PlyError> {
+	let header_end = data.windows(10).position(|window| window == b"end_header").ok_or(PlyError::InvalidHeader)?;
+	let body_start = data[header_end ..].iter().position(|&byte| byte == b'\n').ok_or(PlyError::InvalidHeader)? + header_end + 1;
+	let (encoding, elements) = parse_header(&String::from_utf8_lossy(&data[.. header_end]))?;
+	
+	let text;
+	let mut reader = match encoding {
+		Encoding::Ascii => {
+			text = String::from_utf8_lossy(&data[body_start ..]);
+			Reader::Ascii(&text)
+		},
+		_ => Reader::Binary(&data[body_start ..], encoding),
+	};
+	
+	let mut mesh = Mesh { vertices: Vec::new(), indices: Vec::new() };
+	
+	for element in &elements {
+		match element.name.as_str() {
+			"vertex" => read_vertices(&mut reader, element, &mut mesh.vertices)?,
+			"face" => read_faces(&mut reader, element, &mut mesh.indices)?,
+			_ => for _ in 0 .. element.count {
+				for property in &element.properties {
+					skip(&mut reader, property)?;
+				}
+			},
+		}
+	}
+	
+	if mesh.indices.iter().any(|&index| index as usize >= mesh.vertices.len()) {
+		return Err(PlyError::InvalidIndex);
+	}
+	
+	Ok(mesh)
+}
+
+fn parse_header(header: &str) -> Result<(Encoding, Vec<Element>), PlyError> {
+	let mut lines = header.lines().map(str::trim);
+	
+	if lines.next() != Some("ply") {
+		return Err(PlyError::InvalidHeader);
+	}
+	
+	let mut encoding = None;
+	let mut elements: Vec<Element> = Vec::new();
+	
+	for line in lines {
+		let words: Vec<&str> = line.split_whitespace().collect();
+		
+		match words.as_slice() {
+			["format", "ascii", _] => encoding = Some(Encoding::Ascii),
+			["format", "binary_little_endian", _] => encoding = Some(Encoding::LittleEndian),
+			["format", "binary_big_endian", _] => encoding = Some(Encoding::BigEndian),
+			["element", name, count] => elements.push(Element { name: name.to_string(),
+			                                                    count: count.parse().map_err(|_| PlyError::InvalidHeader)?,
+			                                                    properties: Vec::new() }),
+			["property", "list", count, item, name] => elements.last_mut()
+			                                                   .ok_or(PlyError::InvalidHeader)?
+			                                                   .properties
+			                                                   .push(Property::List(name.to_string(), parse_type(count)?, parse_type(item)?)),
+			["property", ty, name] => elements.last_mut()
+			                                  .ok_or(PlyError::InvalidHeader)?
+			                                  .properties
+			                                  .push(Property::Scalar(name.to_string(), parse_type(ty)?)),
+			["comment", ..] | ["obj_info", ..] | [] => {},
+			_ => return Err(PlyError::InvalidHeader),
+		}
+	}
+	
+	Ok((encoding.ok_or(PlyError::InvalidHeader)?, elements))
+}
+
+fn parse_type(name: &str) -> Result<Type, PlyError> {
+	Ok(match name {
+		"char" | "int8" => Type::I8,
+		"uchar" | "uint8" => Type::U8,
+		"short" | "int16" => Type::I16,
+		"ushort" | "uint16" => Type::U16,
+		"int" | "int32" => Type::I32,
+		"uint" | "uint32" => Type::U32,
+		"float" | "float32" => Type::F32,
+		"double" | "float64" => Type::F64,
+		_ => return Err(PlyError::InvalidHeader),
+	})
+}
+
+// Colors stored as integers are sRGB like image files and get decoded, floats are taken as linear.
+// Missing colors are white.
+fn read_vertices(reader: &mut Reader, element: &Element, vertices: &mut Vec<Vertex>) -> Result<(), PlyError> {
+	// The count comes from the header, so it's capped by what the rest of the file can hold
+	let min_size = element.properties.iter().map(|property| reader.min_size(property)).sum::<usize>().max(1);
+	vertices.reserve(element.count.min(reader.remaining() / min_size));
+	
+	for _ in 0 .. element.count {
+		let mut pos = [0.0; 3];
+		let mut normal = [0.0; 3];
+		let mut tex_coord = [0.0; 2];
+		let mut color = [1.0; 4];
+		
+		for property in &element.properties {
+			match property {
+				Property::Scalar(name, ty) => {
+					let value = reader.read(*ty)?;
+					let (channel, alpha) = match ty {
+						Type::U8 => (srgb_to_linear(value / 255.0), value / 255.0),
+						Type::U16 => (srgb_to_linear(value / 65535.0), value / 65535.0),
+						_ => (value, value),
+					};
+					
+					match name.as_str() {
+						"x" => pos[0] = value as f32,
+						"y" => pos[1] = value as f32,
+						"z" => pos[2] = value as f32,
+						"nx" => normal[0] = value as f32,
+						"ny" => normal[1] = value as f32,
+						"nz" => normal[2] = value as f32,
+						"s" | "u" | "texture_u" => tex_coord[0] = value as f32,
+						"t" | "v" | "texture_v" => tex_coord[1] = 1.0 - value as f32,
+						"red" | "diffuse_red" => color[0] = channel as f32,
+						"green" | "diffuse_green" => color[1] = channel as f32,
+						"blue" | "diffuse_blue" => color[2] = channel as f32,
+						"alpha" => color[3] = alpha as f32,
+						_ => {},
+					}
+				},
+				Property::List(..) => skip(reader, property)?,
+			}
+		}
+		
+		vertices.push(Vertex::new(pos, normal, tex_coord).with_color(color));
+	}
+	
+	Ok(())
+}
+
+// Alpha is never encoded
+fn srgb_to_linear(value: f64) -> f64 {
+	if value <= 0.04045 {
+		value / 12.92
+	} else {
+		((value + 0.055) / 1.055).powf(2.4)
+	}
+}
+
+fn read_faces(reader: &mut Reader, element: &Element, indices: &mut Vec<u32>) -> Result<(), PlyError> {
+	for _ in 0 .. element.count {
+		for property in &element.properties {
+			match property {
+				Property::List(name, count, item) if name == "vertex_indices" || name == "vertex_index" => {
+					let mut polygon = Vec::new();
+					for _ in 0 .. reader.read(*count)? as usize {
+						polygon.push(reader.read(*item)? as u32);
+					}
+					
+					for i in 2 .. polygon.len() {
+						indices.extend_from_slice(&[polygon[0], polygon[i - 1], polygon[i]]);
+					}
+				},
+				_ => skip(reader, property)?,
+			}
+		}
+	}
+	
+	Ok(())
+}
+
+fn skip(reader: &mut Reader, property: &Property) -> Result<(), PlyError> {
+	match property {
+		Property::Scalar(_, ty) => { reader.read(*ty)?; },
+		Property::List(_, count, item) => {
+			for _ in 0 .. reader.read(*count)? as usize {
+				reader.read(*item)?;
+			}
+		},
+	}
+	
+	Ok(())
+}
+
+enum Reader<'a> {
+	Ascii(&'a str),
+	Binary(&'a [u8], Encoding),
+}
+
+impl Reader<'_> {
+	// Every type fits into a double without loss
+	fn read(&mut self, ty: Type) -> Result<f64, PlyError> {
+		match self {
+			Reader::Ascii(text) => {
+				let token_start = text.trim_start();
+				let (token, rest) = token_start.split_at(token_start.find(char::is_whitespace).unwrap_or(token_start.len()));
+				*text = rest;
+				
+				if token.is_empty() {
+					return Err(PlyError::Truncated);
+				}
+				
+				token.parse().map_err(|_| PlyError::InvalidNumber)
+			},
+			Reader::Binary(data, encoding) => {
+				let size = ty.size();
+				
+				if data.len() < size {
+					return Err(PlyError::Truncated);
+				}
+				
+				let mut bytes = [0; 8];
+				bytes[.. size].copy_from_slice(&data[.. size]);
+				*data = &data[size ..];
+				
+				if *encoding == Encoding::BigEndian {
+					bytes[.. size].reverse();
+				}
+				
+				let bytes_of = |size: usize| &bytes[.. size];
+				
+				Ok(match ty {
+					Type::I8 => bytes[0] as i8 as f64,
+					Type::U8 => bytes[0] as f64,
+					Type::I16 => i16::from_le_bytes(bytes_of(2).try_into().unwrap()) as f64,
+					Type::U16 => u16::from_le_bytes(bytes_of(2).try_into().unwrap()) as f64,
+					Type::I32 => i32::from_le_bytes(bytes_of(4).try_into().unwrap()) as f64,
+					Type::U32 => u32::from_le_bytes(bytes_of(4).try_into().unwrap()) as f64,
+					Type::F32 => f32::from_le_bytes(bytes_of(4).try_into().unwrap()) as f64,
+					Type::F64 => f64::from_le_bytes(bytes),
+				})
+			},
+		}
+	}
+	
+	fn remaining(&self) -> usize {
+		match self {
+			Reader::Ascii(text) => text.len(),
+			Reader::Binary(data, _) => data.len(),
+		}
+	}
+	
+	// Fewest bytes the property can take up, lists can be empty
+	fn min_size(&self, property: &Property) -> usize {
+		let ty = match property {
+			Property::Scalar(_, ty) | Property::List(_, ty, _) => *ty,
+		};
+		
+		match self {
+			// A digit and a separator
+			Reader::Ascii(_) => 2,
+			Reader::Binary(..) => ty.size(),
+		}
+	}
+}
+
+impl Type {
+	fn size(self) -> usize {
+		match self {
+			Type::I8 | Type::U8 => 1,
+			Type::I16 | Type::U16 => 2,
+			Type::I32 | Type::U32 | Type::F32 => 4,
+			Type::F64 => 8,
+		}
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum PlyError {
+	#[error(display = "PLY header is missing or invalid")] InvalidHeader,
+	#[error(display = "PLY file is truncated")] Truncated,
+	#[error(display = "Invalid number in ASCII PLY")] InvalidNumber,
+	#[error(display = "PLY face refers to a vertex that doesn't exist")] InvalidIndex,
+	#[error(display = "{}", _0)] IoError(#[error(source)] io::Error),
+}
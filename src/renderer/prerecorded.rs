@@ -0,0 +1,89 @@
+use std::sync::Arc;
+use vulkano::buffer::{CpuAccessibleBuffer, BufferUsage};
+use vulkano::device::Device;
+use vulkano::memory::DeviceMemoryAllocError;
+
+use crate::renderer::{LightUniform, ViewUniform};
+use crate::renderer::secondary::SceneCommands;
+
+// Prepass and model command buffers of a static scene, recorded once and executed again every frame. Only the light
+// and view uniforms they read are rewritten. vulkano doesn't lock what secondary command buffers use, so every frame
+// in flight gets its own uniforms and its own recording of the scene.
+pub struct Prerecorded {
+	slots: Vec<Slot>,
+	next: usize,
+}
+
+pub struct Slot {
+	pub light: Arc<CpuAccessibleBuffer<LightUniform>>,
+	pub views: (Arc<CpuAccessibleBuffer<ViewUniform>>, Arc<CpuAccessibleBuffer<ViewUniform>>),
+	pub recorded: Option<(RecordKey, (SceneCommands, SceneCommands))>,
+}
+
+// Everything the recording depends on besides the models themselves, which only change through `mark_scene_dirty`
+#[derive(Clone, PartialEq)]
+pub struct RecordKey {
+	// Visible models that finished loading
+	pub drawn: Vec<usize>,
+	pub region: [u32; 2],
+	pub prepass: bool,
+	pub wireframe: bool,
+	#[cfg(feature = "indirect-draw")]
+	pub indirect_draw: bool,
+}
+
+impl Prerecorded {
+	// `frames` has to cover all frames that can be in flight
+	pub fn new(device: &Arc<Device>, frames: usize) -> Result<Prerecorded, DeviceMemoryAllocError> {
+		let light = LightUniform { direction: [0.0; 3], ambient: 0.0, pv: [[0.0; 4]; 4] };
+		let view = ViewUniform { pv: [[0.0; 4]; 4], previous_pv: [[0.0; 4]; 4] };
+		let uniform = || BufferUsage { uniform_buffer: true, ..BufferUsage::none() };
+		
+		let mut slots = Vec::with_capacity(frames);
+		for _ in 0 .. frames.max(1) {
+			slots.push(Slot {
+				light: CpuAccessibleBuffer::from_data(device.clone(), uniform(), false, light)?,
+				views: (CpuAccessibleBuffer::from_data(device.clone(), uniform(), false, view)?,
+				        CpuAccessibleBuffer::from_data(device.clone(), uniform(), false, view)?),
+				recorded: None,
+			});
+		}
+		
+		Ok(Prerecorded {
+			slots,
+			next: 0,
+		})
+	}
+	
+	// Slot of the frame about to be drawn. The frame that used it last has finished once the renderer waited for the
+	// frames in flight.
+	pub fn advance(&mut self) -> usize {
+		let slot = self.next;
+		self.next = (self.next + 1) % self.slots.len();
+		slot
+	}
+	
+	pub fn slot(&self, slot: usize) -> &Slot {
+		&self.slots[slot]
+	}
+	
+	pub fn store(&mut self, slot: usize, key: RecordKey, commands: (SceneCommands, SceneCommands)) {
+		self.slots[slot].recorded = Some((key, commands));
+	}
+	
+	pub fn invalidate(&mut self) {
+		for slot in &mut self.slots {
+			slot.recorded = None;
+		}
+	}
+}
+
+impl Slot {
+	// Recorded scene if it's still valid for `key`
+	pub fn commands(&self, key: &RecordKey) -> Option<&(SceneCommands, SceneCommands)> {
+		match &self.recorded {
+			Some((recorded_key, commands)) if recorded_key == key => Some(commands),
+			_ => None,
+		}
+	}
+}
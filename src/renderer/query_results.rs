@@ -0,0 +1,62 @@
+use std::os::raw::c_void;
+use std::mem;
+use vulkano::device::{Device, DeviceOwned};
+use vulkano::query::UnsafeQueryPool;
+use vulkano::instance::loader;
+use vulkano::VulkanObject;
+
+// vulkano 0.18 can't read query results back, so the GPU timer and occlusion culling call vkGetQueryPoolResults
+// through the loader directly.
+pub struct QueryResults {
+	get_results: GetQueryPoolResults,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum QueryStatus {
+	Ready,
+	NotReady,
+	Failed,
+}
+
+impl QueryResults {
+	// `None` if the loader doesn't have the function
+	pub fn load(device: &Device) -> Option<QueryResults> {
+		unsafe {
+			let loader = loader::auto_loader().ok()?;
+			let function: *const c_void = mem::transmute(loader.get_instance_proc_addr(device.instance().internal_object(), b"vkGetQueryPoolResults\0".as_ptr() as *const _));
+			
+			if function.is_null() {
+				return None;
+			}
+			
+			Some(QueryResults { get_results: mem::transmute(function) })
+		}
+	}
+	
+	// One 64 bit result per query, starting at `first`. Never waits for the GPU.
+	pub fn read(&self, query_pool: &UnsafeQueryPool, first: u32, results: &mut [u64]) -> QueryStatus {
+		let result = unsafe {
+			(self.get_results)(query_pool.device().internal_object(),
+			                   query_pool.internal_object(),
+			                   first,
+			                   results.len() as u32,
+			                   mem::size_of_val(results),
+			                   results.as_mut_ptr() as *mut c_void,
+			                   mem::size_of::<u64>() as u64,
+			                   QUERY_RESULT_64)
+		};
+		
+		match result {
+			SUCCESS => QueryStatus::Ready,
+			NOT_READY => QueryStatus::NotReady,
+			_ => QueryStatus::Failed,
+		}
+	}
+}
+
+// Just enough of the Vulkan headers to read the results back
+const SUCCESS: i32 = 0;
+const NOT_READY: i32 = 1;
+const QUERY_RESULT_64: u32 = 0x1;
+
+type GetQueryPoolResults = unsafe extern "system" fn(usize, u64, u32, u32, usize, *mut c_void, u64, u32) -> i32;
@@ -0,0 +1,182 @@
+use std::sync::Arc;
+use err_derive::Error;
+use vulkano::buffer::{CpuAccessibleBuffer, BufferUsage};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
+use vulkano::device::Device;
+use vulkano::framebuffer::Subpass;
+use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineCreationError};
+use vulkano::pipeline::blend::AttachmentBlend;
+use vulkano::pipeline::depth_stencil::{DepthStencil, Stencil, StencilOp, Compare};
+use vulkano::pipeline::raster::FrontFace;
+use vulkano::memory::DeviceMemoryAllocError;
+use vulkano::OomError;
+use cgmath::{Matrix4, Vector4, Matrix, SquareMatrix, InnerSpace};
+
+use crate::shaders;
+use crate::renderer::{RenderError, PipelineType, PipelineDesc, ModelPipelines};
+use crate::renderer::model::{self, Vertex};
+
+// Pixels of the mirror are marked with this stencil bit, the hidden area keeps the lowest one
+const MIRROR_STENCIL: u32 = 2;
+
+// Marks the mirror where the hidden area doesn't cover it
+const MARK_STENCIL: Stencil = Stencil {
+	compare: Compare::Equal,
+	pass_op: StencilOp::Replace,
+	fail_op: StencilOp::Keep,
+	depth_fail_op: StencilOp::Keep,
+	compare_mask: Some(1),
+	write_mask: Some(0xff),
+	reference: Some(MIRROR_STENCIL),
+};
+
+// The reflected scene only draws where the mirror was marked
+const REFLECTED_STENCIL: Stencil = Stencil {
+	compare: Compare::Equal,
+	pass_op: StencilOp::Keep,
+	fail_op: StencilOp::Keep,
+	depth_fail_op: StencilOp::Keep,
+	compare_mask: Some(0xff),
+	write_mask: Some(0),
+	reference: Some(MIRROR_STENCIL),
+};
+
+// A single flat mirror. Its quad is first marked in the stencil, then the scene is drawn inside it with the view
+// reflected across its plane, and finally the quad's own depth replaces the reflection's so the rest of the scene is
+// tested against the mirror. Needs a depth format with a stencil.
+pub struct Reflection {
+	// Unit quad in the XY plane, facing +Z
+	quad: Arc<CpuAccessibleBuffer<[Vertex]>>,
+	transform: Matrix4<f32>,
+	mark_pipeline: Arc<PipelineType>,
+	cover_pipeline: Arc<PipelineType>,
+	// Model pipelines with the winding flipped, the reflection turns every triangle around
+	pub pipelines: ModelPipelines,
+}
+
+impl Reflection {
+	pub fn new(device: &Arc<Device>, desc: &PipelineDesc) -> Result<Reflection, ReflectionError> {
+		let vs = shaders::vert::Shader::load(device.clone()).map_err(ReflectionError::ShaderLoadError)?;
+		let fs = shaders::frag::Shader::load(device.clone()).map_err(ReflectionError::ShaderLoadError)?;
+		let quad_vs = shaders::occlusion_vert::Shader::load(device.clone()).map_err(ReflectionError::ShaderLoadError)?;
+		let quad_fs = shaders::shadow_frag::Shader::load(device.clone()).map_err(ReflectionError::ShaderLoadError)?;
+		
+		let desc = PipelineDesc { front_face: match desc.front_face {
+			                          FrontFace::CounterClockwise => FrontFace::Clockwise,
+			                          FrontFace::Clockwise => FrontFace::CounterClockwise,
+		                          },
+		                          visible_stencil: REFLECTED_STENCIL,
+		                          ..desc.clone() };
+		
+		let pipelines = desc.build(device, vs.main_entry_point(), fs.main_entry_point())?;
+		
+		// Both passes over the quad only touch depth and stencil. Its back is never drawn, eyes behind the mirror skip it.
+		let quad_pipeline = |depth_stencil: DepthStencil| -> Result<Arc<PipelineType>, GraphicsPipelineCreationError> {
+			Ok(Arc::new(
+				GraphicsPipeline::start()
+				                 .vertex_input_single_buffer::<model::Vertex>()
+				                 .vertex_shader(quad_vs.main_entry_point(), ())
+				                 .viewports_dynamic_scissors_irrelevant(1)
+				                 .fragment_shader(quad_fs.main_entry_point(), ())
+				                 .depth_stencil(depth_stencil)
+				                 .blend_collective(AttachmentBlend { mask_red: false,
+				                                                     mask_green: false,
+				                                                     mask_blue: false,
+				                                                     mask_alpha: false,
+				                                                     ..AttachmentBlend::pass_through() })
+				                 .cull_mode_disabled()
+				                 .render_pass(Subpass::from(desc.render_pass.clone(), 0).unwrap())
+				                 .with_auto_layout(device.clone(), &[])?
+			))
+		};
+		
+		// Models in front of the mirror still hide it, they're drawn later and test against its depth
+		let mark_pipeline = quad_pipeline(DepthStencil { depth_write: false,
+		                                                 stencil_front: MARK_STENCIL,
+		                                                 stencil_back: MARK_STENCIL,
+		                                                 ..DepthStencil::simple_depth_test() })?;
+		
+		let cover_pipeline = quad_pipeline(DepthStencil { depth_compare: Compare::Always,
+		                                                  stencil_front: REFLECTED_STENCIL,
+		                                                  stencil_back: REFLECTED_STENCIL,
+		                                                  ..DepthStencil::simple_depth_test() })?;
+		
+		let corners = [[-0.5, -0.5], [0.5, -0.5], [0.5, 0.5], [-0.5, -0.5], [0.5, 0.5], [-0.5, 0.5]];
+		let quad = CpuAccessibleBuffer::from_iter(device.clone(),
+		                                          BufferUsage { vertex_buffer: true, ..BufferUsage::none() },
+		                                          false,
+		                                          corners.iter().map(|&[x, y]| Vertex::new([x, y, 0.0], [0.0, 0.0, 1.0], [0.0, 0.0])))?;
+		
+		Ok(Reflection {
+			quad,
+			transform: Matrix4::identity(),
+			mark_pipeline,
+			cover_pipeline,
+			pipelines,
+		})
+	}
+	
+	pub fn set_transform(&mut self, transform: Matrix4<f32>) {
+		self.transform = transform;
+	}
+	
+	// Projection and view of the eye reflected across the mirror, with the near plane moved onto the mirror so nothing
+	// behind it shows up in the reflection. `None` when the eye is behind the mirror and can't see it.
+	pub fn reflect(&self, projection: &Matrix4<f32>, view: &Matrix4<f32>) -> Option<Matrix4<f32>> {
+		let normal = self.transform.z.truncate().normalize();
+		let distance = normal.dot(self.transform.w.truncate());
+		let plane = normal.extend(-distance);
+		
+		let n = normal;
+		let reflection = Matrix4::new(1.0 - 2.0 * n.x * n.x,      -2.0 * n.x * n.y,      -2.0 * n.x * n.z, 0.0,
+		                                   -2.0 * n.y * n.x, 1.0 - 2.0 * n.y * n.y,      -2.0 * n.y * n.z, 0.0,
+		                                   -2.0 * n.z * n.x,      -2.0 * n.z * n.y, 1.0 - 2.0 * n.z * n.z, 0.0,
+		                                 2.0 * distance * n.x,  2.0 * distance * n.y,  2.0 * distance * n.z, 1.0);
+		
+		let reflected_view = view * reflection;
+		let view_plane = reflected_view.invert()?.transpose() * plane;
+		
+		// The reflected eye ends up behind the plane whenever the eye itself is in front of it
+		if view_plane.w >= 0.0 {
+			return None;
+		}
+		
+		Some(oblique(projection, view_plane)? * reflected_view)
+	}
+	
+	// Marks the mirror's pixels, drawn right after the hidden area
+	pub fn mark(&self, builder: AutoCommandBufferBuilder, pv: &Matrix4<f32>, dynamic_state: &DynamicState) -> Result<AutoCommandBufferBuilder, RenderError> {
+		let mvp: [[f32; 4]; 4] = (pv * self.transform).into();
+		Ok(builder.draw(self.mark_pipeline.clone(), dynamic_state, self.quad.clone(), (), mvp)?)
+	}
+	
+	// Puts the mirror's depth back where the reflection was drawn
+	pub fn cover(&self, builder: AutoCommandBufferBuilder, pv: &Matrix4<f32>, dynamic_state: &DynamicState) -> Result<AutoCommandBufferBuilder, RenderError> {
+		let mvp: [[f32; 4]; 4] = (pv * self.transform).into();
+		Ok(builder.draw(self.cover_pipeline.clone(), dynamic_state, self.quad.clone(), (), mvp)?)
+	}
+}
+
+// Replaces the near plane of a Vulkan style `projection` with `plane`, given in view space and facing away from the
+// eye, keeping the far corners of the frustum where they were. Lengyel's oblique near plane clipping for 0..1 depth.
+fn oblique(projection: &Matrix4<f32>, plane: Vector4<f32>) -> Option<Matrix4<f32>> {
+	let inverse = projection.invert()?;
+	let clip_plane = inverse.transpose() * plane;
+	let corner = inverse * Vector4::new(clip_plane.x.signum(), clip_plane.y.signum(), 1.0, 1.0);
+	let row = plane / plane.dot(corner);
+	
+	let mut projection = *projection;
+	projection.x.z = row.x;
+	projection.y.z = row.y;
+	projection.z.z = row.z;
+	projection.w.z = row.w;
+	Some(projection)
+}
+
+#[derive(Debug, Error)]
+pub enum ReflectionError {
+	#[error(display = "Depth format has no stencil to mask the mirror with.")] NoStencil,
+	#[error(display = "Failed to load shader: {}", _0)] ShaderLoadError(#[error(source, no_from)] OomError),
+	#[error(display = "{}", _0)] GraphicsPipelineCreationError(#[error(source)] GraphicsPipelineCreationError),
+	#[error(display = "{}", _0)] DeviceMemoryAllocError(#[error(source)] DeviceMemoryAllocError),
+}
@@ -0,0 +1,50 @@
+use std::time::Duration;
+
+// Fractions of the frame budget the GPU time is kept between
+const SCALE_DOWN_ABOVE: f32 = 0.9;
+const SCALE_UP_BELOW: f32 = 0.7;
+const TARGET_LOAD: f32 = 0.8;
+// Per measured frame, slow enough not to run straight back into the upper limit
+const SCALE_UP_STEP: f32 = 1.02;
+
+// Scales the rendered part of the eye images so the GPU time stays within the frame budget.
+// Scales are relative to the render scale, the eye images are allocated at the maximum.
+pub struct AdaptiveResolution {
+	min: f32,
+	max: f32,
+	scale: f32,
+	budget: Duration,
+}
+
+impl AdaptiveResolution {
+	pub fn new(min: f32, max: f32, budget: Duration) -> AdaptiveResolution {
+		AdaptiveResolution {
+			min,
+			max,
+			scale: 1.0f32.max(min).min(max),
+			budget,
+		}
+	}
+	
+	pub fn scale(&self) -> f32 {
+		self.scale
+	}
+	
+	// Part of the eye images that gets rendered to, along each axis
+	pub fn fraction(&self) -> f32 {
+		self.scale / self.max
+	}
+	
+	pub fn update(&mut self, gpu_time: Duration) {
+		let load = gpu_time.as_secs_f32() / self.budget.as_secs_f32();
+		
+		// GPU time is mostly proportional to the pixel count, which goes with the square of the scale
+		if load > SCALE_DOWN_ABOVE {
+			self.scale *= (TARGET_LOAD / load).sqrt();
+		} else if load < SCALE_UP_BELOW {
+			self.scale *= SCALE_UP_STEP;
+		}
+		
+		self.scale = self.scale.max(self.min).min(self.max);
+	}
+}
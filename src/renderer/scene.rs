@@ -0,0 +1,248 @@
+use std::cmp::Ordering;
+use err_derive::Error;
+use cgmath::{Matrix4, Point3, Vector3, SquareMatrix, InnerSpace, Transform};
+
+use crate::renderer::model::Model;
+
+// Slots get reused once a model is removed, the generation tells stale handles apart
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ModelHandle {
+	index: usize,
+	generation: u32,
+}
+
+// Models without a parent are positioned in world space, so the flat API doesn't notice the hierarchy
+struct Entry {
+	model: Model,
+	local: Matrix4<f32>,
+	// Resolved world matrix, only valid while not dirty
+	world: Matrix4<f32>,
+	dirty: bool,
+	parent: Option<usize>,
+	children: Vec<usize>,
+}
+
+struct Slot {
+	generation: u32,
+	entry: Option<Entry>,
+}
+
+pub struct Scene {
+	slots: Vec<Slot>,
+	free: Vec<usize>,
+}
+
+impl Scene {
+	pub fn new() -> Scene {
+		Scene {
+			slots: Vec::new(),
+			free: Vec::new(),
+		}
+	}
+	
+	// Starts out with an identity transformation
+	pub fn add(&mut self, model: Model) -> ModelHandle {
+		let entry = Entry {
+			model,
+			local: Matrix4::identity(),
+			world: Matrix4::identity(),
+			dirty: false,
+			parent: None,
+			children: Vec::new(),
+		};
+		
+		match self.free.pop() {
+			Some(index) => {
+				let slot = &mut self.slots[index];
+				slot.generation += 1;
+				slot.entry = Some(entry);
+				
+				ModelHandle { index, generation: slot.generation }
+			},
+			None => {
+				self.slots.push(Slot { generation: 0, entry: Some(entry) });
+				
+				ModelHandle { index: self.slots.len() - 1, generation: 0 }
+			},
+		}
+	}
+	
+	// Children are kept, they become roots and their local transforms are used as world transforms from now on
+	pub fn remove(&mut self, handle: ModelHandle) -> Result<Model, InvalidHandleError> {
+		self.entry(handle)?;
+		
+		self.detach(handle.index);
+		
+		let entry = self.slots[handle.index].entry.take().unwrap();
+		self.free.push(handle.index);
+		
+		for &child in &entry.children {
+			self.slot_entry(child).parent = None;
+			self.mark_dirty(child);
+		}
+		
+		Ok(entry.model)
+	}
+	
+	// Relative to the parent, if there is one
+	pub fn set_transform(&mut self, handle: ModelHandle, matrix: Matrix4<f32>) -> Result<(), InvalidHandleError> {
+		self.entry(handle)?.local = matrix;
+		self.mark_dirty(handle.index);
+		Ok(())
+	}
+	
+	// The local transform is kept, so the model moves along with its new parent
+	pub fn set_parent(&mut self, handle: ModelHandle, parent: Option<ModelHandle>) -> Result<(), ParentError> {
+		self.entry(handle)?;
+		
+		if let Some(parent) = parent {
+			self.entry(parent)?;
+			
+			// Walking up from the new parent must not run into the model itself
+			let mut ancestor = Some(parent.index);
+			while let Some(index) = ancestor {
+				if index == handle.index {
+					return Err(ParentError::Cycle);
+				}
+				
+				ancestor = self.slot_entry(index).parent;
+			}
+		}
+		
+		self.detach(handle.index);
+		
+		if let Some(parent) = parent {
+			self.slot_entry(parent.index).children.push(handle.index);
+		}
+		
+		self.slot_entry(handle.index).parent = parent.map(|parent| parent.index);
+		self.mark_dirty(handle.index);
+		
+		Ok(())
+	}
+	
+	pub fn world_transform(&mut self, handle: ModelHandle) -> Result<Matrix4<f32>, InvalidHandleError> {
+		self.entry(handle)?;
+		Ok(self.resolve(handle.index))
+	}
+	
+	pub fn set_visible(&mut self, handle: ModelHandle, visible: bool) -> Result<(), InvalidHandleError> {
+		self.entry(handle)?.model.set_visible(visible);
+		Ok(())
+	}
+	
+	// Models share their buffers when cloned, so this is cheap.
+	// Only world matrices of subtrees that changed since the last call get recomputed.
+	pub fn models(&mut self) -> Vec<(Model, Matrix4<f32>)> {
+		for index in 0 .. self.slots.len() {
+			if self.slots[index].entry.is_some() {
+				self.resolve(index);
+			}
+		}
+		
+		self.slots.iter()
+		          .filter_map(|slot| slot.entry.as_ref())
+		          .map(|entry| (entry.model.clone(), entry.world))
+		          .collect()
+	}
+	
+	// Nearest model whose bounding box the ray hits, with the distance along `direction`. Boxes are tested in model space,
+	// so they stay tight around rotated models. Invisible models and models still loading can't be hit.
+	pub fn raycast(&mut self, origin: Point3<f32>, direction: Vector3<f32>) -> Option<(ModelHandle, f32)> {
+		for index in 0 .. self.slots.len() {
+			if self.slots[index].entry.is_some() {
+				self.resolve(index);
+			}
+		}
+		
+		// Zero or infinite directions have no usable normalization
+		let length2 = direction.magnitude2();
+		if length2 == 0.0 || !length2.is_finite() {
+			return None;
+		}
+		
+		let direction = direction.normalize();
+		
+		self.slots.iter()
+		          .enumerate()
+		          .filter_map(|(index, slot)| {
+			          let entry = slot.entry.as_ref()?;
+			          if !entry.model.visible() || !entry.model.loaded() {
+				          return None;
+			          }
+			
+			          // The model space direction isn't normalized, so distances along it are still in world units
+			          let inverse = entry.world.invert()?;
+			          let distance = entry.model.aabb()?.intersect_ray(inverse.transform_point(origin), inverse.transform_vector(direction))?;
+			
+			          Some((ModelHandle { index, generation: slot.generation }, distance))
+		          })
+		          .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))
+	}
+	
+	pub fn iter(&self) -> impl Iterator<Item = &Model> {
+		self.slots.iter()
+		          .filter_map(|slot| slot.entry.as_ref())
+		          .map(|entry| &entry.model)
+	}
+	
+	fn resolve(&mut self, index: usize) -> Matrix4<f32> {
+		let entry = self.slot_entry(index);
+		if !entry.dirty {
+			return entry.world;
+		}
+		
+		let local = entry.local;
+		let parent_world = match entry.parent {
+			Some(parent) => self.resolve(parent),
+			None => Matrix4::identity(),
+		};
+		
+		let entry = self.slot_entry(index);
+		entry.world = parent_world * local;
+		entry.dirty = false;
+		entry.world
+	}
+	
+	// A dirty model's children are dirty already, so the walk can stop there
+	fn mark_dirty(&mut self, index: usize) {
+		let entry = self.slot_entry(index);
+		if entry.dirty {
+			return;
+		}
+		
+		entry.dirty = true;
+		
+		for child in entry.children.clone() {
+			self.mark_dirty(child);
+		}
+	}
+	
+	fn detach(&mut self, index: usize) {
+		if let Some(parent) = self.slot_entry(index).parent.take() {
+			self.slot_entry(parent).children.retain(|&child| child != index);
+		}
+	}
+	
+	// For indices taken from other entries, which always point at live slots
+	fn slot_entry(&mut self, index: usize) -> &mut Entry {
+		self.slots[index].entry.as_mut().unwrap()
+	}
+	
+	fn entry(&mut self, handle: ModelHandle) -> Result<&mut Entry, InvalidHandleError> {
+		match self.slots.get_mut(handle.index) {
+			Some(Slot { generation, entry: Some(entry) }) if *generation == handle.generation => Ok(entry),
+			_ => Err(InvalidHandleError),
+		}
+	}
+}
+
+#[derive(Debug, Error)]
+#[error(display = "Model has already been removed from the scene.")]
+pub struct InvalidHandleError;
+
+#[derive(Debug, Error)]
+pub enum ParentError {
+	#[error(display = "{}", _0)] InvalidHandleError(#[error(source)] InvalidHandleError),
+	#[error(display = "Model can't be parented to one of its own descendants.")] Cycle,
+}
@@ -0,0 +1,297 @@
+use std::mem;
+use std::sync::Arc;
+use rayon::prelude::*;
+use vulkano::device::{Device, DeviceOwned, Queue};
+use vulkano::instance::QueueFamily;
+use vulkano::framebuffer::{Subpass, RenderPassAbstract, FramebufferAbstract};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBuffer, CommandBufferExecError, DynamicState};
+#[cfg(feature = "indirect-draw")]
+use vulkano::command_buffer::DrawIndexedIndirectCommand;
+use vulkano::command_buffer::pool::standard::{StandardCommandPoolAlloc, StandardCommandPoolBuilder};
+use vulkano::command_buffer::synced::{SyncCommandBufferBuilder, SyncCommandBuffer};
+use vulkano::command_buffer::sys::{UnsafeCommandBuffer, Kind, KindSecondaryRenderPass, KindOcclusionQuery, Flags};
+use vulkano::descriptor::descriptor::ShaderStages;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::buffer::{BufferAccess, TypedBufferAccess};
+use vulkano::image::{ImageAccess, ImageLayout};
+use vulkano::pipeline::input_assembly::IndexType;
+use vulkano::query::QueryPipelineStatisticFlags;
+use vulkano::sync::{GpuFuture, AccessCheckError, PipelineStages, AccessFlagBits};
+use cgmath::Matrix4;
+
+use crate::renderer::{RenderError, PipelineType};
+use crate::renderer::model::{Model, Indices};
+use crate::renderer::transforms::TransformSet;
+#[cfg(feature = "indirect-draw")]
+use crate::renderer::indirect::{Indirect, IndirectDraws};
+
+// Depth prepass and models of an eye, in the order they're executed in
+pub type SceneCommands = (Vec<Arc<ModelCommandBuffer>>, Vec<Arc<ModelCommandBuffer>>);
+
+// Fewer models aren't worth a command buffer and a trip to another thread
+const MIN_CHUNK: usize = 32;
+
+// What the worker threads need to record an eye's models into secondary command buffers, `Renderer` itself can't be
+// shared between threads.
+pub struct EyeDraws<'a> {
+	pub device: &'a Arc<Device>,
+	pub family: QueueFamily<'a>,
+	pub subpass: Subpass<Arc<dyn RenderPassAbstract + Send + Sync>>,
+	pub scene: &'a [(Model, Matrix4<f32>)],
+	pub joint_sets: &'a [Arc<dyn DescriptorSet + Send + Sync>],
+	pub set: Arc<dyn DescriptorSet + Send + Sync>,
+	pub view_set: Arc<dyn DescriptorSet + Send + Sync>,
+	// Transforms of `scene`, in the same order
+	pub transforms: Arc<TransformSet>,
+	pub pipeline: &'a Arc<PipelineType>,
+	pub transparent_pipeline: &'a Arc<PipelineType>,
+	pub points_pipeline: &'a Arc<PipelineType>,
+	pub prepass_pipeline: &'a Arc<PipelineType>,
+	pub dynamic_state: &'a DynamicState,
+	// Batches opaque models into indirect draws when set
+	#[cfg(feature = "indirect-draw")]
+	pub indirect: Option<&'a IndirectDraws<'a>>,
+}
+
+// Model draws, recorded below `AutoCommandBufferBuilder` because it can't pass the dynamic offsets of the transforms
+pub struct ModelCommandBuffer(SyncCommandBuffer<StandardCommandPoolAlloc>);
+
+// Builds a `ModelCommandBuffer`, only binding what changed since the previous model
+pub struct ModelRecorder {
+	builder: SyncCommandBufferBuilder<StandardCommandPoolBuilder>,
+	pipeline: Option<Arc<PipelineType>>,
+	sets: Vec<Arc<dyn DescriptorSet + Send + Sync>>,
+}
+
+impl<'a> EyeDraws<'a> {
+	// Empty secondary command buffer for the eye's render pass
+	pub fn builder(&self) -> Result<AutoCommandBufferBuilder, RenderError> {
+		Ok(AutoCommandBufferBuilder::secondary_graphics_one_time_submit(self.device.clone(), self.family, self.subpass.clone())?)
+	}
+	
+	// `reusable` command buffers can be executed again in later frames, as long as nothing they use changes
+	pub fn scene(&self, order: &[usize], prepass: bool, reusable: bool) -> Result<SceneCommands, RenderError> {
+		let prepass = if prepass { self.prepass(order, reusable)? } else { Vec::new() };
+		Ok((prepass, self.models(order, reusable)?))
+	}
+	
+	// Depth of the opaque models in `order`
+	fn prepass(&self, order: &[usize], reusable: bool) -> Result<Vec<Arc<ModelCommandBuffer>>, RenderError> {
+		self.record(order, reusable, |recorder, index| {
+			let (model, _) = &self.scene[index];
+			if model.transparent() || model.indices.is_none() { return Ok(()) }
+			
+			let sets = vec![self.view_set.clone(), self.joint_sets[index].clone()];
+			recorder.draw(self.prepass_pipeline, model, sets, &self.transforms, index, false)
+		})
+	}
+	
+	fn models(&self, order: &[usize], reusable: bool) -> Result<Vec<Arc<ModelCommandBuffer>>, RenderError> {
+		#[cfg(feature = "indirect-draw")]
+		{
+			if let Some(indirect) = self.indirect {
+				// Batched models are all opaque, so drawing them first keeps transparent ones behind them in order
+				let (batched, rest): (Vec<usize>, Vec<usize>) = order.iter().partition(|&&index| Indirect::batched(&self.scene[index].0));
+				
+				let mut recorder = ModelRecorder::new(self.device, self.family, self.subpass.clone(), reusable, self.dynamic_state)?;
+				indirect.record(&mut recorder, self.scene, &batched, |index| vec![self.scene[index].0.set.clone(), self.set.clone(), self.joint_sets[index].clone()])?;
+				
+				let mut commands = vec![recorder.build()?];
+				commands.extend(self.draw_models(&rest, reusable)?);
+				return Ok(commands);
+			}
+		}
+		
+		self.draw_models(order, reusable)
+	}
+	
+	fn draw_models(&self, order: &[usize], reusable: bool) -> Result<Vec<Arc<ModelCommandBuffer>>, RenderError> {
+		self.record(order, reusable, |recorder, index| {
+			let (model, _) = &self.scene[index];
+			let sets = vec![model.set.clone(), self.set.clone(), self.joint_sets[index].clone()];
+			
+			let pipeline = match &model.indices {
+				None => self.points_pipeline,
+				Some(_) if model.transparent() => self.transparent_pipeline,
+				Some(_) => self.pipeline,
+			};
+			
+			recorder.draw(pipeline, model, sets, &self.transforms, index, true)
+		})
+	}
+	
+	// `order` split evenly across the thread pool, one command buffer per chunk. They come back in the same order, so
+	// transparent models are still drawn back to front once they're executed.
+	fn record<F>(&self, order: &[usize], reusable: bool, draw: F) -> Result<Vec<Arc<ModelCommandBuffer>>, RenderError>
+	            where F: Fn(&mut ModelRecorder, usize) -> Result<(), RenderError> + Sync {
+		let threads = rayon::current_num_threads();
+		let chunk = ((order.len() + threads - 1) / threads).max(MIN_CHUNK);
+		
+		order.par_chunks(chunk)
+		     .map(|chunk| {
+			     let mut recorder = ModelRecorder::new(self.device, self.family, self.subpass.clone(), reusable, self.dynamic_state)?;
+			
+			     for &index in chunk {
+				     draw(&mut recorder, index)?;
+			     }
+			
+			     recorder.build()
+		     })
+		     .collect()
+	}
+}
+
+impl ModelRecorder {
+	pub fn new(device: &Arc<Device>, family: QueueFamily, subpass: Subpass<Arc<dyn RenderPassAbstract + Send + Sync>>, reusable: bool,
+	           dynamic_state: &DynamicState)
+	          -> Result<ModelRecorder, RenderError> {
+		let kind = Kind::Secondary {
+			render_pass: Some(KindSecondaryRenderPass { subpass, framebuffer: None::<Arc<dyn FramebufferAbstract + Send + Sync>> }),
+			occlusion_query: KindOcclusionQuery::Forbidden,
+			query_statistics_flags: QueryPipelineStatisticFlags::none(),
+		};
+		let flags = if reusable { Flags::None } else { Flags::OneTimeSubmit };
+		
+		let mut builder = unsafe { SyncCommandBufferBuilder::new(&Device::standard_command_pool(device, family), kind, flags)? };
+		
+		// The viewport is dynamic in all the model pipelines, so it stays set when they're bound
+		if let Some(viewports) = &dynamic_state.viewports {
+			unsafe { builder.set_viewport(0, viewports.clone().into_iter()) };
+		}
+		
+		Ok(ModelRecorder {
+			builder,
+			pipeline: None,
+			sets: Vec::new(),
+		})
+	}
+	
+	// `sets` are bound from set 0 and the transforms right after them, at the offset of the model's `index`. With
+	// `material` the model's color and emissive color go to the push constants, for pipelines with a fragment shader
+	// that reads them.
+	pub fn draw(&mut self, pipeline: &Arc<PipelineType>, model: &Model, sets: Vec<Arc<dyn DescriptorSet + Send + Sync>>,
+	            transforms: &Arc<TransformSet>, index: usize, material: bool)
+	           -> Result<(), RenderError> {
+		unsafe {
+			self.bind(pipeline, model, sets, transforms.clone(), Some(transforms.offset(index)))?;
+			
+			if material {
+				self.push_material(pipeline, model.color(), model.emissive());
+			}
+			
+			match &model.indices {
+				Some(Indices::U16(indices)) => {
+					self.builder.bind_index_buffer(indices.clone(), IndexType::U16)?;
+					self.builder.draw_indexed(indices.len() as u32, 1, 0, 0, 0);
+				},
+				Some(Indices::U32(indices)) => {
+					self.builder.bind_index_buffer(indices.clone(), IndexType::U32)?;
+					self.builder.draw_indexed(indices.len() as u32, 1, 0, 0, 0);
+				},
+				None => self.builder.draw(model.vertices.len() as u32, 1, 0, 0),
+			}
+		}
+		
+		Ok(())
+	}
+	
+	// Draws `count` commands out of `commands` with the model's vertices and indices. `draws` goes right after `sets`,
+	// it holds the transform and color each command reads.
+	#[cfg(feature = "indirect-draw")]
+	pub fn draw_indirect<B>(&mut self, pipeline: &Arc<PipelineType>, model: &Model, sets: Vec<Arc<dyn DescriptorSet + Send + Sync>>,
+	                        draws: Arc<dyn DescriptorSet + Send + Sync>, commands: B, count: u32)
+	                       -> Result<(), RenderError>
+	                       where B: BufferAccess + Send + Sync + 'static {
+		unsafe {
+			self.bind(pipeline, model, sets, draws, None)?;
+			self.push_material(pipeline, [1.0; 4], [0.0; 3]);
+			
+			match &model.indices {
+				Some(Indices::U16(indices)) => self.builder.bind_index_buffer(indices.clone(), IndexType::U16)?,
+				Some(Indices::U32(indices)) => self.builder.bind_index_buffer(indices.clone(), IndexType::U32)?,
+				None => return Ok(()),
+			}
+			
+			self.builder.draw_indexed_indirect(commands, count, mem::size_of::<DrawIndexedIndirectCommand>() as u32)?;
+		}
+		
+		Ok(())
+	}
+	
+	// Binds the pipeline and the sets that changed, `last` is always bound right after `sets`
+	unsafe fn bind(&mut self, pipeline: &Arc<PipelineType>, model: &Model, sets: Vec<Arc<dyn DescriptorSet + Send + Sync>>,
+	               last: Arc<dyn DescriptorSet + Send + Sync>, dynamic_offset: Option<u32>)
+	              -> Result<(), RenderError> {
+		if !self.pipeline.as_ref().map_or(false, |bound| Arc::ptr_eq(bound, pipeline)) {
+			self.builder.bind_pipeline_graphics(pipeline.clone());
+			self.pipeline = Some(pipeline.clone());
+			self.sets.clear();
+		}
+		
+		let first = self.sets.iter()
+		                     .zip(&sets)
+		                     .take_while(|(bound, set)| same_set(bound, set))
+		                     .count();
+		
+		let mut binder = self.builder.bind_descriptor_sets();
+		for set in &sets[first ..] {
+			binder.add(set.clone());
+		}
+		binder.add(last);
+		binder.submit(true, pipeline.clone(), first as u32, dynamic_offset.into_iter())?;
+		self.sets = sets;
+		
+		let mut vertices = self.builder.bind_vertex_buffers();
+		vertices.add(model.vertices.clone());
+		vertices.submit(0)?;
+		
+		Ok(())
+	}
+	
+	// The Material block of frag.glsl
+	unsafe fn push_material(&mut self, pipeline: &Arc<PipelineType>, color: [f32; 4], emissive: [f32; 3]) {
+		let material = [color, [emissive[0], emissive[1], emissive[2], 0.0]];
+		self.builder.push_constants(pipeline.clone(), ShaderStages { fragment: true, ..ShaderStages::none() }, 0, mem::size_of_val(&material) as u32, &material);
+	}
+	
+	pub fn build(self) -> Result<Arc<ModelCommandBuffer>, RenderError> {
+		Ok(Arc::new(ModelCommandBuffer(self.builder.build()?)))
+	}
+}
+
+// Compares the sets themselves, `Arc::ptr_eq` would also compare the vtables
+fn same_set(a: &Arc<dyn DescriptorSet + Send + Sync>, b: &Arc<dyn DescriptorSet + Send + Sync>) -> bool {
+	&**a as *const _ as *const u8 == &**b as *const _ as *const u8
+}
+
+unsafe impl CommandBuffer for ModelCommandBuffer {
+	type PoolAlloc = StandardCommandPoolAlloc;
+	
+	fn inner(&self) -> &UnsafeCommandBuffer<StandardCommandPoolAlloc> {
+		self.0.as_ref()
+	}
+	
+	fn lock_submit(&self, future: &dyn GpuFuture, queue: &Queue) -> Result<(), CommandBufferExecError> {
+		self.0.lock_submit(future, queue)
+	}
+	
+	unsafe fn unlock(&self) {
+		self.0.unlock()
+	}
+	
+	fn check_buffer_access(&self, buffer: &dyn BufferAccess, exclusive: bool, queue: &Queue)
+	                      -> Result<Option<(PipelineStages, AccessFlagBits)>, AccessCheckError> {
+		self.0.check_buffer_access(buffer, exclusive, queue)
+	}
+	
+	fn check_image_access(&self, image: &dyn ImageAccess, layout: ImageLayout, exclusive: bool, queue: &Queue)
+	                     -> Result<Option<(PipelineStages, AccessFlagBits)>, AccessCheckError> {
+		self.0.check_image_access(image, layout, exclusive, queue)
+	}
+}
+
+unsafe impl DeviceOwned for ModelCommandBuffer {
+	fn device(&self) -> &Arc<Device> {
+		self.0.device()
+	}
+}
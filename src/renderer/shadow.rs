@@ -0,0 +1,131 @@
+use std::sync::Arc;
+use std::iter;
+use err_derive::Error;
+use vulkano::device::Device;
+use vulkano::image::{StorageImage, ImageUsage, Dimensions, ImageCreationError};
+use vulkano::format::{Format, ClearValue};
+use vulkano::framebuffer::{Framebuffer, FramebufferAbstract, FramebufferCreationError, RenderPassAbstract, RenderPassCreationError};
+use vulkano::sampler::{Sampler, Filter, MipmapMode, SamplerAddressMode, SamplerCreationError};
+use vulkano::pipeline::depth_stencil::Compare;
+use cgmath::{Matrix4, Point3, Vector3, InnerSpace, EuclideanSpace};
+
+use crate::renderer::CLIP;
+use crate::renderer::model::BoundingSphere;
+
+// Guaranteed to support both depth attachments and sampling
+pub const SHADOW_FORMAT: Format = Format::D16Unorm;
+
+// Depth map of the scene as seen from the directional light, rendered before the eyes every frame.
+// Kept in the general layout, the eyes sample it from secondary command buffers that can't transition it.
+pub struct ShadowMap {
+	pub image: Arc<StorageImage<Format>>,
+	pub render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+	pub frame_buffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+	// Compares instead of returning depth, the fragment shader filters the results itself
+	pub sampler: Arc<Sampler>,
+}
+
+impl ShadowMap {
+	pub fn new(device: &Arc<Device>, size: u32) -> Result<ShadowMap, ShadowMapError> {
+		let render_pass: Arc<dyn RenderPassAbstract + Send + Sync> = Arc::new(
+			vulkano::single_pass_renderpass!(device.clone(),
+				attachments: {
+					depth: {
+						load: Clear,
+						store: Store,
+						format: SHADOW_FORMAT,
+						samples: 1,
+						initial_layout: ImageLayout::Undefined,
+						final_layout: ImageLayout::General,
+					}
+				},
+				pass: {
+					color: [],
+					depth_stencil: {depth}
+				}
+			)?
+		);
+		
+		let image = StorageImage::with_usage(device.clone(),
+		                                     Dimensions::Dim2d { width: size, height: size },
+		                                     SHADOW_FORMAT,
+		                                     ImageUsage { depth_stencil_attachment: true,
+		                                                  sampled: true,
+		                                                  ..ImageUsage::none() },
+		                                     iter::empty())?;
+		
+		let frame_buffer = Arc::new(
+			Framebuffer::start(render_pass.clone())
+			            .add(image.clone())?
+			            .build()?
+		);
+		
+		let sampler = Sampler::compare(device.clone(),
+		                               Filter::Nearest,
+		                               Filter::Nearest,
+		                               MipmapMode::Nearest,
+		                               SamplerAddressMode::ClampToEdge,
+		                               SamplerAddressMode::ClampToEdge,
+		                               SamplerAddressMode::ClampToEdge,
+		                               0.0,
+		                               1.0,
+		                               0.0,
+		                               0.0,
+		                               Compare::LessOrEqual)?;
+		
+		Ok(ShadowMap {
+			image,
+			render_pass,
+			frame_buffer,
+			sampler,
+		})
+	}
+	
+	pub fn size(&self) -> u32 {
+		self.image.dimensions().width()
+	}
+	
+	pub fn clear_values(&self) -> Vec<ClearValue> {
+		vec![ClearValue::Depth(1.0)]
+	}
+	
+	// Orthographic projection along `direction` covering `bounds`, the whole map is spent on what can cast shadows.
+	// Large scenes spread it thin, the shadows get blurrier the further apart the models are.
+	pub fn light_pv(direction: Vector3<f32>, bounds: &BoundingSphere) -> Matrix4<f32> {
+		let radius = bounds.radius.max(0.01);
+		let direction = direction.normalize();
+		let up = if direction.y.abs() > 0.99 { Vector3::unit_z() } else { Vector3::unit_y() };
+		
+		let eye = bounds.center - direction * radius;
+		let view = Matrix4::look_at_dir(eye, direction, up);
+		let projection = cgmath::ortho(-radius, radius, -radius, radius, 0.0, radius * 2.0);
+		
+		CLIP * projection * view
+	}
+}
+
+// Smallest sphere around the bounds of all spheres, not the tightest fit
+pub fn enclosing_sphere<I: IntoIterator<Item = BoundingSphere>>(spheres: I) -> Option<BoundingSphere> {
+	let mut spheres = spheres.into_iter();
+	let first = spheres.next()?;
+	let mut min = first.center - Vector3::new(first.radius, first.radius, first.radius);
+	let mut max = first.center + Vector3::new(first.radius, first.radius, first.radius);
+	
+	for sphere in spheres {
+		min = Point3::new(min.x.min(sphere.center.x - sphere.radius), min.y.min(sphere.center.y - sphere.radius), min.z.min(sphere.center.z - sphere.radius));
+		max = Point3::new(max.x.max(sphere.center.x + sphere.radius), max.y.max(sphere.center.y + sphere.radius), max.z.max(sphere.center.z + sphere.radius));
+	}
+	
+	Some(BoundingSphere {
+		center: min.midpoint(max),
+		radius: (max - min).magnitude() / 2.0,
+	})
+}
+
+#[derive(Debug, Error)]
+pub enum ShadowMapError {
+	#[error(display = "{}", _0)] RenderPassCreationError(#[error(source)] RenderPassCreationError),
+	#[error(display = "{}", _0)] ImageCreationError(#[error(source)] ImageCreationError),
+	#[error(display = "{}", _0)] FramebufferCreationError(#[error(source)] FramebufferCreationError),
+	#[error(display = "{}", _0)] SamplerCreationError(#[error(source)] SamplerCreationError),
+}
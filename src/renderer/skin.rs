@@ -0,0 +1,242 @@
+use cgmath::{Matrix4, Vector3, Quaternion, InnerSpace, SquareMatrix, VectorSpace};
+use gltf::animation::Interpolation;
+use gltf::animation::util::ReadOutputs;
+
+use crate::renderer::model::ModelError;
+
+// Size of the joint palette in the shaders, see the `Joints` block of vert.glsl
+pub const MAX_JOINTS: usize = 64;
+
+// Local transformation of a node, the part animation channels replace
+#[derive(Copy, Clone)]
+struct Pose {
+	translation: Vector3<f32>,
+	rotation: Quaternion<f32>,
+	scale: Vector3<f32>,
+}
+
+impl Pose {
+	fn matrix(&self) -> Matrix4<f32> {
+		Matrix4::from_translation(self.translation)
+		* Matrix4::from(self.rotation)
+		* Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+	}
+}
+
+enum Values {
+	Translations(Vec<Vector3<f32>>),
+	Rotations(Vec<Quaternion<f32>>),
+	Scales(Vec<Vector3<f32>>),
+}
+
+struct Channel {
+	node: usize,
+	times: Vec<f32>,
+	values: Values,
+	step: bool,
+}
+
+// Joints of a skinned glTF mesh along with the first animation of its file. Shared by the models of the mesh's
+// primitives, each of them keeps its own palette.
+pub struct Skin {
+	// Rest pose of every node in the file
+	rest: Vec<Pose>,
+	parents: Vec<Option<usize>>,
+	// All nodes, parents before their children
+	order: Vec<usize>,
+	// World matrix of the mesh's node is applied by the model matrix, joints are kept relative to it
+	mesh_inverse: Matrix4<f32>,
+	joints: Vec<usize>,
+	inverse_bind: Vec<Matrix4<f32>>,
+	channels: Vec<Channel>,
+	duration: f32,
+}
+
+impl Skin {
+	pub fn load(document: &gltf::Document, buffers: &[gltf::buffer::Data], mesh_node: &gltf::Node, skin: &gltf::Skin) -> Result<Skin, ModelError> {
+		let joints: Vec<usize> = skin.joints().map(|joint| joint.index()).collect();
+		if joints.len() > MAX_JOINTS {
+			return Err(ModelError::TooManyJoints(joints.len()));
+		}
+		
+		// Missing inverse bind matrices are identities by the spec
+		let inverse_bind = match skin.reader(|buffer| Some(&*buffers[buffer.index()])).read_inverse_bind_matrices() {
+			Some(matrices) => matrices.map(Matrix4::from).collect(),
+			None => vec![Matrix4::identity(); joints.len()],
+		};
+		
+		let rest: Vec<Pose> = document.nodes()
+		                              .map(|node| {
+			                              let (translation, rotation, scale) = node.transform().decomposed();
+			                              Pose {
+				                              translation: translation.into(),
+				                              rotation: quaternion(rotation),
+				                              scale: scale.into(),
+			                              }
+		                              })
+		                              .collect();
+		
+		let mut parents = vec![None; rest.len()];
+		for node in document.nodes() {
+			for child in node.children() {
+				parents[child.index()] = Some(node.index());
+			}
+		}
+		
+		let mut order = Vec::with_capacity(rest.len());
+		let mut pending: Vec<gltf::Node> = document.nodes().filter(|node| parents[node.index()].is_none()).collect();
+		while let Some(node) = pending.pop() {
+			order.push(node.index());
+			pending.extend(node.children());
+		}
+		
+		let mut channels = Vec::new();
+		
+		if let Some(animation) = document.animations().next() {
+			for channel in animation.channels() {
+				let reader = channel.reader(|buffer| Some(&*buffers[buffer.index()]));
+				let times: Vec<f32> = match reader.read_inputs() {
+					Some(inputs) => inputs.collect(),
+					None => continue,
+				};
+				
+				let values = match reader.read_outputs() {
+					Some(ReadOutputs::Translations(translations)) => Values::Translations(translations.map(Vector3::from).collect()),
+					Some(ReadOutputs::Rotations(rotations)) => Values::Rotations(rotations.into_f32().map(quaternion).collect()),
+					Some(ReadOutputs::Scales(scales)) => Values::Scales(scales.map(Vector3::from).collect()),
+					// Morph targets aren't supported
+					_ => continue,
+				};
+				
+				// Cubic splines store an in and out tangent around every value, only the values are used and
+				// interpolated linearly
+				let values = match channel.sampler().interpolation() {
+					Interpolation::CubicSpline => values.spline_values(),
+					_ => values,
+				};
+				
+				if times.is_empty() || values.len() < times.len() {
+					continue;
+				}
+				
+				channels.push(Channel {
+					node: channel.target().node().index(),
+					times,
+					values,
+					step: channel.sampler().interpolation() == Interpolation::Step,
+				});
+			}
+		}
+		
+		let duration = channels.iter()
+		                       .filter_map(|channel| channel.times.last())
+		                       .fold(0.0, |duration: f32, &time| duration.max(time));
+		
+		let mut skin = Skin {
+			rest,
+			parents,
+			order,
+			mesh_inverse: Matrix4::identity(),
+			joints,
+			inverse_bind,
+			channels,
+			duration,
+		};
+		
+		let mesh_world = skin.world_matrices(&skin.rest)[mesh_node.index()];
+		skin.mesh_inverse = mesh_world.invert().unwrap_or_else(Matrix4::identity);
+		
+		Ok(skin)
+	}
+	
+	// Joint matrices in the rest pose
+	pub fn rest_palette(&self) -> Vec<Matrix4<f32>> {
+		self.palette_of(&self.rest)
+	}
+	
+	// Joint matrices `time` seconds into the animation, looping over its length
+	pub fn palette(&self, time: f32) -> Vec<Matrix4<f32>> {
+		let time = if self.duration > 0.0 { time.rem_euclid(self.duration) } else { 0.0 };
+		let mut poses = self.rest.clone();
+		
+		for channel in &self.channels {
+			let pose = &mut poses[channel.node];
+			let (from, to, factor) = keyframes(&channel.times, time, channel.step);
+			
+			match &channel.values {
+				Values::Translations(values) => pose.translation = values[from].lerp(values[to], factor),
+				Values::Scales(values) => pose.scale = values[from].lerp(values[to], factor),
+				Values::Rotations(values) => {
+					// Flipping one side takes the short way around
+					let (a, b) = (values[from], values[to]);
+					let b = if a.dot(b) < 0.0 { -b } else { b };
+					pose.rotation = (a * (1.0 - factor) + b * factor).normalize();
+				},
+			}
+		}
+		
+		self.palette_of(&poses)
+	}
+	
+	fn palette_of(&self, poses: &[Pose]) -> Vec<Matrix4<f32>> {
+		let world = self.world_matrices(poses);
+		
+		self.joints.iter()
+		           .zip(&self.inverse_bind)
+		           .map(|(&joint, inverse_bind)| self.mesh_inverse * world[joint] * inverse_bind)
+		           .collect()
+	}
+	
+	fn world_matrices(&self, poses: &[Pose]) -> Vec<Matrix4<f32>> {
+		let mut world = vec![Matrix4::identity(); poses.len()];
+		
+		for &node in &self.order {
+			world[node] = match self.parents[node] {
+				Some(parent) => world[parent] * poses[node].matrix(),
+				None => poses[node].matrix(),
+			};
+		}
+		
+		world
+	}
+}
+
+impl Values {
+	fn len(&self) -> usize {
+		match self {
+			Values::Translations(values) => values.len(),
+			Values::Rotations(values) => values.len(),
+			Values::Scales(values) => values.len(),
+		}
+	}
+	
+	fn spline_values(self) -> Values {
+		fn middle<T: Copy>(values: Vec<T>) -> Vec<T> {
+			values.chunks(3).filter_map(|triplet| triplet.get(1).cloned()).collect()
+		}
+		
+		match self {
+			Values::Translations(values) => Values::Translations(middle(values)),
+			Values::Rotations(values) => Values::Rotations(middle(values)),
+			Values::Scales(values) => Values::Scales(middle(values)),
+		}
+	}
+}
+
+// Indices of the keyframes around `time` and how far it is between them, clamped to the first and last one
+fn keyframes(times: &[f32], time: f32, step: bool) -> (usize, usize, f32) {
+	match times.iter().position(|&keyframe| keyframe > time) {
+		Some(0) => (0, 0, 0.0),
+		Some(next) if !step => {
+			let (start, end) = (times[next - 1], times[next]);
+			(next - 1, next, (time - start) / (end - start))
+		},
+		Some(next) => (next - 1, next - 1, 0.0),
+		None => (times.len() - 1, times.len() - 1, 0.0),
+	}
+}
+
+// glTF stores quaternions as XYZW
+fn quaternion([x, y, z, w]: [f32; 4]) -> Quaternion<f32> {
+	Quaternion::new(w, x, y, z)
+}
@@ -0,0 +1,203 @@
+use std::sync::Arc;
+use std::path::Path;
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::{self, BufReader};
+use err_derive::Error;
+use image::{DynamicImage, GenericImageView, ImageBuffer, Pixel, Rgb};
+use image::codecs::hdr::{HdrDecoder, HdrMetadata};
+use vulkano::buffer::{ImmutableBuffer, BufferUsage};
+use vulkano::image::{ImmutableImage, Dimensions, ImageCreationError};
+use vulkano::sync::GpuFuture;
+use vulkano::format::{Format, AcceptsPixels};
+use vulkano::half::f16;
+use vulkano::memory::DeviceMemoryAllocError;
+use vulkano::sampler::Sampler;
+use vulkano::descriptor::descriptor_set::{DescriptorSet, PersistentDescriptorSet, PersistentDescriptorSetError, PersistentDescriptorSetBuildError};
+use vulkano::descriptor::PipelineLayoutAbstract;
+use cgmath::{Matrix4, Matrix3, Vector3, InnerSpace};
+
+use crate::renderer::Renderer;
+use crate::renderer::model::Vertex;
+
+// Large enough that the eye offset baked into the projections doesn't give the sky any parallax
+const SCALE: f32 = 1000.0;
+
+// Only the positions are used, they double as the sampling direction
+pub const CUBE_VERTICES: [Vertex; 8] = [
+	Vertex::new([-1.0, -1.0, -1.0], [0.0, 0.0, 0.0], [0.0, 0.0]),
+	Vertex::new([ 1.0, -1.0, -1.0], [0.0, 0.0, 0.0], [0.0, 0.0]),
+	Vertex::new([-1.0,  1.0, -1.0], [0.0, 0.0, 0.0], [0.0, 0.0]),
+	Vertex::new([ 1.0,  1.0, -1.0], [0.0, 0.0, 0.0], [0.0, 0.0]),
+	Vertex::new([-1.0, -1.0,  1.0], [0.0, 0.0, 0.0], [0.0, 0.0]),
+	Vertex::new([ 1.0, -1.0,  1.0], [0.0, 0.0, 0.0], [0.0, 0.0]),
+	Vertex::new([-1.0,  1.0,  1.0], [0.0, 0.0, 0.0], [0.0, 0.0]),
+	Vertex::new([ 1.0,  1.0,  1.0], [0.0, 0.0, 0.0], [0.0, 0.0]),
+];
+
+pub const CUBE_INDICES: [u16; 36] = [
+	0, 2, 6,  0, 6, 4, // -X
+	1, 5, 7,  1, 7, 3, // +X
+	0, 4, 5,  0, 5, 1, // -Y
+	2, 3, 7,  2, 7, 6, // +Y
+	0, 1, 3,  0, 3, 2, // -Z
+	4, 6, 7,  4, 7, 5, // +Z
+];
+
+pub struct Skybox {
+	pub vertices: Arc<ImmutableBuffer<[Vertex]>>,
+	pub indices: Arc<ImmutableBuffer<[u16]>>,
+	pub image: Arc<ImmutableImage<Format>>,
+	pub set: Arc<dyn DescriptorSet + Send + Sync>,
+}
+
+impl Skybox {
+	// Faces in +X, -X, +Y, -Y, +Z, -Z order, the returned future finishes the upload
+	pub fn new(faces: &[DynamicImage; 6], renderer: &Renderer) -> Result<(Skybox, Box<dyn GpuFuture>), SkyboxError> {
+		let size = faces[0].width();
+		if faces.iter().any(|face| face.width() != size || face.height() != size) {
+			return Err(SkyboxError::FaceSizeMismatch);
+		}
+		
+		let pixels: Vec<u8> = faces.iter().flat_map(|face| face.to_rgba().into_vec()).collect();
+		
+		// sRGB like the model textures, the eye image encodes it again on the way out
+		Skybox::upload(pixels, size, Format::R8G8B8A8Srgb, renderer)
+	}
+	
+	// Faces in the same order as for `new`, from `pixels` in `format` one face after the other
+	fn upload<P>(pixels: Vec<P>, size: u32, format: Format, renderer: &Renderer) -> Result<(Skybox, Box<dyn GpuFuture>), SkyboxError>
+	            where P: Send + Sync + Clone + 'static,
+	                  Format: AcceptsPixels<P> {
+		let queue = &renderer.queue;
+		
+		let (vertices, vertices_promise) = ImmutableBuffer::from_iter(CUBE_VERTICES.iter().cloned(),
+		                                                              BufferUsage{ vertex_buffer: true, ..BufferUsage::none() },
+		                                                              queue.clone())?;
+		
+		let (indices, indices_promise) = ImmutableBuffer::from_iter(CUBE_INDICES.iter().cloned(),
+		                                                            BufferUsage{ index_buffer: true, ..BufferUsage::none() },
+		                                                            queue.clone())?;
+		
+		let (image, image_promise) = ImmutableImage::from_iter(pixels.into_iter(),
+		                                                       Dimensions::Cubemap{ size },
+		                                                       format,
+		                                                       queue.clone())?;
+		
+		// Cube maps always clamp to the edge, the address mode doesn't matter
+		let sampler = Sampler::simple_repeat_linear_no_mipmap(queue.device().clone());
+		
+		let set = Arc::new(
+			PersistentDescriptorSet::start(renderer.skybox_pipeline.descriptor_set_layout(0).ok_or(SkyboxError::NoLayout)?.clone())
+			                        .add_sampled_image(image.clone(), sampler.clone())?
+			                        .build()?
+		);
+		
+		let future = Box::new(vertices_promise.join(indices_promise).join(image_promise));
+		
+		Ok((Skybox {
+			vertices,
+			indices,
+			image,
+			set,
+		}, future))
+	}
+	
+	pub fn load<P: AsRef<Path>>(paths: &[P; 6], renderer: &Renderer) -> Result<(Skybox, Box<dyn GpuFuture>), SkyboxError> {
+		let open = |index: usize| image::open(paths[index].as_ref());
+		
+		Skybox::new(&[open(0)?, open(1)?, open(2)?, open(3)?, open(4)?, open(5)?], renderer)
+	}
+	
+	// Resamples an equirectangular panorama into faces half as tall as the panorama
+	pub fn load_equirect<P: AsRef<Path>>(path: P, renderer: &Renderer) -> Result<(Skybox, Box<dyn GpuFuture>), SkyboxError> {
+		let panorama = image::open(path)?.to_rgba();
+		let faces = equirect_faces(&panorama);
+		let pixels: Vec<u8> = faces.iter().flat_map(|face| face.as_raw().iter().cloned()).collect();
+		
+		Skybox::upload(pixels, faces[0].width(), Format::R8G8B8A8Srgb, renderer)
+	}
+	
+	// Same as `load_equirect`, but keeps the range of a Radiance HDR (.hdr) panorama in a half float cube map, for
+	// renderers built with `RendererBuilder::hdr`. The values are linear radiance, `Renderer::set_exposure` scales them.
+	pub fn load_equirect_hdr<P: AsRef<Path>>(path: P, renderer: &Renderer) -> Result<(Skybox, Box<dyn GpuFuture>), SkyboxError> {
+		let decoder = HdrDecoder::new(BufReader::new(File::open(path)?))?;
+		let HdrMetadata { width, height, .. } = decoder.metadata();
+		let pixels = decoder.read_image_hdr()?;
+		
+		let panorama: ImageBuffer<Rgb<f32>, Vec<f32>> = ImageBuffer::from_raw(width, height, pixels.iter().flat_map(|pixel| pixel.0.iter().cloned()).collect())
+		                                                            .ok_or(SkyboxError::Truncated)?;
+		let faces = equirect_faces(&panorama);
+		let size = faces[0].width();
+		
+		let pixels: Vec<[f16; 4]> = faces.iter()
+		                                 .flat_map(|face| face.pixels())
+		                                 .map(|&Rgb([r, g, b])| [f16::from_f32(r), f16::from_f32(g), f16::from_f32(b), f16::from_f32(1.0)])
+		                                 .collect();
+		
+		Skybox::upload(pixels, size, Format::R16G16B16A16Sfloat, renderer)
+	}
+	
+	// Only the rotation of the view is kept, so the sky stays put while the head moves. Takes the projection and view
+	// of this frame and the previous one, for the velocity.
+	pub fn push_constants(&self, (projection, view): (&Matrix4<f32>, &Matrix4<f32>), (previous_projection, previous_view): (&Matrix4<f32>, &Matrix4<f32>))
+	                     -> [[[f32; 4]; 4]; 2] {
+		let pv = |projection: &Matrix4<f32>, view: &Matrix4<f32>| {
+			let rotation = Matrix3::from_cols(view.x.truncate(), view.y.truncate(), view.z.truncate());
+			(projection * Matrix4::from(rotation) * Matrix4::from_scale(SCALE)).into()
+		};
+		
+		[pv(projection, view), pv(previous_projection, previous_view)]
+	}
+}
+
+// Faces half as tall as the panorama, in the order `Skybox::new` takes them
+fn equirect_faces<P: Pixel + 'static>(panorama: &ImageBuffer<P, Vec<P::Subpixel>>) -> [ImageBuffer<P, Vec<P::Subpixel>>; 6] {
+	let size = (panorama.height() / 2).max(1);
+	
+	// Directions for the face coordinates, following the cube map face selection table
+	[
+		equirect_face(panorama, size, |s, t| Vector3::new( 1.0,  -t,  -s)),
+		equirect_face(panorama, size, |s, t| Vector3::new(-1.0,  -t,   s)),
+		equirect_face(panorama, size, |s, t| Vector3::new(   s, 1.0,   t)),
+		equirect_face(panorama, size, |s, t| Vector3::new(   s,-1.0,  -t)),
+		equirect_face(panorama, size, |s, t| Vector3::new(   s,  -t, 1.0)),
+		equirect_face(panorama, size, |s, t| Vector3::new(  -s,  -t,-1.0)),
+	]
+}
+
+// Nearest sampling, the faces have about the same resolution as the panorama anyway
+fn equirect_face<P, F>(panorama: &ImageBuffer<P, Vec<P::Subpixel>>, size: u32, direction: F) -> ImageBuffer<P, Vec<P::Subpixel>>
+                      where P: Pixel + 'static,
+                            F: Fn(f32, f32) -> Vector3<f32> {
+	let (width, height) = panorama.dimensions();
+	
+	ImageBuffer::from_fn(size, size, |x, y| {
+		let s = (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+		let t = (y as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+		let direction = direction(s, t).normalize();
+		
+		// Straight ahead (-Z) ends up in the middle of the panorama
+		let longitude = direction.x.atan2(-direction.z);
+		let latitude = direction.y.asin();
+		
+		let u = ((longitude / (2.0 * PI) + 0.5) * width as f32) as u32;
+		let v = ((0.5 - latitude / PI) * height as f32) as u32;
+		
+		*panorama.get_pixel(u.min(width - 1), v.min(height - 1))
+	})
+}
+
+
+#[derive(Debug, Error)]
+pub enum SkyboxError {
+	#[error(display = "Pipeline doesn't have layout set 0")] NoLayout,
+	#[error(display = "Skybox faces have to be square and all the same size")] FaceSizeMismatch,
+	#[error(display = "HDR image has fewer pixels than its size")] Truncated,
+	#[error(display = "{}", _0)] ImageError(#[error(source)] image::ImageError),
+	#[error(display = "{}", _0)] IoError(#[error(source)] io::Error),
+	#[error(display = "{}", _0)] DeviceMemoryAllocError(#[error(source)] DeviceMemoryAllocError),
+	#[error(display = "{}", _0)] ImageCreationError(#[error(source)] ImageCreationError),
+	#[error(display = "{}", _0)] PersistentDescriptorSetError(#[error(source)] PersistentDescriptorSetError),
+	#[error(display = "{}", _0)] PersistentDescriptorSetBuildError(#[error(source)] PersistentDescriptorSetBuildError),
+}
@@ -0,0 +1,69 @@
+use std::fs;
+use std::io;
+use std::convert::TryInto;
+use std::num::ParseFloatError;
+use std::path::Path;
+use err_derive::Error;
+
+const HEADER_SIZE: usize = 84;
+const TRIANGLE_SIZE: usize = 50;
+
+pub type Triangle = [[f32; 3]; 3];
+
+// Normals stored in the file are ignored, plenty of exporters leave them zeroed
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<Triangle>, StlError> {
+	parse(&fs::read(path)?)
+}
+
+pub fn parse(data: &[u8]) -> Result<Vec<Triangle>, StlError> {
+	// Binary files may start with "solid" too, their size is the reliable tell
+	if data.len() >= HEADER_SIZE && data.len() == HEADER_SIZE + TRIANGLE_SIZE * triangle_count(data) {
+		Ok(parse_binary(data))
+	} else if data.starts_with(b"solid") {
+		parse_ascii(&String::from_utf8_lossy(data))
+	} else {
+		Err(StlError::Truncated)
+	}
+}
+
+fn triangle_count(data: &[u8]) -> usize {
+	u32::from_le_bytes(data[80 .. 84].try_into().unwrap()) as usize
+}
+
+fn parse_binary(data: &[u8]) -> Vec<Triangle> {
+	let float = |offset: usize| f32::from_le_bytes(data[offset .. offset + 4].try_into().unwrap());
+	let point = |offset: usize| [float(offset), float(offset + 4), float(offset + 8)];
+	
+	(0 .. triangle_count(data)).map(|index| {
+		                           // Each triangle is a normal, three vertices and two bytes of attributes
+		                           let offset = HEADER_SIZE + index * TRIANGLE_SIZE + 12;
+		                           [point(offset), point(offset + 12), point(offset + 24)]
+	                           })
+	                           .collect()
+}
+
+// Only the vertex lines matter, every three of them make a triangle
+fn parse_ascii(text: &str) -> Result<Vec<Triangle>, StlError> {
+	let mut points = Vec::new();
+	let mut tokens = text.split_whitespace();
+	
+	while let Some(token) = tokens.next() {
+		if token != "vertex" { continue }
+		
+		let mut coordinate = || tokens.next().ok_or(StlError::Truncated)?.parse::<f32>().map_err(StlError::from);
+		points.push([coordinate()?, coordinate()?, coordinate()?]);
+	}
+	
+	if points.len() % 3 != 0 {
+		return Err(StlError::Truncated);
+	}
+	
+	Ok(points.chunks(3).map(|triangle| [triangle[0], triangle[1], triangle[2]]).collect())
+}
+
+#[derive(Debug, Error)]
+pub enum StlError {
+	#[error(display = "STL file is truncated or not an STL file")] Truncated,
+	#[error(display = "{}", _0)] IoError(#[error(source)] io::Error),
+	#[error(display = "Invalid number in ASCII STL: {}", _0)] ParseFloatError(#[error(source)] ParseFloatError),
+}
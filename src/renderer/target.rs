@@ -0,0 +1,206 @@
+use std::sync::Arc;
+use vulkano::instance::{Instance, RawInstanceExtensions, PhysicalDevice};
+use vulkano::device::RawDeviceExtensions;
+use openvr::{System, Compositor, Eye, tracked_device_index, property};
+use openvr::system::HiddenAreaMeshType;
+use cgmath::{Matrix4, Matrix, SquareMatrix, Deg, PerspectiveFov};
+
+use crate::openvr_vulkan::*;
+
+// Everything the renderer needs to know about where the eye images end up
+pub trait Target {
+	fn instance_extensions(&self) -> RawInstanceExtensions;
+	fn device_extensions(&self, physical: &PhysicalDevice) -> RawDeviceExtensions;
+	// Device the target is connected to, if it can tell
+	fn physical_device<'a>(&self, instance: &'a Arc<Instance>) -> Option<PhysicalDevice<'a>>;
+	fn render_target_size(&self) -> (u32, u32);
+	// OpenGL style projection
+	fn projection(&self, eye: Eye, near: f32, far: f32) -> Matrix4<f32>;
+	fn eye_to_head(&self, eye: Eye) -> Matrix4<f32>;
+	// Triangles covering the parts of the image that can't be seen, in 0..1 image coordinates
+	fn hidden_area_mesh(&self, eye: Eye) -> Vec<[f32; 2]>;
+	// Refresh rate of the display, if it can tell
+	fn refresh_rate(&self) -> Option<f32>;
+	// Targets without a compositor render a single view, shown in the mirror window if there is one
+	fn into_compositor(self) -> Option<Compositor>;
+	fn needs_mirror(&self) -> bool;
+}
+
+pub struct OpenVRTarget<'a> {
+	system: &'a System,
+	compositor: Compositor,
+}
+
+impl<'a> OpenVRTarget<'a> {
+	pub fn new(system: &'a System, compositor: Compositor) -> OpenVRTarget<'a> {
+		OpenVRTarget {
+			system,
+			compositor,
+		}
+	}
+}
+
+impl<'a> Target for OpenVRTarget<'a> {
+	fn instance_extensions(&self) -> RawInstanceExtensions {
+		RawInstanceExtensions::new(self.compositor.vulkan_instance_extensions_required())
+	}
+	
+	fn device_extensions(&self, physical: &PhysicalDevice) -> RawDeviceExtensions {
+		RawDeviceExtensions::new(vulkan_device_extensions_required(&self.compositor, physical))
+	}
+	
+	fn physical_device<'b>(&self, instance: &'b Arc<Instance>) -> Option<PhysicalDevice<'b>> {
+		self.system.vulkan_output_device(instance.as_ptr())
+		           .and_then(|ptr| PhysicalDevice::enumerate(instance).find(|physical| physical.as_ptr() == ptr))
+	}
+	
+	fn render_target_size(&self) -> (u32, u32) {
+		self.system.recommended_render_target_size()
+	}
+	
+	fn projection(&self, eye: Eye, near: f32, far: f32) -> Matrix4<f32> {
+		Matrix4::from(self.system.projection_matrix(eye, near, far)).transpose()
+	}
+	
+	fn eye_to_head(&self, eye: Eye) -> Matrix4<f32> {
+		mat4(&self.system.eye_to_head_transform(eye))
+	}
+	
+	fn hidden_area_mesh(&self, eye: Eye) -> Vec<[f32; 2]> {
+		self.system.hidden_area_mesh(eye, HiddenAreaMeshType::Standard)
+		           .map_or_else(Vec::new, |mesh| mesh.to_vec())
+	}
+	
+	fn refresh_rate(&self) -> Option<f32> {
+		self.system.float_tracked_device_property(tracked_device_index::HMD, property::DisplayFrequency_Float).ok()
+	}
+	
+	fn into_compositor(self) -> Option<Compositor> {
+		Some(self.compositor)
+	}
+	
+	fn needs_mirror(&self) -> bool {
+		false
+	}
+}
+
+pub struct FlatTarget {
+	size: (u32, u32),
+	fov: Deg<f32>,
+}
+
+impl FlatTarget {
+	// `fov` is the vertical field of view
+	pub fn new(size: (u32, u32), fov: Deg<f32>) -> FlatTarget {
+		FlatTarget {
+			size,
+			fov,
+		}
+	}
+}
+
+impl Target for FlatTarget {
+	fn instance_extensions(&self) -> RawInstanceExtensions {
+		(&vulkano_win::required_extensions()).into()
+	}
+	
+	fn device_extensions(&self, _physical: &PhysicalDevice) -> RawDeviceExtensions {
+		RawDeviceExtensions::none()
+	}
+	
+	fn physical_device<'b>(&self, _instance: &'b Arc<Instance>) -> Option<PhysicalDevice<'b>> {
+		None
+	}
+	
+	fn render_target_size(&self) -> (u32, u32) {
+		self.size
+	}
+	
+	fn projection(&self, _eye: Eye, near: f32, far: f32) -> Matrix4<f32> {
+		PerspectiveFov { fovy: self.fov.into(),
+		                 aspect: self.size.0 as f32 / self.size.1 as f32,
+		                 near,
+		                 far }.into()
+	}
+	
+	fn eye_to_head(&self, _eye: Eye) -> Matrix4<f32> {
+		Matrix4::identity()
+	}
+	
+	fn hidden_area_mesh(&self, _eye: Eye) -> Vec<[f32; 2]> {
+		Vec::new()
+	}
+	
+	fn refresh_rate(&self) -> Option<f32> {
+		None
+	}
+	
+	fn into_compositor(self) -> Option<Compositor> {
+		None
+	}
+	
+	fn needs_mirror(&self) -> bool {
+		true
+	}
+}
+
+// Renders without a window or HMD, the images can only be read back with `Renderer::read_eye`
+pub struct HeadlessTarget {
+	size: (u32, u32),
+	fov: Deg<f32>,
+}
+
+impl HeadlessTarget {
+	// `fov` is the vertical field of view
+	pub fn new(size: (u32, u32), fov: Deg<f32>) -> HeadlessTarget {
+		HeadlessTarget {
+			size,
+			fov,
+		}
+	}
+}
+
+impl Target for HeadlessTarget {
+	fn instance_extensions(&self) -> RawInstanceExtensions {
+		RawInstanceExtensions::none()
+	}
+	
+	fn device_extensions(&self, _physical: &PhysicalDevice) -> RawDeviceExtensions {
+		RawDeviceExtensions::none()
+	}
+	
+	fn physical_device<'b>(&self, _instance: &'b Arc<Instance>) -> Option<PhysicalDevice<'b>> {
+		None
+	}
+	
+	fn render_target_size(&self) -> (u32, u32) {
+		self.size
+	}
+	
+	fn projection(&self, _eye: Eye, near: f32, far: f32) -> Matrix4<f32> {
+		PerspectiveFov { fovy: self.fov.into(),
+		                 aspect: self.size.0 as f32 / self.size.1 as f32,
+		                 near,
+		                 far }.into()
+	}
+	
+	fn eye_to_head(&self, _eye: Eye) -> Matrix4<f32> {
+		Matrix4::identity()
+	}
+	
+	fn hidden_area_mesh(&self, _eye: Eye) -> Vec<[f32; 2]> {
+		Vec::new()
+	}
+	
+	fn refresh_rate(&self) -> Option<f32> {
+		None
+	}
+	
+	fn into_compositor(self) -> Option<Compositor> {
+		None
+	}
+	
+	fn needs_mirror(&self) -> bool {
+		false
+	}
+}
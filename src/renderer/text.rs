@@ -0,0 +1,262 @@
+use std::sync::Arc;
+use err_derive::Error;
+use vulkano::device::Queue;
+use vulkano::buffer::CpuBufferPool;
+use vulkano::image::{ImmutableImage, Dimensions, ImageCreationError};
+use vulkano::format::Format;
+use vulkano::framebuffer::{Subpass, RenderPassAbstract};
+use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineCreationError};
+use vulkano::pipeline::vertex::SingleBufferDefinition;
+use vulkano::pipeline::depth_stencil::{DepthStencil, Stencil};
+use vulkano::sampler::{Sampler, Filter, MipmapMode, SamplerAddressMode, SamplerCreationError};
+use vulkano::descriptor::descriptor_set::{DescriptorSet, PersistentDescriptorSet, PersistentDescriptorSetError, PersistentDescriptorSetBuildError};
+use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::sync::GpuFuture;
+use vulkano::OomError;
+use cgmath::{Matrix4, Point3, Transform};
+
+use crate::shaders;
+
+// Printable ASCII, everything else is drawn as '?'
+const FIRST_CHAR: u8 = b' ';
+const LAST_CHAR: u8 = b'~';
+const GLYPH_SIZE: u32 = 8;
+const ATLAS_COLUMNS: u32 = 16;
+const ATLAS_ROWS: u32 = 6;
+
+#[derive(Default, Copy, Clone)]
+pub struct TextVertex {
+	pos: [f32; 3],
+	uv: [f32; 2],
+}
+
+vulkano::impl_vertex!(TextVertex, pos, uv);
+
+type TextPipelineType = GraphicsPipeline<
+	SingleBufferDefinition<TextVertex>,
+	Box<dyn PipelineLayoutAbstract + Send + Sync>,
+	Arc<dyn RenderPassAbstract + Send + Sync>
+>;
+
+// Bitmap font text in world space, hidden behind models like the debug lines
+pub struct Text {
+	pub pipeline: Arc<TextPipelineType>,
+	// Font atlas, 16 glyphs per row
+	pub set: Arc<dyn DescriptorSet + Send + Sync>,
+	// Glyph quads of the next frame, six vertices each
+	pub pending: Vec<TextVertex>,
+	// All pending text of a frame ends up in one chunk
+	pub buffer: CpuBufferPool<TextVertex>,
+}
+
+impl Text {
+	// The returned future finishes the upload of the font atlas
+	pub fn new(queue: &Arc<Queue>, render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>, visible_stencil: Stencil) -> Result<(Text, Box<dyn GpuFuture>), TextError> {
+		let device = queue.device();
+		
+		let vs = shaders::text_vert::Shader::load(device.clone()).map_err(TextError::ShaderLoadError)?;
+		let fs = shaders::text_frag::Shader::load(device.clone()).map_err(TextError::ShaderLoadError)?;
+		
+		// Not culled, so text stays readable (mirrored) from behind
+		let pipeline = Arc::new(
+			GraphicsPipeline::start()
+			                 .vertex_input_single_buffer::<TextVertex>()
+			                 .vertex_shader(vs.main_entry_point(), ())
+			                 .viewports_dynamic_scissors_irrelevant(1)
+			                 .fragment_shader(fs.main_entry_point(), ())
+			                 .cull_mode_disabled()
+			                 .depth_stencil(DepthStencil { stencil_front: visible_stencil,
+			                                               stencil_back: visible_stencil,
+			                                               ..DepthStencil::simple_depth_test() })
+			                 .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+			                 .build(device.clone())?
+		);
+		
+		let width = ATLAS_COLUMNS * GLYPH_SIZE;
+		let height = ATLAS_ROWS * GLYPH_SIZE;
+		let pixels = (0 .. width * height).map(|index| {
+			                                  let (x, y) = (index % width, index / width);
+			                                  // The last cell of the atlas has no glyph
+			                                  match FONT.get((y / GLYPH_SIZE * ATLAS_COLUMNS + x / GLYPH_SIZE) as usize) {
+				                                  Some(glyph) if glyph[(y % GLYPH_SIZE) as usize] & (1 << (x % GLYPH_SIZE)) != 0 => 255u8,
+				                                  _ => 0,
+			                                  }
+		                                  });
+		
+		let (atlas, atlas_promise) = ImmutableImage::from_iter(pixels,
+		                                                       Dimensions::Dim2d{ width, height },
+		                                                       Format::R8Unorm,
+		                                                       queue.clone())?;
+		
+		// Nearest, glyphs keep their hard pixel edges up close
+		let sampler = Sampler::new(device.clone(),
+		                           Filter::Nearest,
+		                           Filter::Nearest,
+		                           MipmapMode::Nearest,
+		                           SamplerAddressMode::ClampToEdge,
+		                           SamplerAddressMode::ClampToEdge,
+		                           SamplerAddressMode::ClampToEdge,
+		                           0.0,
+		                           1.0,
+		                           0.0,
+		                           0.0)?;
+		
+		let set = Arc::new(
+			PersistentDescriptorSet::start(pipeline.descriptor_set_layout(1).ok_or(TextError::NoLayout)?.clone())
+			                        .add_sampled_image(atlas, sampler)?
+			                        .build()?
+		);
+		
+		Ok((Text {
+			pipeline,
+			set,
+			pending: Vec::new(),
+			buffer: CpuBufferPool::vertex_buffer(device.clone()),
+		}, Box::new(atlas_promise)))
+	}
+	
+	// Glyphs are one unit wide and tall, starting at the origin and going along +X, facing +Z. Lines go down along -Y.
+	pub fn push(&mut self, text: &str, transform: &Matrix4<f32>) {
+		let (mut column, mut line) = (0.0, 0.0);
+		
+		for char in text.chars() {
+			match char {
+				'\n' => {
+					column = 0.0;
+					line += 1.0;
+					continue;
+				},
+				' ' => {},
+				_ => {
+					let code = if char.is_ascii() && (FIRST_CHAR ..= LAST_CHAR).contains(&(char as u8)) { char as u8 } else { b'?' };
+					let index = (code - FIRST_CHAR) as u32;
+					let u = (index % ATLAS_COLUMNS) as f32 / ATLAS_COLUMNS as f32;
+					let v = (index / ATLAS_COLUMNS) as f32 / ATLAS_ROWS as f32;
+					let (du, dv) = (1.0 / ATLAS_COLUMNS as f32, 1.0 / ATLAS_ROWS as f32);
+					
+					let corner = |x: f32, y: f32, u: f32, v: f32| TextVertex {
+						pos: transform.transform_point(Point3::new(column + x, -line - y, 0.0)).into(),
+						uv: [u, v],
+					};
+					
+					let (top_left, top_right) = (corner(0.0, 0.0, u, v), corner(1.0, 0.0, u + du, v));
+					let (bottom_left, bottom_right) = (corner(0.0, 1.0, u, v + dv), corner(1.0, 1.0, u + du, v + dv));
+					
+					self.pending.extend_from_slice(&[top_left, bottom_left, bottom_right, top_left, bottom_right, top_right]);
+				},
+			}
+			
+			column += 1.0;
+		}
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum TextError {
+	#[error(display = "Pipeline doesn't have layout set 1")] NoLayout,
+	#[error(display = "Failed to load shader: {}", _0)] ShaderLoadError(#[error(source, no_from)] OomError),
+	#[error(display = "{}", _0)] GraphicsPipelineCreationError(#[error(source)] GraphicsPipelineCreationError),
+	#[error(display = "{}", _0)] ImageCreationError(#[error(source)] ImageCreationError),
+	#[error(display = "{}", _0)] SamplerCreationError(#[error(source)] SamplerCreationError),
+	#[error(display = "{}", _0)] PersistentDescriptorSetError(#[error(source)] PersistentDescriptorSetError),
+	#[error(display = "{}", _0)] PersistentDescriptorSetBuildError(#[error(source)] PersistentDescriptorSetBuildError),
+}
+
+// font8x8 by Daniel Hepper (public domain), rows top to bottom, lowest bit is the leftmost pixel
+const FONT: [[u8; 8]; (LAST_CHAR - FIRST_CHAR + 1) as usize] = [
+	[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+	[0x18, 0x3C, 0x3C, 0x18, 0x18, 0x00, 0x18, 0x00], // '!'
+	[0x36, 0x36, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '"'
+	[0x36, 0x36, 0x7F, 0x36, 0x7F, 0x36, 0x36, 0x00], // '#'
+	[0x0C, 0x3E, 0x03, 0x1E, 0x30, 0x1F, 0x0C, 0x00], // '$'
+	[0x00, 0x63, 0x33, 0x18, 0x0C, 0x66, 0x63, 0x00], // '%'
+	[0x1C, 0x36, 0x1C, 0x6E, 0x3B, 0x33, 0x6E, 0x00], // '&'
+	[0x06, 0x06, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00], // '''
+	[0x18, 0x0C, 0x06, 0x06, 0x06, 0x0C, 0x18, 0x00], // '('
+	[0x06, 0x0C, 0x18, 0x18, 0x18, 0x0C, 0x06, 0x00], // ')'
+	[0x00, 0x66, 0x3C, 0xFF, 0x3C, 0x66, 0x00, 0x00], // '*'
+	[0x00, 0x0C, 0x0C, 0x3F, 0x0C, 0x0C, 0x00, 0x00], // '+'
+	[0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C, 0x06], // ','
+	[0x00, 0x00, 0x00, 0x3F, 0x00, 0x00, 0x00, 0x00], // '-'
+	[0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C, 0x00], // '.'
+	[0x60, 0x30, 0x18, 0x0C, 0x06, 0x03, 0x01, 0x00], // '/'
+	[0x3E, 0x63, 0x73, 0x7B, 0x6F, 0x67, 0x3E, 0x00], // '0'
+	[0x0C, 0x0E, 0x0C, 0x0C, 0x0C, 0x0C, 0x3F, 0x00], // '1'
+	[0x1E, 0x33, 0x30, 0x1C, 0x06, 0x33, 0x3F, 0x00], // '2'
+	[0x1E, 0x33, 0x30, 0x1C, 0x30, 0x33, 0x1E, 0x00], // '3'
+	[0x38, 0x3C, 0x36, 0x33, 0x7F, 0x30, 0x78, 0x00], // '4'
+	[0x3F, 0x03, 0x1F, 0x30, 0x30, 0x33, 0x1E, 0x00], // '5'
+	[0x1C, 0x06, 0x03, 0x1F, 0x33, 0x33, 0x1E, 0x00], // '6'
+	[0x3F, 0x33, 0x30, 0x18, 0x0C, 0x0C, 0x0C, 0x00], // '7'
+	[0x1E, 0x33, 0x33, 0x1E, 0x33, 0x33, 0x1E, 0x00], // '8'
+	[0x1E, 0x33, 0x33, 0x3E, 0x30, 0x18, 0x0E, 0x00], // '9'
+	[0x00, 0x0C, 0x0C, 0x00, 0x00, 0x0C, 0x0C, 0x00], // ':'
+	[0x00, 0x0C, 0x0C, 0x00, 0x00, 0x0C, 0x0C, 0x06], // ';'
+	[0x18, 0x0C, 0x06, 0x03, 0x06, 0x0C, 0x18, 0x00], // '<'
+	[0x00, 0x00, 0x3F, 0x00, 0x00, 0x3F, 0x00, 0x00], // '='
+	[0x06, 0x0C, 0x18, 0x30, 0x18, 0x0C, 0x06, 0x00], // '>'
+	[0x1E, 0x33, 0x30, 0x18, 0x0C, 0x00, 0x0C, 0x00], // '?'
+	[0x3E, 0x63, 0x7B, 0x7B, 0x7B, 0x03, 0x1E, 0x00], // '@'
+	[0x0C, 0x1E, 0x33, 0x33, 0x3F, 0x33, 0x33, 0x00], // 'A'
+	[0x3F, 0x66, 0x66, 0x3E, 0x66, 0x66, 0x3F, 0x00], // 'B'
+	[0x3C, 0x66, 0x03, 0x03, 0x03, 0x66, 0x3C, 0x00], // 'C'
+	[0x1F, 0x36, 0x66, 0x66, 0x66, 0x36, 0x1F, 0x00], // 'D'
+	[0x7F, 0x46, 0x16, 0x1E, 0x16, 0x46, 0x7F, 0x00], // 'E'
+	[0x7F, 0x46, 0x16, 0x1E, 0x16, 0x06, 0x0F, 0x00], // 'F'
+	[0x3C, 0x66, 0x03, 0x03, 0x73, 0x66, 0x7C, 0x00], // 'G'
+	[0x33, 0x33, 0x33, 0x3F, 0x33, 0x33, 0x33, 0x00], // 'H'
+	[0x1E, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // 'I'
+	[0x78, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1E, 0x00], // 'J'
+	[0x67, 0x66, 0x36, 0x1E, 0x36, 0x66, 0x67, 0x00], // 'K'
+	[0x0F, 0x06, 0x06, 0x06, 0x46, 0x66, 0x7F, 0x00], // 'L'
+	[0x63, 0x77, 0x7F, 0x7F, 0x6B, 0x63, 0x63, 0x00], // 'M'
+	[0x63, 0x67, 0x6F, 0x7B, 0x73, 0x63, 0x63, 0x00], // 'N'
+	[0x1C, 0x36, 0x63, 0x63, 0x63, 0x36, 0x1C, 0x00], // 'O'
+	[0x3F, 0x66, 0x66, 0x3E, 0x06, 0x06, 0x0F, 0x00], // 'P'
+	[0x1E, 0x33, 0x33, 0x33, 0x3B, 0x1E, 0x38, 0x00], // 'Q'
+	[0x3F, 0x66, 0x66, 0x3E, 0x36, 0x66, 0x67, 0x00], // 'R'
+	[0x1E, 0x33, 0x07, 0x0E, 0x38, 0x33, 0x1E, 0x00], // 'S'
+	[0x3F, 0x2D, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // 'T'
+	[0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x3F, 0x00], // 'U'
+	[0x33, 0x33, 0x33, 0x33, 0x33, 0x1E, 0x0C, 0x00], // 'V'
+	[0x63, 0x63, 0x63, 0x6B, 0x7F, 0x77, 0x63, 0x00], // 'W'
+	[0x63, 0x63, 0x36, 0x1C, 0x1C, 0x36, 0x63, 0x00], // 'X'
+	[0x33, 0x33, 0x33, 0x1E, 0x0C, 0x0C, 0x1E, 0x00], // 'Y'
+	[0x7F, 0x63, 0x31, 0x18, 0x4C, 0x66, 0x7F, 0x00], // 'Z'
+	[0x1E, 0x06, 0x06, 0x06, 0x06, 0x06, 0x1E, 0x00], // '['
+	[0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x40, 0x00], // '\'
+	[0x1E, 0x18, 0x18, 0x18, 0x18, 0x18, 0x1E, 0x00], // ']'
+	[0x08, 0x1C, 0x36, 0x63, 0x00, 0x00, 0x00, 0x00], // '^'
+	[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF], // '_'
+	[0x0C, 0x0C, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00], // '`'
+	[0x00, 0x00, 0x1E, 0x30, 0x3E, 0x33, 0x6E, 0x00], // 'a'
+	[0x07, 0x06, 0x06, 0x3E, 0x66, 0x66, 0x3B, 0x00], // 'b'
+	[0x00, 0x00, 0x1E, 0x33, 0x03, 0x33, 0x1E, 0x00], // 'c'
+	[0x38, 0x30, 0x30, 0x3E, 0x33, 0x33, 0x6E, 0x00], // 'd'
+	[0x00, 0x00, 0x1E, 0x33, 0x3F, 0x03, 0x1E, 0x00], // 'e'
+	[0x1C, 0x36, 0x06, 0x0F, 0x06, 0x06, 0x0F, 0x00], // 'f'
+	[0x00, 0x00, 0x6E, 0x33, 0x33, 0x3E, 0x30, 0x1F], // 'g'
+	[0x07, 0x06, 0x36, 0x6E, 0x66, 0x66, 0x67, 0x00], // 'h'
+	[0x0C, 0x00, 0x0E, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // 'i'
+	[0x30, 0x00, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1E], // 'j'
+	[0x07, 0x06, 0x66, 0x36, 0x1E, 0x36, 0x67, 0x00], // 'k'
+	[0x0E, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // 'l'
+	[0x00, 0x00, 0x33, 0x7F, 0x7F, 0x6B, 0x63, 0x00], // 'm'
+	[0x00, 0x00, 0x1F, 0x33, 0x33, 0x33, 0x33, 0x00], // 'n'
+	[0x00, 0x00, 0x1E, 0x33, 0x33, 0x33, 0x1E, 0x00], // 'o'
+	[0x00, 0x00, 0x3B, 0x66, 0x66, 0x3E, 0x06, 0x0F], // 'p'
+	[0x00, 0x00, 0x6E, 0x33, 0x33, 0x3E, 0x30, 0x78], // 'q'
+	[0x00, 0x00, 0x3B, 0x6E, 0x66, 0x06, 0x0F, 0x00], // 'r'
+	[0x00, 0x00, 0x3E, 0x03, 0x1E, 0x30, 0x1F, 0x00], // 's'
+	[0x08, 0x0C, 0x3E, 0x0C, 0x0C, 0x2C, 0x18, 0x00], // 't'
+	[0x00, 0x00, 0x33, 0x33, 0x33, 0x33, 0x6E, 0x00], // 'u'
+	[0x00, 0x00, 0x33, 0x33, 0x33, 0x1E, 0x0C, 0x00], // 'v'
+	[0x00, 0x00, 0x63, 0x6B, 0x7F, 0x7F, 0x36, 0x00], // 'w'
+	[0x00, 0x00, 0x63, 0x36, 0x1C, 0x36, 0x63, 0x00], // 'x'
+	[0x00, 0x00, 0x33, 0x33, 0x33, 0x3E, 0x30, 0x1F], // 'y'
+	[0x00, 0x00, 0x3F, 0x19, 0x0C, 0x26, 0x3F, 0x00], // 'z'
+	[0x38, 0x0C, 0x0C, 0x07, 0x0C, 0x0C, 0x38, 0x00], // '{'
+	[0x18, 0x18, 0x18, 0x00, 0x18, 0x18, 0x18, 0x00], // '|'
+	[0x07, 0x0C, 0x0C, 0x38, 0x0C, 0x0C, 0x07, 0x00], // '}'
+	[0x6E, 0x3B, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '~'
+];
@@ -0,0 +1,143 @@
+use std::iter;
+use std::sync::Arc;
+use err_derive::Error;
+use image::RgbaImage;
+use vulkano::buffer::{CpuAccessibleBuffer, BufferUsage};
+use vulkano::buffer::cpu_access::ReadLockError;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, BeginRenderPassError, ExecuteCommandsError, AutoCommandBufferBuilderContextError, CopyBufferImageError, BuildError, CommandBufferExecError};
+use vulkano::framebuffer::{Framebuffer, FramebufferAbstract, FramebufferCreationError, Subpass};
+use vulkano::image::{AttachmentImage, ImageUsage, ImageAccess, ImageCreationError};
+use vulkano::memory::DeviceMemoryAllocError;
+use vulkano::sync::{GpuFuture, FlushError};
+use vulkano::sync;
+use vulkano::format;
+use vulkano::OomError;
+use cgmath::{Matrix4, Vector3, InnerSpace, SquareMatrix, Deg, Rad, perspective};
+
+use crate::renderer::{Renderer, RenderError, LightUniform, CLIP};
+use crate::renderer::model::Model;
+use crate::renderer::secondary::ModelRecorder;
+use crate::renderer::eye::{self, Eye, EyeVelocity};
+
+const THUMBNAIL_FOV: Deg<f32> = Deg(30.0);
+// From the front, a bit to the right and above, like product shots
+const THUMBNAIL_DIRECTION: Vector3<f32> = Vector3::new(0.5, 0.4, 1.0);
+
+impl Renderer {
+	// Square preview of `model` in its current pose, framed by its bounding sphere, e.g. for an asset browser.
+	// `background` is linear like the clear color, an alpha of 0 leaves it transparent. Uses the model pipeline and the
+	// light of the scene without shadows, and waits for the GPU like the captures do.
+	pub fn render_thumbnail(&mut self, model: &Model, size: u32, background: [f32; 4]) -> Result<RgbaImage, ThumbnailError> {
+		let sphere = model.bounding_sphere().ok_or(ThumbnailError::NotLoaded)?;
+		let size = size.max(1);
+		let dimensions = [size, size];
+		
+		// Far enough for the whole sphere to fit the field of view, with the depth range hugging it
+		let radius = sphere.radius.max(0.001);
+		let distance = radius / (Rad::from(THUMBNAIL_FOV).0 * 0.5).sin();
+		let camera = sphere.center + THUMBNAIL_DIRECTION.normalize() * distance;
+		let view = Matrix4::look_at(camera, sphere.center, Vector3::unit_y());
+		let projection = CLIP * perspective(THUMBNAIL_FOV, 1.0, (distance - radius) * 0.5, distance + radius * 2.0);
+		
+		// Same formats and samples as the eyes, so the render pass of the model pipelines can be reused
+		let render_pass = self.pipeline.render_pass().clone();
+		let scene_format = self.eyes.0.hdr.as_ref().map_or(eye::IMAGE_FORMAT, |hdr| hdr.image.format());
+		let msaa_image = if self.msaa > 1 {
+			Some(AttachmentImage::transient_multisampled(self.device.clone(), dimensions, self.msaa, scene_format)?)
+		} else {
+			None
+		};
+		let depth_image = AttachmentImage::transient_multisampled(self.device.clone(), dimensions, self.msaa, self.eyes.0.depth_image.format())?;
+		let velocity = EyeVelocity::new(&self.device, dimensions, self.msaa)?;
+		let image = AttachmentImage::with_usage(self.device.clone(),
+		                                        dimensions,
+		                                        format::R8G8B8A8Srgb,
+		                                        ImageUsage { transfer_source: true, ..ImageUsage::none() })?;
+		
+		// With HDR the scene goes through the tonemap pass like the eyes do
+		let (frame_buffer, hdr) = match &self.tonemap {
+			Some(tonemap) => {
+				let hdr_image = AttachmentImage::sampled(self.device.clone(), dimensions, scene_format)?;
+				let tonemap_frame_buffer: Arc<dyn FramebufferAbstract + Send + Sync> = Arc::new(Framebuffer::start(tonemap.render_pass.clone())
+				                                                                                   .add(image.clone())?
+				                                                                                   .build()?);
+				(Eye::frame_buffer(&render_pass, &msaa_image, &depth_image, &velocity, &hdr_image)?, Some((hdr_image, tonemap_frame_buffer)))
+			},
+			None => (Eye::frame_buffer(&render_pass, &msaa_image, &depth_image, &velocity, &image)?, None),
+		};
+		
+		// Maps everything behind the shadow map's far plane, which the fragment shader treats as lit
+		let unshadowed = Matrix4::from_translation(Vector3::new(0.0, 0.0, 2.0)) * Matrix4::from_scale(0.0);
+		let light = self.light_buffer.next(LightUniform { direction: self.light_direction.into(),
+		                                                  ambient: self.ambient,
+		                                                  pv: unshadowed.into() })?;
+		
+		let (set, _) = self.eye_sets(light, projection * view, projection * view)?;
+		let joint_set = self.joint_set(model.joints().unwrap_or(&[]))?;
+		// Nothing moves in a still image
+		let identity = Matrix4::identity();
+		let transforms = self.transform_set(iter::once((&identity, &identity)))?;
+		let dynamic_state = self.dynamic_state(dimensions);
+		let pipeline = match &model.indices {
+			None => &self.points_pipeline,
+			Some(_) if model.transparent() => &self.transparent_pipeline,
+			Some(_) => &self.pipeline,
+		};
+		
+		let mut draw = ModelRecorder::new(&self.device, self.queue.family(), Subpass::from(render_pass.clone(), 0).unwrap(), false, &dynamic_state)?;
+		draw.draw(pipeline, model, vec![model.set.clone(), set, joint_set], &transforms, 0, true)?;
+		let draw = draw.build()?;
+		
+		let buffer = CpuAccessibleBuffer::from_iter(self.device.clone(),
+		                                            BufferUsage::transfer_destination(),
+		                                            true,
+		                                            (0 .. size * size * 4).map(|_| 0u8))?;
+		
+		let command_buffer = AutoCommandBufferBuilder::primary_one_time_submit(self.device.clone(), self.queue.family())?
+		                                              .begin_render_pass(frame_buffer, true, self.clear_values(background))?;
+		
+		// Unsafe because vulkano doesn't synchronize secondary command buffers, what the model uses doesn't change once
+		// uploaded and everything else is only used by this frame
+		let command_buffer = unsafe { command_buffer.execute_commands(draw)? };
+		
+		let mut command_buffer = command_buffer.end_render_pass()?;
+		
+		if let Some((hdr_image, tonemap_frame_buffer)) = hdr {
+			command_buffer = self.tonemap_into(command_buffer, &hdr_image, tonemap_frame_buffer, &dynamic_state)?;
+		}
+		
+		let command_buffer = command_buffer.copy_image_to_buffer(image, buffer.clone())?
+		                                   .build()?;
+		
+		// Queued behind the frames in flight, the eye images and everything else they use are left alone
+		let future = self.previous_frame_end.take()
+		                                    .unwrap()
+		                                    .then_execute(self.queue.clone(), command_buffer)?
+		                                    .then_signal_fence_and_flush();
+		
+		self.previous_frame_end = Some(Box::new(sync::now(self.device.clone())) as Box<_>);
+		
+		future?.wait(None)?;
+		
+		let pixels = buffer.read()?.to_vec();
+		Ok(RgbaImage::from_raw(size, size, pixels).unwrap())
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum ThumbnailError {
+	#[error(display = "Model hasn't finished loading")] NotLoaded,
+	#[error(display = "{}", _0)] RenderError(#[error(source)] RenderError),
+	#[error(display = "{}", _0)] ImageCreationError(#[error(source)] ImageCreationError),
+	#[error(display = "{}", _0)] FramebufferCreationError(#[error(source)] FramebufferCreationError),
+	#[error(display = "{}", _0)] DeviceMemoryAllocError(#[error(source)] DeviceMemoryAllocError),
+	#[error(display = "{}", _0)] OomError(#[error(source)] OomError),
+	#[error(display = "{}", _0)] BeginRenderPassError(#[error(source)] BeginRenderPassError),
+	#[error(display = "{}", _0)] ExecuteCommandsError(#[error(source)] ExecuteCommandsError),
+	#[error(display = "{}", _0)] AutoCommandBufferBuilderContextError(#[error(source)] AutoCommandBufferBuilderContextError),
+	#[error(display = "{}", _0)] CopyBufferImageError(#[error(source)] CopyBufferImageError),
+	#[error(display = "{}", _0)] BuildError(#[error(source)] BuildError),
+	#[error(display = "{}", _0)] CommandBufferExecError(#[error(source)] CommandBufferExecError),
+	#[error(display = "{}", _0)] FlushError(#[error(source)] FlushError),
+	#[error(display = "{}", _0)] ReadLockError(#[error(source)] ReadLockError),
+}
@@ -0,0 +1,52 @@
+use std::time::{Duration, Instant};
+
+const SAMPLES: usize = 32;
+
+// Wall clock time between submits, smoothed over the last few frames
+pub struct FrameTimer {
+	last: Option<Instant>,
+	samples: [Duration; SAMPLES],
+	next: usize,
+	count: usize,
+}
+
+impl FrameTimer {
+	pub fn new() -> FrameTimer {
+		FrameTimer {
+			last: None,
+			samples: [Duration::from_secs(0); SAMPLES],
+			next: 0,
+			count: 0,
+		}
+	}
+	
+	pub fn tick(&mut self) {
+		let now = Instant::now();
+		
+		if let Some(last) = self.last.replace(now) {
+			self.samples[self.next] = now - last;
+			self.next = (self.next + 1) % SAMPLES;
+			self.count = (self.count + 1).min(SAMPLES);
+		}
+	}
+	
+	// Zero until two frames have been submitted
+	pub fn last_frame_time(&self) -> Duration {
+		if self.count == 0 {
+			return Duration::from_secs(0);
+		}
+		
+		self.samples[(self.next + SAMPLES - 1) % SAMPLES]
+	}
+	
+	pub fn fps(&self) -> f32 {
+		// Until the ring is full the samples are all at the front
+		let total: Duration = self.samples[..self.count].iter().sum();
+		
+		if total == Duration::from_secs(0) {
+			0.0
+		} else {
+			self.count as f32 / total.as_secs_f32()
+		}
+	}
+}
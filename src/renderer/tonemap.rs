@@ -0,0 +1,94 @@
+use std::sync::Arc;
+use err_derive::Error;
+use vulkano::device::Device;
+use vulkano::framebuffer::{Subpass, RenderPassAbstract, RenderPassCreationError};
+use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineCreationError};
+use vulkano::pipeline::vertex::BufferlessDefinition;
+use vulkano::sampler::{Sampler, Filter, MipmapMode, SamplerAddressMode, SamplerCreationError};
+use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::format::Format;
+use vulkano::OomError;
+
+use crate::shaders;
+use crate::renderer::eye;
+
+// Half floats keep enough range for bright skies and highlights, and every device can render to and blend them
+pub const HDR_FORMAT: Format = Format::R16G16B16A16Sfloat;
+
+type TonemapPipelineType = GraphicsPipeline<
+	BufferlessDefinition,
+	Box<dyn PipelineLayoutAbstract + Send + Sync>,
+	Arc<dyn RenderPassAbstract + Send + Sync>
+>;
+
+// Full screen pass mapping an eye's HDR image to the displayable range with the ACES filmic curve, on its way into
+// the image that gets submitted, or into the FXAA input when that runs too.
+pub struct Tonemap {
+	pub render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+	pub pipeline: Arc<TonemapPipelineType>,
+	// Nearest, the source has the same size as the destination
+	pub sampler: Arc<Sampler>,
+}
+
+impl Tonemap {
+	pub fn new(device: &Arc<Device>) -> Result<Tonemap, TonemapError> {
+		let vs = shaders::fxaa_vert::Shader::load(device.clone()).map_err(TonemapError::ShaderLoadError)?;
+		let fs = shaders::tonemap_frag::Shader::load(device.clone()).map_err(TonemapError::ShaderLoadError)?;
+		
+		// Every pixel of the rendered region is overwritten, so there is nothing to load or clear
+		let render_pass: Arc<dyn RenderPassAbstract + Send + Sync> = Arc::new(
+			vulkano::single_pass_renderpass!(device.clone(),
+				attachments: {
+					color: {
+						load: DontCare,
+						store: Store,
+						format: eye::IMAGE_FORMAT,
+						samples: 1,
+					}
+				},
+				pass: {
+					color: [color],
+					depth_stencil: {}
+				}
+			)?
+		);
+		
+		// Same full screen triangle as FXAA
+		let pipeline = Arc::new(
+			GraphicsPipeline::start()
+			                 .vertex_input(BufferlessDefinition)
+			                 .vertex_shader(vs.main_entry_point(), ())
+			                 .viewports_dynamic_scissors_irrelevant(1)
+			                 .fragment_shader(fs.main_entry_point(), ())
+			                 .cull_mode_disabled()
+			                 .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+			                 .build(device.clone())?
+		);
+		
+		let sampler = Sampler::new(device.clone(),
+		                           Filter::Nearest,
+		                           Filter::Nearest,
+		                           MipmapMode::Nearest,
+		                           SamplerAddressMode::ClampToEdge,
+		                           SamplerAddressMode::ClampToEdge,
+		                           SamplerAddressMode::ClampToEdge,
+		                           0.0,
+		                           1.0,
+		                           0.0,
+		                           0.0)?;
+		
+		Ok(Tonemap {
+			render_pass,
+			pipeline,
+			sampler,
+		})
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum TonemapError {
+	#[error(display = "Failed to load shader: {}", _0)] ShaderLoadError(#[error(source, no_from)] OomError),
+	#[error(display = "{}", _0)] RenderPassCreationError(#[error(source)] RenderPassCreationError),
+	#[error(display = "{}", _0)] GraphicsPipelineCreationError(#[error(source)] GraphicsPipelineCreationError),
+	#[error(display = "{}", _0)] SamplerCreationError(#[error(source)] SamplerCreationError),
+}
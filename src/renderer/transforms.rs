@@ -0,0 +1,135 @@
+use std::iter;
+use std::sync::Arc;
+use vulkano::buffer::{CpuBufferPool, BufferAccess, BufferSlice};
+use vulkano::buffer::cpu_pool::CpuBufferPoolChunk;
+use vulkano::descriptor::descriptor::DescriptorDesc;
+use vulkano::descriptor::descriptor_set::{DescriptorSet, DescriptorSetDesc, UnsafeDescriptorSet, UnsafeDescriptorSetLayout, DescriptorWrite,
+                                          DescriptorPool, DescriptorPoolAlloc, StdDescriptorPoolAlloc};
+use vulkano::device::{Device, DeviceOwned};
+use vulkano::image::ImageViewAccess;
+use vulkano::memory::pool::StdMemoryPool;
+use cgmath::{Matrix4, Matrix3, Matrix, SquareMatrix};
+
+use crate::renderer::RenderError;
+
+// vec4s in the Transform block of the shaders, a mat4 and a mat3 whose columns are padded to 16 bytes, then the model
+// matrix of the previous frame
+pub const TRANSFORM_SIZE: usize = 11;
+
+// Model, normal and previous model matrices of every model in the frame, packed into one buffer from a ring of them. Draws bind the
+// same descriptor set and only change its dynamic offset.
+pub struct Transforms {
+	buffer: CpuBufferPool<[f32; 4]>,
+	// Distance between two transforms in vec4s, dynamic offsets have to be aligned
+	stride: usize,
+}
+
+// vulkano 0.18 only writes plain uniform buffers into its descriptor sets, this one points at the transforms with a
+// dynamic descriptor
+pub struct TransformSet {
+	inner: StdDescriptorPoolAlloc,
+	layout: Arc<UnsafeDescriptorSetLayout>,
+	buffer: CpuBufferPoolChunk<[f32; 4], Arc<StdMemoryPool>>,
+	stride: usize,
+}
+
+impl Transforms {
+	pub fn new(device: &Arc<Device>) -> Transforms {
+		let alignment = device.physical_device().limits().min_uniform_buffer_offset_alignment() as usize;
+		let size = TRANSFORM_SIZE * 16;
+		
+		Transforms {
+			buffer: CpuBufferPool::uniform_buffer(device.clone()),
+			stride: (size + alignment - 1) / alignment * alignment / 16,
+		}
+	}
+	
+	// The transform of the nth pair of current and previous matrices is read at `TransformSet::offset(n)`. `layout` is
+	// set 3 of the model pipelines.
+	pub fn set<'a, I>(&self, layout: &Arc<UnsafeDescriptorSetLayout>, matrices: I) -> Result<Arc<TransformSet>, RenderError>
+	                 where I: IntoIterator<Item = (&'a Matrix4<f32>, &'a Matrix4<f32>)> {
+		let mut data = Vec::new();
+		for (matrix, previous) in matrices {
+			data.extend_from_slice(&transform(matrix, previous));
+			data.resize(data.len() + self.stride - TRANSFORM_SIZE, [0.0; 4]);
+		}
+		
+		// The descriptor needs something to point at even if nothing is drawn
+		data.resize(data.len().max(TRANSFORM_SIZE), [0.0; 4]);
+		
+		let buffer = self.buffer.chunk(data)?;
+		let mut inner = Device::standard_descriptor_pool(layout.device()).alloc(layout)?;
+		
+		// Dynamic offsets are added to the start of the range, which only covers a single transform
+		unsafe {
+			let range = BufferSlice::from_typed_buffer_access(buffer.clone()).slice(0 .. TRANSFORM_SIZE).unwrap();
+			inner.inner_mut().write(layout.device(), iter::once(DescriptorWrite::dynamic_uniform_buffer(0, 0, &range)));
+		}
+		
+		Ok(Arc::new(TransformSet {
+			inner,
+			layout: layout.clone(),
+			buffer,
+			stride: self.stride,
+		}))
+	}
+}
+
+impl TransformSet {
+	pub fn offset(&self, index: usize) -> u32 {
+		(index * self.stride * 16) as u32
+	}
+}
+
+// Normals use the inverse transpose of the model matrix so non-uniform scale doesn't skew them
+pub fn transform(matrix: &Matrix4<f32>, previous: &Matrix4<f32>) -> [[f32; 4]; TRANSFORM_SIZE] {
+	let normal = Matrix3::from_cols(matrix.x.truncate(), matrix.y.truncate(), matrix.z.truncate())
+	                     .invert()
+	                     .unwrap_or_else(Matrix3::identity)
+	                     .transpose();
+	
+	[matrix.x.into(), matrix.y.into(), matrix.z.into(), matrix.w.into(),
+	 normal.x.extend(0.0).into(), normal.y.extend(0.0).into(), normal.z.extend(0.0).into(),
+	 previous.x.into(), previous.y.into(), previous.z.into(), previous.w.into()]
+}
+
+unsafe impl DescriptorSet for TransformSet {
+	fn inner(&self) -> &UnsafeDescriptorSet {
+		self.inner.inner()
+	}
+	
+	fn num_buffers(&self) -> usize {
+		1
+	}
+	
+	fn buffer(&self, index: usize) -> Option<(&dyn BufferAccess, u32)> {
+		match index {
+			0 => Some((&self.buffer, 0)),
+			_ => None,
+		}
+	}
+	
+	fn num_images(&self) -> usize {
+		0
+	}
+	
+	fn image(&self, _index: usize) -> Option<(&dyn ImageViewAccess, u32)> {
+		None
+	}
+}
+
+unsafe impl DescriptorSetDesc for TransformSet {
+	fn num_bindings(&self) -> usize {
+		self.layout.num_bindings()
+	}
+	
+	fn descriptor(&self, binding: usize) -> Option<DescriptorDesc> {
+		self.layout.descriptor(binding)
+	}
+}
+
+unsafe impl DeviceOwned for TransformSet {
+	fn device(&self) -> &Arc<Device> {
+		self.layout.device()
+	}
+}
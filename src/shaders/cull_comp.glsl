@@ -0,0 +1,61 @@
+#version 450
+
+layout(local_size_x = 64) in;
+
+// Bounding sphere in model space and the flags below, see culling.rs
+struct Cullable {
+	mat4 model;
+	vec4 sphere;
+	uint flags;
+};
+
+// Models without bounds are never outside the frustums
+const uint BOUNDED = 1u;
+const uint OCCLUDED = 2u;
+
+layout(set = 0, binding = 0) readonly buffer Models {
+	Cullable models[];
+};
+
+// Inward facing planes of the left eye's frustum, then the right eye's
+layout(set = 0, binding = 1) uniform Frustums {
+	vec4 planes[12];
+	uint count;
+} frustums;
+
+// Bit 0 is set for models the left eye sees, bit 1 for the right eye
+layout(set = 0, binding = 2) writeonly buffer Visibility {
+	uint visible[];
+};
+
+bool inside(vec3 center, float radius, uint first) {
+	for(uint i = first; i < first + 6u; i++) {
+		if(dot(frustums.planes[i].xyz, center) + frustums.planes[i].w < -radius) return false;
+	}
+	
+	return true;
+}
+
+void main() {
+	uint index = gl_GlobalInvocationID.x;
+	if(index >= frustums.count) return;
+	
+	Cullable cullable = models[index];
+	
+	if((cullable.flags & OCCLUDED) != 0u) {
+		visible[index] = 0u;
+		return;
+	}
+	
+	if((cullable.flags & BOUNDED) == 0u) {
+		visible[index] = 3u;
+		return;
+	}
+	
+	// Same as BoundingSphere::transform, scaled by the longest axis
+	vec3 center = (cullable.model * vec4(cullable.sphere.xyz, 1.0)).xyz;
+	float scale = max(length(cullable.model[0].xyz), max(length(cullable.model[1].xyz), length(cullable.model[2].xyz)));
+	float radius = cullable.sphere.w * scale;
+	
+	visible[index] = (inside(center, radius, 0u) ? 1u : 0u) | (inside(center, radius, 6u) ? 2u : 0u);
+}
@@ -0,0 +1,36 @@
+#version 450
+
+layout(location = 0) in vec3 pos;
+layout(location = 5) in uvec4 joints;
+layout(location = 6) in vec4 weights;
+
+layout(set = 0, binding = 0) uniform View {
+	mat4 pv;
+} view;
+
+layout(set = 1, binding = 0) uniform Joints {
+	mat4 matrices[64];
+} skin;
+
+// Same block as set 3 of vert.glsl, the same descriptor set is bound to both
+layout(set = 2, binding = 0) uniform Transform {
+	mat4 model;
+	mat3 normal;
+} transform;
+
+// Has to come out exactly like vert.glsl, the color pass after the prepass only draws where the depth is equal
+invariant gl_Position;
+
+mat4 skin_matrix() {
+	if(weights == vec4(0.0)) return mat4(1.0);
+	
+	return weights.x * skin.matrices[joints.x]
+	     + weights.y * skin.matrices[joints.y]
+	     + weights.z * skin.matrices[joints.z]
+	     + weights.w * skin.matrices[joints.w];
+}
+
+void main() {
+	vec4 world = transform.model * (skin_matrix() * vec4(pos, 1.0));
+	gl_Position = view.pv * world;
+}
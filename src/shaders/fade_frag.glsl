@@ -0,0 +1,14 @@
+#version 450
+
+layout(location = 0) out vec4 f_color;
+layout(location = 1) out vec4 f_velocity;
+
+layout(push_constant) uniform Fade {
+	float alpha;
+};
+
+// Blended over the whole frame, the velocities fade out along with the image
+void main() {
+	f_color = vec4(0.0, 0.0, 0.0, alpha);
+	f_velocity = vec4(0.0, 0.0, 0.0, alpha);
+}
@@ -1,10 +1,81 @@
 #version 450
 
 layout(location = 0) in vec2 tex_coords;
+layout(location = 1) in vec3 world_normal;
+layout(location = 2) in vec4 vertex_color;
+layout(location = 3) in vec4 world_tangent;
+layout(location = 4) in vec4 light_space;
+layout(location = 5) in vec4 current_position;
+layout(location = 6) in vec4 previous_position;
 layout(location = 0) out vec4 f_color;
+// Motion since the previous frame in 0..1 image coordinates, alpha blends it like the color for transparent models
+layout(location = 1) out vec4 f_velocity;
 
 layout(set = 0, binding = 0) uniform sampler2D tex;
+layout(set = 0, binding = 1) uniform sampler2D normal_map;
+layout(set = 0, binding = 2) uniform sampler2D emissive_map;
+
+// Direction the light travels in, in world space
+layout(set = 1, binding = 0) uniform Light {
+	vec3 direction;
+	float ambient;
+	mat4 pv;
+} light;
+
+layout(set = 1, binding = 1) uniform sampler2DShadow shadow_map;
+
+// Only the rgb of the emissive color is used
+layout(push_constant) uniform Material {
+	vec4 color;
+	vec4 emissive;
+} material;
+
+// Keeps surfaces from shadowing themselves, in shadow map depth
+const float SHADOW_BIAS = 0.002;
+
+// 3x3 percentage closer filtering, 1 is fully lit. Nothing outside the shadow map casts shadows.
+float shadow() {
+	vec3 coords = light_space.xyz / light_space.w;
+	
+	if(any(greaterThan(abs(coords.xy), vec2(1.0))) || coords.z < 0.0 || coords.z > 1.0) {
+		return 1.0;
+	}
+	
+	vec2 uv = coords.xy * 0.5 + 0.5;
+	vec2 texel = 1.0 / vec2(textureSize(shadow_map, 0));
+	float lit = 0.0;
+	
+	for(int x = -1; x <= 1; x++) {
+		for(int y = -1; y <= 1; y++) {
+			lit += texture(shadow_map, vec3(uv + vec2(x, y) * texel, coords.z - SHADOW_BIAS));
+		}
+	}
+	
+	return lit / 9.0;
+}
 
 void main() {
-	f_color = texture(tex, tex_coords);
+	vec4 color = texture(tex, tex_coords) * vertex_color * material.color;
+	float diffuse = 1.0;
+	
+	if(length(world_normal) > 0.0) {
+		vec3 normal = normalize(world_normal);
+		
+		if(length(world_tangent.xyz) > 0.0) {
+			vec3 tangent = normalize(world_tangent.xyz - normal * dot(normal, world_tangent.xyz));
+			vec3 bitangent = cross(normal, tangent) * world_tangent.w;
+			vec3 perturbed = texture(normal_map, tex_coords).xyz * 2.0 - 1.0;
+			normal = normalize(mat3(tangent, bitangent, normal) * perturbed);
+		}
+		
+		diffuse = max(dot(normal, -light.direction), 0.0) * shadow();
+	}
+	
+	// Glows the same whether it's lit or not
+	vec3 emissive = texture(emissive_map, tex_coords).rgb * material.emissive.rgb;
+	f_color = vec4(color.rgb * min(light.ambient + diffuse, 1.0) + emissive, color.a);
+	
+	vec2 current = current_position.xy / current_position.w;
+	vec2 previous = previous_position.xy / previous_position.w;
+	f_velocity = vec4((current - previous) * 0.5, 0.0, color.a);
 }
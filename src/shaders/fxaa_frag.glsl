@@ -0,0 +1,54 @@
+#version 450
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler2D scene;
+
+// Along the lines of the console version of FXAA 3.11
+const float EDGE_THRESHOLD = 1.0 / 8.0;
+const float EDGE_THRESHOLD_MIN = 1.0 / 32.0;
+const float REDUCE_MUL = 1.0 / 8.0;
+const float REDUCE_MIN = 1.0 / 128.0;
+const float SPAN_MAX = 8.0;
+
+// The sRGB image is sampled as linear, edges are found on perceived brightness instead
+float luma(vec3 color) {
+	return sqrt(dot(color, vec3(0.299, 0.587, 0.114)));
+}
+
+void main() {
+	// Pixel coordinates, so the top left region rendered with adaptive resolution maps onto itself
+	vec2 texel = 1.0 / vec2(textureSize(scene, 0));
+	vec2 uv = gl_FragCoord.xy * texel;
+	
+	vec4 center = texture(scene, uv);
+	float m = luma(center.rgb);
+	float nw = luma(texture(scene, uv + vec2(-0.5, -0.5) * texel).rgb);
+	float ne = luma(texture(scene, uv + vec2( 0.5, -0.5) * texel).rgb);
+	float sw = luma(texture(scene, uv + vec2(-0.5,  0.5) * texel).rgb);
+	float se = luma(texture(scene, uv + vec2( 0.5,  0.5) * texel).rgb);
+	
+	float luma_min = min(m, min(min(nw, ne), min(sw, se)));
+	float luma_max = max(m, max(max(nw, ne), max(sw, se)));
+	
+	// Flat areas are left alone
+	if (luma_max - luma_min < max(EDGE_THRESHOLD_MIN, luma_max * EDGE_THRESHOLD)) {
+		f_color = center;
+		return;
+	}
+	
+	// Blur along the edge, perpendicular to the luma gradient
+	vec2 dir = vec2((sw + se) - (nw + ne), (nw + sw) - (ne + se));
+	float reduce = max((nw + ne + sw + se) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+	float scale = 1.0 / (min(abs(dir.x), abs(dir.y)) + reduce);
+	dir = clamp(dir * scale, -SPAN_MAX, SPAN_MAX) * texel;
+	
+	vec3 inner = 0.5 * (texture(scene, uv + dir * (1.0 / 3.0 - 0.5)).rgb
+	                  + texture(scene, uv + dir * (2.0 / 3.0 - 0.5)).rgb);
+	vec3 outer = 0.5 * inner + 0.25 * (texture(scene, uv - dir * 0.5).rgb
+	                                 + texture(scene, uv + dir * 0.5).rgb);
+	
+	// The wider blur crossed another edge if it went out of the local range
+	float luma_outer = luma(outer);
+	f_color = vec4(luma_outer < luma_min || luma_outer > luma_max ? inner : outer, center.a);
+}
@@ -0,0 +1,5 @@
+#version 450
+
+// Color writes are masked off, the pipeline only marks the stencil
+void main() {
+}
@@ -0,0 +1,8 @@
+#version 450
+
+layout(location = 0) in vec3 pos;
+
+void main() {
+	// Already in clip space. At the near plane, so it also masks through depth when there is no stencil.
+	gl_Position = vec4(pos.xy, 0.0, 1.0);
+}
@@ -0,0 +1,11 @@
+#version 450
+
+layout(location = 0) in vec4 line_color;
+layout(location = 0) out vec4 f_color;
+layout(location = 1) out vec4 f_velocity;
+
+void main() {
+	f_color = line_color;
+	// Overlays aren't reprojected, they fade out the motion of what's behind them as they blend over it
+	f_velocity = vec4(0.0, 0.0, 0.0, line_color.a);
+}
@@ -0,0 +1,15 @@
+#version 450
+
+layout(location = 0) in vec3 pos;
+layout(location = 1) in vec4 color;
+layout(location = 0) out vec4 line_color;
+
+layout(set = 0, binding = 0) uniform View {
+	mat4 pv;
+} view;
+
+// Lines are already in world space
+void main() {
+	gl_Position = view.pv * vec4(pos, 1.0);
+	line_color = color;
+}
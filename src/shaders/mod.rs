@@ -12,3 +12,130 @@ pub mod frag {
 		path: "src/shaders/frag.glsl"
 	}
 }
+
+pub mod skybox_vert {
+	vulkano_shaders::shader! {
+		ty: "vertex",
+		path: "src/shaders/skybox_vert.glsl"
+	}
+}
+
+pub mod skybox_frag {
+	vulkano_shaders::shader! {
+		ty: "fragment",
+		path: "src/shaders/skybox_frag.glsl"
+	}
+}
+
+pub mod hidden_area_vert {
+	vulkano_shaders::shader! {
+		ty: "vertex",
+		path: "src/shaders/hidden_area_vert.glsl"
+	}
+}
+
+pub mod hidden_area_frag {
+	vulkano_shaders::shader! {
+		ty: "fragment",
+		path: "src/shaders/hidden_area_frag.glsl"
+	}
+}
+
+pub mod fade_frag {
+	vulkano_shaders::shader! {
+		ty: "fragment",
+		path: "src/shaders/fade_frag.glsl"
+	}
+}
+
+#[cfg(feature = "indirect-draw")]
+pub mod indirect_vert {
+	vulkano_shaders::shader! {
+		ty: "vertex",
+		path: "src/shaders/indirect_vert.glsl"
+	}
+}
+
+pub mod depth_vert {
+	vulkano_shaders::shader! {
+		ty: "vertex",
+		path: "src/shaders/depth_vert.glsl"
+	}
+}
+
+pub mod shadow_vert {
+	vulkano_shaders::shader! {
+		ty: "vertex",
+		path: "src/shaders/shadow_vert.glsl"
+	}
+}
+
+pub mod shadow_frag {
+	vulkano_shaders::shader! {
+		ty: "fragment",
+		path: "src/shaders/shadow_frag.glsl"
+	}
+}
+
+pub mod occlusion_vert {
+	vulkano_shaders::shader! {
+		ty: "vertex",
+		path: "src/shaders/occlusion_vert.glsl"
+	}
+}
+
+pub mod fxaa_vert {
+	vulkano_shaders::shader! {
+		ty: "vertex",
+		path: "src/shaders/fxaa_vert.glsl"
+	}
+}
+
+pub mod fxaa_frag {
+	vulkano_shaders::shader! {
+		ty: "fragment",
+		path: "src/shaders/fxaa_frag.glsl"
+	}
+}
+
+pub mod line_vert {
+	vulkano_shaders::shader! {
+		ty: "vertex",
+		path: "src/shaders/line_vert.glsl"
+	}
+}
+
+pub mod line_frag {
+	vulkano_shaders::shader! {
+		ty: "fragment",
+		path: "src/shaders/line_frag.glsl"
+	}
+}
+
+pub mod text_vert {
+	vulkano_shaders::shader! {
+		ty: "vertex",
+		path: "src/shaders/text_vert.glsl"
+	}
+}
+
+pub mod text_frag {
+	vulkano_shaders::shader! {
+		ty: "fragment",
+		path: "src/shaders/text_frag.glsl"
+	}
+}
+
+pub mod tonemap_frag {
+	vulkano_shaders::shader! {
+		ty: "fragment",
+		path: "src/shaders/tonemap_frag.glsl"
+	}
+}
+
+pub mod cull_comp {
+	vulkano_shaders::shader! {
+		ty: "compute",
+		path: "src/shaders/cull_comp.glsl"
+	}
+}
@@ -0,0 +1,12 @@
+#version 450
+
+layout(location = 0) in vec3 pos;
+
+// Bounding box of a model, mapped from the -1..1 cube straight to clip space
+layout(push_constant) uniform Mats {
+	mat4 mvp;
+} mats;
+
+void main() {
+	gl_Position = mats.mvp * vec4(pos, 1.0);
+}
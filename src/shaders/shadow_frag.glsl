@@ -0,0 +1,5 @@
+#version 450
+
+// Shared by the shadow pass, the depth prepass and the occlusion queries, none of them write any color
+void main() {
+}
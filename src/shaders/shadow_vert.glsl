@@ -0,0 +1,27 @@
+#version 450
+
+layout(location = 0) in vec3 pos;
+layout(location = 5) in uvec4 joints;
+layout(location = 6) in vec4 weights;
+
+// Skinned like vert.glsl, so animated models cast matching shadows
+layout(set = 0, binding = 0) uniform Joints {
+	mat4 matrices[64];
+} skin;
+
+layout(push_constant) uniform Mats {
+	mat4 light_mvp;
+} mats;
+
+mat4 skin_matrix() {
+	if(weights == vec4(0.0)) return mat4(1.0);
+	
+	return weights.x * skin.matrices[joints.x]
+	     + weights.y * skin.matrices[joints.y]
+	     + weights.z * skin.matrices[joints.z]
+	     + weights.w * skin.matrices[joints.w];
+}
+
+void main() {
+	gl_Position = mats.light_mvp * (skin_matrix() * vec4(pos, 1.0));
+}
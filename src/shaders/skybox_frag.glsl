@@ -0,0 +1,15 @@
+#version 450
+
+layout(location = 0) in vec3 direction;
+layout(location = 1) in vec4 current_position;
+layout(location = 2) in vec4 previous_position;
+layout(location = 0) out vec4 f_color;
+layout(location = 1) out vec4 f_velocity;
+
+layout(set = 0, binding = 0) uniform samplerCube sky;
+
+void main() {
+	f_color = texture(sky, direction);
+	// The sky only turns with the head, see frag.glsl for the units
+	f_velocity = vec4((current_position.xy / current_position.w - previous_position.xy / previous_position.w) * 0.5, 0.0, 1.0);
+}
@@ -0,0 +1,19 @@
+#version 450
+
+layout(location = 0) in vec3 pos;
+layout(location = 0) out vec3 direction;
+layout(location = 1) out vec4 current_position;
+layout(location = 2) out vec4 previous_position;
+
+layout(push_constant) uniform Mats {
+	mat4 pv;
+	mat4 previous_pv;
+} mats;
+
+void main() {
+	direction = pos;
+	// z = w puts every fragment on the far plane, behind the rest of the scene
+	gl_Position = (mats.pv * vec4(pos, 1.0)).xyww;
+	current_position = gl_Position;
+	previous_position = mats.previous_pv * vec4(pos, 1.0);
+}
@@ -0,0 +1,15 @@
+#version 450
+
+layout(location = 0) in vec2 glyph_uv;
+layout(location = 0) out vec4 f_color;
+layout(location = 1) out vec4 f_velocity;
+
+layout(set = 1, binding = 0) uniform sampler2D atlas;
+
+// The atlas only has coverage, the background of a glyph is cut out instead of blended
+void main() {
+	if(texture(atlas, glyph_uv).r < 0.5) discard;
+	
+	f_color = vec4(1.0);
+	f_velocity = vec4(0.0);
+}
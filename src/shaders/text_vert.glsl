@@ -0,0 +1,15 @@
+#version 450
+
+layout(location = 0) in vec3 pos;
+layout(location = 1) in vec2 uv;
+layout(location = 0) out vec2 glyph_uv;
+
+layout(set = 0, binding = 0) uniform View {
+	mat4 pv;
+} view;
+
+// Glyph quads are already in world space
+void main() {
+	gl_Position = view.pv * vec4(pos, 1.0);
+	glyph_uv = uv;
+}
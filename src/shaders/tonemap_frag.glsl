@@ -0,0 +1,29 @@
+#version 450
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler2D scene;
+
+layout(push_constant) uniform Tonemap {
+	// Linear factor, already raised from stops
+	float exposure;
+} tonemap;
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve, colors go in scaled by 0.6 to match the reference curve
+vec3 aces(vec3 x) {
+	const float a = 2.51;
+	const float b = 0.03;
+	const float c = 2.43;
+	const float d = 0.59;
+	const float e = 0.14;
+	return clamp((x * (a * x + b)) / (x * (c * x + d) + e), 0.0, 1.0);
+}
+
+void main() {
+	// Pixel coordinates like FXAA, the region rendered with adaptive resolution maps onto itself
+	vec2 uv = gl_FragCoord.xy / vec2(textureSize(scene, 0));
+	vec4 color = texture(scene, uv);
+	
+	// Still linear, the sRGB target encodes it on the way out
+	f_color = vec4(aces(color.rgb * tonemap.exposure * 0.6), color.a);
+}
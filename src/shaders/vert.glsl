@@ -1,14 +1,71 @@
 #version 450
 
 layout(location = 0) in vec3 pos;
-layout(location = 1) in vec2 uv;
+layout(location = 1) in vec3 normal;
+layout(location = 2) in vec2 tex_coord;
+layout(location = 3) in vec4 color;
+layout(location = 4) in vec4 tangent;
+layout(location = 5) in uvec4 joints;
+layout(location = 6) in vec4 weights;
 layout(location = 0) out vec2 tex_coords;
+layout(location = 1) out vec3 world_normal;
+layout(location = 2) out vec4 vertex_color;
+layout(location = 3) out vec4 world_tangent;
+layout(location = 4) out vec4 light_space;
+// Clip space position in this frame and the last one, for the velocity
+layout(location = 5) out vec4 current_position;
+layout(location = 6) out vec4 previous_position;
 
-layout(push_constant) uniform Mats {
-	mat4 mpv;
-} mats;
+layout(set = 1, binding = 0) uniform Light {
+	vec3 direction;
+	float ambient;
+	mat4 pv;
+} light;
+
+// Projection and view of the eye being rendered, and of the same eye in the previous frame
+layout(set = 1, binding = 2) uniform View {
+	mat4 pv;
+	mat4 previous_pv;
+} view;
+
+// Joint matrices of skinned models, relative to the model matrix. See skin.rs for the size.
+layout(set = 2, binding = 0) uniform Joints {
+	mat4 matrices[64];
+} skin;
+
+// One model out of the frame's transforms, picked with a dynamic offset. See transforms.rs.
+layout(set = 3, binding = 0) uniform Transform {
+	mat4 model;
+	mat3 normal;
+	mat4 previous_model;
+} transform;
+
+// The depth prepass computes the position the same way, see depth_vert.glsl
+invariant gl_Position;
+
+// Unskinned vertices have no weights, the palette bound for them is never read
+mat4 skin_matrix() {
+	if(weights == vec4(0.0)) return mat4(1.0);
+	
+	return weights.x * skin.matrices[joints.x]
+	     + weights.y * skin.matrices[joints.y]
+	     + weights.z * skin.matrices[joints.z]
+	     + weights.w * skin.matrices[joints.w];
+}
 
 void main() {
-	gl_Position = mats.mpv * vec4(pos, 1.0);
-	tex_coords = uv;
+	mat4 skinned = skin_matrix();
+	vec4 world = transform.model * (skinned * vec4(pos, 1.0));
+	gl_Position = view.pv * world;
+	current_position = gl_Position;
+	// Joints only have their current pose, skinned motion relative to the model isn't tracked
+	previous_position = view.previous_pv * (transform.previous_model * (skinned * vec4(pos, 1.0)));
+	light_space = light.pv * world;
+	// Only read by the points pipeline, which would be undefined without it
+	gl_PointSize = 1.0;
+	tex_coords = tex_coord;
+	world_normal = transform.normal * (mat3(skinned) * normal);
+	vertex_color = color;
+	// Tangents lie in the surface, unlike normals they take the model matrix as is
+	world_tangent = vec4(mat3(transform.model) * (mat3(skinned) * tangent.xyz), tangent.w);
 }